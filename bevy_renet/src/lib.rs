@@ -1,8 +1,8 @@
 pub use renet;
 
-use bevy::prelude::*;
+use bevy::{app::AppExit, prelude::*};
 
-use renet::{RenetClient, RenetServer, ServerEvent};
+use renet::{ClientEvent, ClientTransport, RenetClient, RenetServer, ServerEvent, ServerTransport};
 
 #[cfg(feature = "transport")]
 pub mod transport;
@@ -33,12 +33,126 @@ impl RenetServerPlugin {
 
 impl Plugin for RenetClientPlugin {
     fn build(&self, app: &mut App) {
+        app.init_resource::<Events<ClientEvent>>();
         app.add_systems(PreUpdate, Self::update_system.run_if(resource_exists::<RenetClient>()));
     }
 }
 
 impl RenetClientPlugin {
-    pub fn update_system(mut client: ResMut<RenetClient>, time: Res<Time>) {
+    pub fn update_system(mut client: ResMut<RenetClient>, time: Res<Time>, mut client_events: EventWriter<ClientEvent>) {
         client.update(time.delta());
+
+        while let Some(event) = client.get_event() {
+            client_events.send(event);
+        }
+    }
+}
+
+// Systems shared by all transports, the transport plugins add them for their own transport type.
+// The transport errors are sent as the event `E`.
+
+/// Updates the server transport `T`, after [RenetServerPlugin::update_system].
+pub fn server_transport_update_system<T: ServerTransport + Resource, E: Event + From<T::Error>>(
+    mut transport: ResMut<T>,
+    mut server: ResMut<RenetServer>,
+    time: Res<Time>,
+    mut transport_errors: EventWriter<E>,
+) {
+    if let Err(e) = transport.update(time.delta(), &mut server) {
+        transport_errors.send(e.into());
+    }
+}
+
+/// Sends the packets of the server with the server transport `T`.
+pub fn server_transport_send_packets<T: ServerTransport + Resource>(mut transport: ResMut<T>, mut server: ResMut<RenetServer>) {
+    transport.send_packets(&mut server);
+}
+
+/// Disconnects all clients of the server transport `T` when the app exits.
+pub fn server_transport_disconnect_on_exit<T: ServerTransport + Resource>(
+    exit: EventReader<AppExit>,
+    mut transport: ResMut<T>,
+    mut server: ResMut<RenetServer>,
+) {
+    if !exit.is_empty() {
+        transport.disconnect_all(&mut server);
+    }
+}
+
+/// Updates the client transport `T`, after [RenetClientPlugin::update_system].
+pub fn client_transport_update_system<T: ClientTransport + Resource, E: Event + From<T::Error>>(
+    mut transport: ResMut<T>,
+    mut client: ResMut<RenetClient>,
+    time: Res<Time>,
+    mut transport_errors: EventWriter<E>,
+) {
+    if let Err(e) = transport.update(time.delta(), &mut client) {
+        transport_errors.send(e.into());
+    }
+}
+
+/// Sends the packets of the client with the client transport `T`.
+pub fn client_transport_send_packets<T: ClientTransport + Resource, E: Event + From<T::Error>>(
+    mut transport: ResMut<T>,
+    mut client: ResMut<RenetClient>,
+    mut transport_errors: EventWriter<E>,
+) {
+    if let Err(e) = transport.send_packets(&mut client) {
+        transport_errors.send(e.into());
+    }
+}
+
+/// Disconnects the client transport `T` when the app exits.
+pub fn client_transport_disconnect_on_exit<T: ClientTransport + Resource>(exit: EventReader<AppExit>, mut transport: ResMut<T>) {
+    if !exit.is_empty() && !transport.is_disconnected() {
+        transport.disconnect();
+    }
+}
+
+// Run conditions shared by all client transports, the transport modules expose them for their own transport type.
+
+/// Run condition for when the client transport `T` is connected.
+pub fn client_transport_connected<T: ClientTransport + Resource>() -> impl FnMut(Option<Res<T>>) -> bool {
+    |transport| match transport {
+        Some(transport) => transport.is_connected(),
+        None => false,
+    }
+}
+
+/// Run condition for when the client transport `T` is disconnected, or doesn't exist.
+pub fn client_transport_disconnected<T: ClientTransport + Resource>() -> impl FnMut(Option<Res<T>>) -> bool {
+    |transport| match transport {
+        Some(transport) => transport.is_disconnected(),
+        None => true,
+    }
+}
+
+/// Run condition for when the client transport `T` is connecting.
+pub fn client_transport_connecting<T: ClientTransport + Resource>() -> impl FnMut(Option<Res<T>>) -> bool {
+    |transport| match transport {
+        Some(transport) => transport.is_connecting(),
+        None => false,
+    }
+}
+
+/// Run condition for the first run after the client transport `T` connected.
+pub fn client_transport_just_connected<T: ClientTransport + Resource>() -> impl FnMut(Local<bool>, Option<Res<T>>) -> bool {
+    |mut last_connected: Local<bool>, transport| {
+        let connected = transport.map(|transport| transport.is_connected()).unwrap_or(false);
+
+        let just_connected = !*last_connected && connected;
+        *last_connected = connected;
+        just_connected
+    }
+}
+
+/// Run condition for the first run after the client transport `T` disconnected.
+pub fn client_transport_just_disconnected<T: ClientTransport + Resource>() -> impl FnMut(Local<bool>, Option<Res<T>>) -> bool {
+    |mut last_connected: Local<bool>, transport| {
+        let disconnected = transport.map(|transport| transport.is_disconnected()).unwrap_or(true);
+
+        let just_disconnected = *last_connected && disconnected;
+        *last_connected = !disconnected;
+        just_disconnected
     }
 }
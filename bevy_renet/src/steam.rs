@@ -1,11 +1,22 @@
+use std::convert::Infallible;
+
 use bevy::{app::AppExit, prelude::*};
 use renet::{RenetClient, RenetServer};
 use renet_steam::steamworks::SteamError;
 
-use crate::{RenetClientPlugin, RenetServerPlugin};
+use crate::{
+    client_transport_disconnect_on_exit, client_transport_send_packets, client_transport_update_system,
+    server_transport_disconnect_on_exit, server_transport_send_packets, server_transport_update_system, RenetClientPlugin,
+    RenetServerPlugin,
+};
 
 pub use renet_steam::{AccessPermission, SteamClientTransport, SteamServerConfig, SteamServerTransport};
 
+/// Updates and sends the packets of the [SteamServerTransport], insert it with `App::insert_resource`.
+///
+/// The transport used to be inserted with `App::insert_non_send_resource`, that still works with
+/// [SteamServerPlugin::update_system], [SteamServerPlugin::send_packets] and [SteamServerPlugin::disconnect_on_exit],
+/// but those systems have to run in the main thread. Prefer inserting it as a resource.
 pub struct SteamServerPlugin;
 
 pub struct SteamClientPlugin;
@@ -19,15 +30,48 @@ impl std::fmt::Display for SteamTransportError {
     }
 }
 
+impl From<SteamError> for SteamTransportError {
+    fn from(inner: SteamError) -> Self {
+        SteamTransportError(inner)
+    }
+}
+
+// The server transport never fails to update, the conversion is only needed by the shared systems
+impl From<Infallible> for SteamTransportError {
+    fn from(inner: Infallible) -> Self {
+        match inner {}
+    }
+}
+
 impl Plugin for SteamServerPlugin {
     fn build(&self, app: &mut App) {
+        app.add_event::<SteamTransportError>();
+
         app.add_systems(
             PreUpdate,
-            Self::update_system
+            server_transport_update_system::<SteamServerTransport, SteamTransportError>
+                .run_if(resource_exists::<SteamServerTransport>())
                 .run_if(resource_exists::<RenetServer>())
                 .after(RenetServerPlugin::update_system),
         );
 
+        app.add_systems(
+            PostUpdate,
+            (
+                server_transport_send_packets::<SteamServerTransport>,
+                server_transport_disconnect_on_exit::<SteamServerTransport>,
+            )
+                .run_if(resource_exists::<SteamServerTransport>())
+                .run_if(resource_exists::<RenetServer>()),
+        );
+
+        // Transports inserted as non send resources
+        app.add_systems(
+            PreUpdate,
+            Self::update_system
+                .run_if(resource_exists::<RenetServer>())
+                .after(RenetServerPlugin::update_system),
+        );
         app.add_systems(
             PostUpdate,
             (Self::send_packets, Self::disconnect_on_exit).run_if(resource_exists::<RenetServer>()),
@@ -67,78 +111,39 @@ impl Plugin for SteamClientPlugin {
 
         app.add_systems(
             PreUpdate,
-            Self::update_system
+            client_transport_update_system::<SteamClientTransport, SteamTransportError>
                 .run_if(resource_exists::<SteamClientTransport>())
                 .run_if(resource_exists::<RenetClient>())
                 .after(RenetClientPlugin::update_system),
         );
         app.add_systems(
             PostUpdate,
-            (Self::send_packets, Self::disconnect_on_exit)
+            (
+                client_transport_send_packets::<SteamClientTransport, SteamTransportError>,
+                client_transport_disconnect_on_exit::<SteamClientTransport>,
+            )
                 .run_if(resource_exists::<SteamClientTransport>())
                 .run_if(resource_exists::<RenetClient>()),
         );
     }
 }
 
-impl SteamClientPlugin {
-    pub fn update_system(mut transport: ResMut<SteamClientTransport>, mut client: ResMut<RenetClient>) {
-        transport.update(&mut client);
-    }
-
-    pub fn send_packets(
-        mut transport: ResMut<SteamClientTransport>,
-        mut client: ResMut<RenetClient>,
-        mut transport_errors: EventWriter<SteamTransportError>,
-    ) {
-        if let Err(e) = transport.send_packets(&mut client) {
-            transport_errors.send(SteamTransportError(e));
-        }
-    }
-
-    fn disconnect_on_exit(exit: EventReader<AppExit>, mut transport: ResMut<SteamClientTransport>) {
-        if !exit.is_empty() && !transport.is_disconnected() {
-            transport.disconnect();
-        }
-    }
-}
-
 pub fn client_connected() -> impl FnMut(Option<Res<SteamClientTransport>>) -> bool {
-    |transport| match transport {
-        Some(transport) => transport.is_connected(),
-        None => false,
-    }
+    crate::client_transport_connected()
 }
 
 pub fn client_diconnected() -> impl FnMut(Option<Res<SteamClientTransport>>) -> bool {
-    |transport| match transport {
-        Some(transport) => transport.is_disconnected(),
-        None => true,
-    }
+    crate::client_transport_disconnected()
 }
 
 pub fn client_connecting() -> impl FnMut(Option<Res<SteamClientTransport>>) -> bool {
-    |transport| match transport {
-        Some(transport) => transport.is_connecting(),
-        None => false,
-    }
+    crate::client_transport_connecting()
 }
 
 pub fn client_just_connected() -> impl FnMut(Local<bool>, Option<Res<SteamClientTransport>>) -> bool {
-    |mut last_connected: Local<bool>, transport| {
-        let connected = transport.map(|transport| transport.is_connected()).unwrap_or(false);
-        let just_connected = !*last_connected && connected;
-        *last_connected = connected;
-        just_connected
-    }
+    crate::client_transport_just_connected()
 }
 
 pub fn client_just_diconnected() -> impl FnMut(Local<bool>, Option<Res<SteamClientTransport>>) -> bool {
-    |mut last_connected: Local<bool>, transport| {
-        let disconnected = transport.map(|transport| transport.is_disconnected()).unwrap_or(true);
-
-        let just_disconnected = *last_connected && disconnected;
-        *last_connected = !disconnected;
-        just_disconnected
-    }
+    crate::client_transport_just_disconnected()
 }
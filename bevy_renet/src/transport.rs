@@ -3,9 +3,13 @@ use renet::{
     RenetClient, RenetServer,
 };
 
-use bevy::{app::AppExit, prelude::*};
+use bevy::prelude::*;
 
-use crate::{RenetClientPlugin, RenetServerPlugin};
+use crate::{
+    client_transport_disconnect_on_exit, client_transport_send_packets, client_transport_update_system,
+    server_transport_disconnect_on_exit, server_transport_send_packets, server_transport_update_system, RenetClientPlugin,
+    RenetServerPlugin,
+};
 
 pub struct NetcodeServerPlugin;
 
@@ -17,7 +21,7 @@ impl Plugin for NetcodeServerPlugin {
 
         app.add_systems(
             PreUpdate,
-            Self::update_system
+            server_transport_update_system::<NetcodeServerTransport, NetcodeTransportError>
                 .run_if(resource_exists::<NetcodeServerTransport>())
                 .run_if(resource_exists::<RenetServer>())
                 .after(RenetServerPlugin::update_system),
@@ -25,122 +29,55 @@ impl Plugin for NetcodeServerPlugin {
 
         app.add_systems(
             PostUpdate,
-            (Self::send_packets, Self::disconnect_on_exit)
+            (
+                server_transport_send_packets::<NetcodeServerTransport>,
+                server_transport_disconnect_on_exit::<NetcodeServerTransport>,
+            )
                 .run_if(resource_exists::<NetcodeServerTransport>())
                 .run_if(resource_exists::<RenetServer>()),
         );
     }
 }
 
-impl NetcodeServerPlugin {
-    pub fn update_system(
-        mut transport: ResMut<NetcodeServerTransport>,
-        mut server: ResMut<RenetServer>,
-        time: Res<Time>,
-        mut transport_errors: EventWriter<NetcodeTransportError>,
-    ) {
-        if let Err(e) = transport.update(time.delta(), &mut server) {
-            transport_errors.send(e);
-        }
-    }
-
-    pub fn send_packets(mut transport: ResMut<NetcodeServerTransport>, mut server: ResMut<RenetServer>) {
-        transport.send_packets(&mut server);
-    }
-
-    pub fn disconnect_on_exit(exit: EventReader<AppExit>, mut transport: ResMut<NetcodeServerTransport>, mut server: ResMut<RenetServer>) {
-        if !exit.is_empty() {
-            transport.disconnect_all(&mut server);
-        }
-    }
-}
-
 impl Plugin for NetcodeClientPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<NetcodeTransportError>();
 
         app.add_systems(
             PreUpdate,
-            Self::update_system
+            client_transport_update_system::<NetcodeClientTransport, NetcodeTransportError>
                 .run_if(resource_exists::<NetcodeClientTransport>())
                 .run_if(resource_exists::<RenetClient>())
                 .after(RenetClientPlugin::update_system),
         );
         app.add_systems(
             PostUpdate,
-            (Self::send_packets, Self::disconnect_on_exit)
+            (
+                client_transport_send_packets::<NetcodeClientTransport, NetcodeTransportError>,
+                client_transport_disconnect_on_exit::<NetcodeClientTransport>,
+            )
                 .run_if(resource_exists::<NetcodeClientTransport>())
                 .run_if(resource_exists::<RenetClient>()),
         );
     }
 }
 
-impl NetcodeClientPlugin {
-    pub fn update_system(
-        mut transport: ResMut<NetcodeClientTransport>,
-        mut client: ResMut<RenetClient>,
-        time: Res<Time>,
-        mut transport_errors: EventWriter<NetcodeTransportError>,
-    ) {
-        if let Err(e) = transport.update(time.delta(), &mut client) {
-            transport_errors.send(e);
-        }
-    }
-
-    pub fn send_packets(
-        mut transport: ResMut<NetcodeClientTransport>,
-        mut client: ResMut<RenetClient>,
-        mut transport_errors: EventWriter<NetcodeTransportError>,
-    ) {
-        if let Err(e) = transport.send_packets(&mut client) {
-            transport_errors.send(e);
-        }
-    }
-
-    fn disconnect_on_exit(exit: EventReader<AppExit>, mut transport: ResMut<NetcodeClientTransport>) {
-        if !exit.is_empty() && !transport.is_disconnected() {
-            transport.disconnect();
-        }
-    }
-}
-
 pub fn client_connected() -> impl FnMut(Option<Res<NetcodeClientTransport>>) -> bool {
-    |transport| match transport {
-        Some(transport) => transport.is_connected(),
-        None => false,
-    }
+    crate::client_transport_connected()
 }
 
 pub fn client_diconnected() -> impl FnMut(Option<Res<NetcodeClientTransport>>) -> bool {
-    |transport| match transport {
-        Some(transport) => transport.is_disconnected(),
-        None => true,
-    }
+    crate::client_transport_disconnected()
 }
 
 pub fn client_connecting() -> impl FnMut(Option<Res<NetcodeClientTransport>>) -> bool {
-    |transport| match transport {
-        Some(transport) => transport.is_connecting(),
-        None => false,
-    }
+    crate::client_transport_connecting()
 }
 
 pub fn client_just_connected() -> impl FnMut(Local<bool>, Option<Res<NetcodeClientTransport>>) -> bool {
-    |mut last_connected: Local<bool>, transport| {
-        let connected = transport.map(|transport| transport.is_connected()).unwrap_or(false);
-
-        let just_connected = !*last_connected && connected;
-        *last_connected = connected;
-        just_connected
-    }
+    crate::client_transport_just_connected()
 }
 
 pub fn client_just_diconnected() -> impl FnMut(Local<bool>, Option<Res<NetcodeClientTransport>>) -> bool {
-    |mut last_connected: Local<bool>, transport| {
-        let disconnected = transport.map(|transport| transport.is_disconnected()).unwrap_or(true);
-
-        let just_disconnected = *last_connected && disconnected;
-        *last_connected = !disconnected;
-        just_disconnected
-    }
+    crate::client_transport_just_disconnected()
 }
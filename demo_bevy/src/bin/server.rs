@@ -45,13 +45,7 @@ fn add_netcode_network(app: &mut App) {
     let public_addr = "127.0.0.1:5000".parse().unwrap();
     let socket = UdpSocket::bind(public_addr).unwrap();
     let current_time: std::time::Duration = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
-    let server_config = ServerConfig {
-        current_time,
-        max_clients: 64,
-        protocol_id: PROTOCOL_ID,
-        public_addresses: vec![public_addr],
-        authentication: ServerAuthentication::Unsecure,
-    };
+    let server_config = ServerConfig::new(current_time, 64, PROTOCOL_ID, vec![public_addr], ServerAuthentication::Unsecure);
 
     let transport = NetcodeServerTransport::new(server_config, socket).unwrap();
     app.insert_resource(server);
@@ -76,7 +70,7 @@ fn add_steam_network(app: &mut App) {
 
     app.add_plugins(SteamServerPlugin);
     app.insert_resource(server);
-    app.insert_non_send_resource(transport);
+    app.insert_resource(transport);
     app.insert_non_send_resource(single);
 
     fn steam_callbacks(client: NonSend<SingleClient>) {
@@ -142,7 +136,7 @@ fn server_update_system(
 ) {
     for event in server_events.iter() {
         match event {
-            ServerEvent::ClientConnected { client_id } => {
+            ServerEvent::ClientConnected { client_id, .. } => {
                 println!("Player {} connected.", client_id);
                 visualizer.add_client(*client_id);
 
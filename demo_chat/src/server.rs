@@ -6,7 +6,7 @@ use std::{
 };
 
 use renet::{
-    transport::{NetcodeServerTransport, ServerAuthentication, ServerConfig},
+    transport::{decode_user_data, NetcodeServerTransport, ServerAuthentication, ServerConfig},
     ClientId, ConnectionConfig, DefaultChannel, RenetServer, ServerEvent,
 };
 use renet_visualizer::RenetServerVisualizer;
@@ -30,13 +30,13 @@ impl ChatServer {
     pub fn new(host_username: String) -> Self {
         let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
         let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
-        let server_config = ServerConfig {
+        let server_config = ServerConfig::new(
             current_time,
-            max_clients: 64,
-            protocol_id: PROTOCOL_ID,
-            public_addresses: vec![socket.local_addr().unwrap()],
-            authentication: ServerAuthentication::Unsecure,
-        };
+            64,
+            PROTOCOL_ID,
+            vec![socket.local_addr().unwrap()],
+            ServerAuthentication::Unsecure,
+        );
 
         let transport = NetcodeServerTransport::new(server_config, socket).unwrap();
 
@@ -62,10 +62,12 @@ impl ChatServer {
 
         while let Some(event) = self.server.get_event() {
             match event {
-                ServerEvent::ClientConnected { client_id } => {
-                    let user_data = self.transport.user_data(client_id).unwrap();
+                ServerEvent::ClientConnected { client_id, info } => {
+                    let user_data = info.user_data.unwrap();
                     self.visualizer.add_client(client_id);
-                    let username = Username::from_user_data(&user_data).0;
+                    let username = decode_user_data::<Username>(user_data.as_ref().try_into().unwrap())
+                        .map(|username| username.0)
+                        .unwrap_or_else(|_| "unknown".to_string());
                     self.usernames.insert(client_id, username.clone());
                     let message = bincode::options()
                         .serialize(&ServerMessages::ClientConnected { client_id, username })
@@ -4,7 +4,7 @@ use eframe::{
     epaint::PathShape,
 };
 use renet::{
-    transport::{ClientAuthentication, NetcodeClientTransport},
+    transport::{encode_user_data, ClientAuthentication, NetcodeClientTransport},
     ClientId, ConnectionConfig, DefaultChannel, RenetClient,
 };
 
@@ -263,7 +263,7 @@ fn create_renet_client(username: String, server_addr: SocketAddr) -> (RenetClien
     let authentication = ClientAuthentication::Unsecure {
         server_addr,
         client_id,
-        user_data: Some(Username(username).to_netcode_user_data()),
+        user_data: Some(encode_user_data(&Username(username)).expect("Username is too big")),
         protocol_id: PROTOCOL_ID,
     };
 
@@ -0,0 +1,113 @@
+//! Compares the server transport with and without batched I/O on loopback.
+//! Run with `cargo bench -p renet --features batch_io`.
+use std::{net::UdpSocket, time::Duration};
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use renet::{
+    transport::{ClientAuthentication, NetcodeClientTransport, NetcodeServerTransport, ServerAuthentication, ServerConfig},
+    ConnectionConfig, DefaultChannel, RenetClient, RenetServer,
+};
+
+const CLIENTS: usize = 64;
+const MESSAGES_PER_TICK: usize = 4;
+
+struct Setup {
+    transport: NetcodeServerTransport,
+    server: RenetServer,
+    clients: Vec<(NetcodeClientTransport, RenetClient)>,
+}
+
+fn setup(batch_io: bool) -> Setup {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let server_addr = socket.local_addr().unwrap();
+    let mut server_config = ServerConfig::new(Duration::ZERO, CLIENTS, 0, vec![server_addr], ServerAuthentication::Unsecure);
+    server_config.max_pending_clients = CLIENTS;
+    let mut transport = NetcodeServerTransport::new(server_config, socket).unwrap();
+    transport.set_batch_io(batch_io);
+    let mut server = RenetServer::new(ConnectionConfig::default());
+
+    let mut clients: Vec<_> = (0..CLIENTS as u64)
+        .map(|client_id| {
+            let authentication = ClientAuthentication::Unsecure {
+                protocol_id: 0,
+                client_id,
+                server_addr,
+                user_data: None,
+            };
+            let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let client_transport = NetcodeClientTransport::new(Duration::ZERO, authentication, socket).unwrap();
+            (client_transport, RenetClient::new(ConnectionConfig::default()))
+        })
+        .collect();
+
+    let delta = Duration::from_millis(10);
+    while transport.connected_clients() < CLIENTS || clients.iter().any(|(_, client)| !client.is_connected()) {
+        for (client_transport, client) in clients.iter_mut() {
+            client_transport.update(delta, client).unwrap();
+            client_transport.send_packets(client).unwrap();
+        }
+        transport.update(delta, &mut server).unwrap();
+        transport.send_packets(&mut server);
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    Setup {
+        transport,
+        server,
+        clients,
+    }
+}
+
+fn send_packets(c: &mut Criterion) {
+    let mut group = c.benchmark_group("send_packets");
+    for batch_io in [false, true] {
+        let Setup {
+            mut transport, mut server, ..
+        } = setup(batch_io);
+        let message = vec![7u8; 1000];
+        group.bench_function(if batch_io { "batched" } else { "unbatched" }, |b| {
+            b.iter(|| {
+                for _ in 0..MESSAGES_PER_TICK {
+                    server.broadcast_message(DefaultChannel::Unreliable, message.clone());
+                }
+                transport.send_packets(&mut server);
+            })
+        });
+    }
+    group.finish();
+}
+
+fn receive_packets(c: &mut Criterion) {
+    let mut group = c.benchmark_group("receive_packets");
+    for batch_io in [false, true] {
+        let Setup {
+            mut transport,
+            mut server,
+            mut clients,
+        } = setup(batch_io);
+        let message = vec![7u8; 1000];
+        group.bench_function(if batch_io { "batched" } else { "unbatched" }, |b| {
+            b.iter_batched(
+                || {
+                    for (client_transport, client) in clients.iter_mut() {
+                        for _ in 0..MESSAGES_PER_TICK {
+                            client.send_message(DefaultChannel::Unreliable, message.clone());
+                        }
+                        client_transport.send_packets(client).unwrap();
+                    }
+                },
+                |_| {
+                    transport.update(Duration::ZERO, &mut server).unwrap();
+                    for client_id in server.clients_id() {
+                        while server.receive_message(client_id, DefaultChannel::Unreliable).is_some() {}
+                    }
+                },
+                BatchSize::PerIteration,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, send_packets, receive_packets);
+criterion_main!(benches);
@@ -1,10 +1,12 @@
 pub(crate) mod reliable;
 pub(crate) mod slice_constructor;
+pub(crate) mod stream;
 pub(crate) mod unreliable;
 
 use std::time::Duration;
 
 pub(crate) use slice_constructor::SliceConstructor;
+pub use stream::{StreamChunk, StreamProgress, StreamState};
 
 /// Delivery garantee of a channel
 #[derive(Debug, Clone)]
@@ -19,6 +21,18 @@ pub enum SendType {
     ReliableUnordered {
         resend_time: Duration,
     },
+    /// Messages are sent with a key, only the most recent message for each key is guaranteed to be received.
+    /// Older unacknowledged messages with the same key are superseded and never retransmitted.
+    /// Use [RenetClient::send_keyed_message][crate::RenetClient::send_keyed_message] to send messages in this channel.
+    ReliableKeyed {
+        resend_time: Duration,
+    },
+    /// Channel used to send large data incrementally from readers, chunks are guaranteed to be received in order.
+    /// The readers are only consumed when the channel has memory available.
+    /// Use [RenetClient::send_stream][crate::RenetClient::send_stream] to send data in this channel.
+    ReliableStream {
+        resend_time: Duration,
+    },
 }
 
 /// Configuration of a channel for a server or client
@@ -1,14 +1,19 @@
 use std::{
-    collections::{btree_map, BTreeMap, BTreeSet, HashMap},
+    collections::{btree_map, BTreeMap, BTreeSet, HashMap, HashSet},
+    io::Read,
     time::Duration,
 };
 
 use bytes::Bytes;
+use octets::OctetsMut;
 
-use super::SliceConstructor;
+use super::{
+    stream::{decode_frame, SendStreams, StreamChunk, StreamProgress},
+    SliceConstructor,
+};
 use crate::{
     error::ChannelError,
-    packet::{Packet, Slice, SLICE_SIZE},
+    packet::{Packet, PacketPool, Slice, DEFAULT_SLICE_SIZE},
 };
 
 #[derive(Debug)]
@@ -19,6 +24,7 @@ enum UnackedMessage {
     },
     Sliced {
         message: Bytes,
+        slice_size: usize,
         num_slices: usize,
         num_acked_slices: usize,
         next_slice_to_send: usize,
@@ -35,6 +41,16 @@ pub struct SendChannelReliable {
     resend_time: Duration,
     max_memory_usage_bytes: usize,
     memory_usage_bytes: usize,
+    slice_size: usize,
+    keyed: bool,
+    // Latest unacked message id sent for each key, and the key of each of these messages, only used in keyed channels
+    unacked_keys: HashMap<u64, u64>,
+    unacked_key_messages: HashMap<u64, u64>,
+    // Only used in stream channels
+    streams: Option<SendStreams>,
+    // Messages that should be reported when acked
+    tracked_messages: HashSet<u64>,
+    delivered_messages: Vec<u64>,
 }
 
 #[derive(Debug)]
@@ -44,6 +60,12 @@ enum ReliableOrder {
         most_recent_message_id: u64,
         received_messages: BTreeSet<u64>,
     },
+    Keyed {
+        received_messages: BTreeSet<u64>,
+        latest_message_ids: HashMap<u64, u64>,
+        // Key of each message in latest_message_ids, to remove the keys no longer needed
+        latest_message_keys: BTreeMap<u64, u64>,
+    },
 }
 
 #[derive(Debug)]
@@ -54,14 +76,16 @@ pub struct ReceiveChannelReliable {
     reliable_order: ReliableOrder,
     memory_usage_bytes: usize,
     max_memory_usage_bytes: usize,
+    stream: bool,
 }
 
 impl UnackedMessage {
-    fn new_sliced(payload: Bytes) -> Self {
-        let num_slices = (payload.len() + SLICE_SIZE - 1) / SLICE_SIZE;
+    fn new_sliced(payload: Bytes, slice_size: usize) -> Self {
+        let num_slices = (payload.len() + slice_size - 1) / slice_size;
 
         Self::Sliced {
             message: payload,
+            slice_size,
             num_slices,
             num_acked_slices: 0,
             next_slice_to_send: 0,
@@ -80,25 +104,107 @@ impl SendChannelReliable {
             resend_time,
             max_memory_usage_bytes,
             memory_usage_bytes: 0,
+            slice_size: DEFAULT_SLICE_SIZE,
+            keyed: false,
+            unacked_keys: HashMap::new(),
+            unacked_key_messages: HashMap::new(),
+            streams: None,
+            tracked_messages: HashSet::new(),
+            delivered_messages: Vec::new(),
+        }
+    }
+
+    pub fn new_keyed(channel_id: u8, resend_time: Duration, max_memory_usage_bytes: usize) -> Self {
+        Self {
+            keyed: true,
+            ..Self::new(channel_id, resend_time, max_memory_usage_bytes)
         }
     }
 
+    pub fn is_keyed(&self) -> bool {
+        self.keyed
+    }
+
+    /// Sets the slice size used for new messages, messages already sliced keep their slice size.
+    pub fn set_slice_size(&mut self, slice_size: usize) {
+        self.slice_size = slice_size;
+    }
+
+    pub fn new_stream(channel_id: u8, resend_time: Duration, max_memory_usage_bytes: usize) -> Self {
+        Self {
+            streams: Some(SendStreams::default()),
+            ..Self::new(channel_id, resend_time, max_memory_usage_bytes)
+        }
+    }
+
+    pub fn is_stream(&self) -> bool {
+        self.streams.is_some()
+    }
+
+    pub fn send_stream(&mut self, reader: Box<dyn Read + Send + Sync>) -> u64 {
+        self.streams.as_mut().expect("channel should be a stream channel").add(reader)
+    }
+
+    pub fn stream_progress(&self, stream_id: u64) -> Option<StreamProgress> {
+        self.streams.as_ref()?.progress(stream_id)
+    }
+
+    pub fn remove_stream(&mut self, stream_id: u64) -> Option<StreamProgress> {
+        self.streams.as_mut()?.remove(stream_id)
+    }
+
+    // Reads frames from the queued streams while the channel has memory available for them
+    fn read_streams(&mut self) {
+        let Some(mut streams) = self.streams.take() else {
+            return;
+        };
+
+        while self.can_send_message(self.slice_size) {
+            let Some((stream_id, frame, data_len)) = streams.next_frame(self.slice_size) else {
+                break;
+            };
+
+            let message_id = self.next_reliable_message_id;
+            self.send_message(frame)
+                .expect("channel should have memory available for the stream frame");
+            streams.frame_sent(message_id, stream_id, data_len);
+        }
+
+        self.streams = Some(streams);
+    }
+
     pub fn available_memory(&self) -> usize {
         self.max_memory_usage_bytes - self.memory_usage_bytes
     }
 
+    pub fn memory_usage_bytes(&self) -> usize {
+        self.memory_usage_bytes
+    }
+
+    pub fn max_memory_usage_bytes(&self) -> usize {
+        self.max_memory_usage_bytes
+    }
+
     pub fn can_send_message(&self, size_bytes: usize) -> bool {
         size_bytes + self.memory_usage_bytes <= self.max_memory_usage_bytes
     }
 
-    pub fn get_packets_to_send(&mut self, packet_sequence: &mut u64, available_bytes: &mut u64, current_time: Duration) -> Vec<Packet> {
+    /// Appends the packets to be sent to `packets`, using the pool for the vectors of new packets.
+    pub fn get_packets_to_send(
+        &mut self,
+        packets: &mut Vec<Packet>,
+        pool: &mut PacketPool,
+        packet_sequence: &mut u64,
+        available_bytes: &mut u64,
+        current_time: Duration,
+    ) {
+        self.read_streams();
+
         if self.unacked_messages.is_empty() {
-            return vec![];
+            return;
         }
 
-        let mut packets: Vec<Packet> = vec![];
-
-        let mut small_messages: Vec<(u64, Bytes)> = vec![];
+        let mut small_messages: Vec<(u64, Bytes)> = pool.reliable_messages();
         let mut small_messages_bytes = 0;
 
         'messages: for (&message_id, unacked_message) in self.unacked_messages.iter_mut() {
@@ -119,11 +225,11 @@ impl SendChannelReliable {
 
                     // Generate packet with small messages if you cannot fit
                     let serialized_size = message.len() + octets::varint_len(message.len() as u64) + octets::varint_len(message_id);
-                    if small_messages_bytes + serialized_size > SLICE_SIZE {
+                    if small_messages_bytes + serialized_size > self.slice_size {
                         packets.push(Packet::SmallReliable {
                             sequence: *packet_sequence,
                             channel_id: self.channel_id,
-                            messages: std::mem::take(&mut small_messages),
+                            messages: std::mem::replace(&mut small_messages, pool.reliable_messages()),
                         });
                         small_messages_bytes = 0;
                         *packet_sequence += 1;
@@ -137,6 +243,7 @@ impl SendChannelReliable {
                 }
                 UnackedMessage::Sliced {
                     message,
+                    slice_size,
                     num_slices,
                     acked,
                     last_sent,
@@ -145,7 +252,7 @@ impl SendChannelReliable {
                 } => {
                     let start_index = *next_slice_to_send;
                     for i in 0..*num_slices {
-                        if *available_bytes < SLICE_SIZE as u64 {
+                        if *available_bytes < *slice_size as u64 {
                             // Skip message, no bytes available to send a slice
                             continue 'messages;
                         }
@@ -161,8 +268,8 @@ impl SendChannelReliable {
                             }
                         }
 
-                        let start = i * SLICE_SIZE;
-                        let end = if i == *num_slices - 1 { message.len() } else { (i + 1) * SLICE_SIZE };
+                        let start = i * *slice_size;
+                        let end = if i == *num_slices - 1 { message.len() } else { (i + 1) * *slice_size };
 
                         let payload = message.slice(start..end);
                         *available_bytes -= payload.len() as u64;
@@ -193,12 +300,12 @@ impl SendChannelReliable {
             packets.push(Packet::SmallReliable {
                 sequence: *packet_sequence,
                 channel_id: self.channel_id,
-                messages: std::mem::take(&mut small_messages),
+                messages: small_messages,
             });
             *packet_sequence += 1;
+        } else {
+            pool.recycle_reliable_messages(small_messages);
         }
-
-        packets
     }
 
     pub fn send_message(&mut self, message: Bytes) -> Result<(), ChannelError> {
@@ -207,8 +314,8 @@ impl SendChannelReliable {
         }
 
         self.memory_usage_bytes += message.len();
-        let unacked_message = if message.len() > SLICE_SIZE {
-            UnackedMessage::new_sliced(message)
+        let unacked_message = if message.len() > self.slice_size {
+            UnackedMessage::new_sliced(message, self.slice_size)
         } else {
             UnackedMessage::Small { message, last_sent: None }
         };
@@ -219,6 +326,73 @@ impl SendChannelReliable {
         Ok(())
     }
 
+    /// Sends a message that is reported in [take_delivered_messages][Self::take_delivered_messages] when acked.
+    pub fn send_tracked_message(&mut self, message: Bytes) -> Result<u64, ChannelError> {
+        let message_id = self.next_reliable_message_id;
+        self.send_message(message)?;
+        self.tracked_messages.insert(message_id);
+
+        Ok(message_id)
+    }
+
+    /// Returns the ids of the tracked messages acked since the last call.
+    pub fn take_delivered_messages(&mut self) -> Vec<u64> {
+        std::mem::take(&mut self.delivered_messages)
+    }
+
+    fn message_delivered(&mut self, message_id: u64) {
+        if self.tracked_messages.remove(&message_id) {
+            self.delivered_messages.push(message_id);
+        }
+
+        if let Some(key) = self.unacked_key_messages.remove(&message_id) {
+            self.unacked_keys.remove(&key);
+        }
+    }
+
+    pub fn send_keyed_message(&mut self, key: u64, message: Bytes) -> Result<(), ChannelError> {
+        // The key is sent as a varint prefix of the message
+        let mut keyed_message = vec![0u8; octets::varint_len(key) + message.len()];
+        let mut oct = OctetsMut::with_slice(&mut keyed_message);
+        oct.put_varint(key).expect("keyed message buffer should fit the key");
+        oct.put_bytes(&message).expect("keyed message buffer should fit the message");
+
+        if let Some(&superseded_message_id) = self.unacked_keys.get(&key) {
+            self.supersede_message(superseded_message_id);
+        }
+
+        let message_id = self.next_reliable_message_id;
+        self.send_message(keyed_message.into())?;
+        if let Some(superseded_message_id) = self.unacked_keys.insert(key, message_id) {
+            self.unacked_key_messages.remove(&superseded_message_id);
+        }
+        self.unacked_key_messages.insert(message_id, key);
+
+        Ok(())
+    }
+
+    // Replaces an unacked message with an empty one, so it still occupies its message id,
+    // but its payload is released and no longer retransmitted.
+    fn supersede_message(&mut self, message_id: u64) {
+        let Some(unacked_message) = self.unacked_messages.get_mut(&message_id) else {
+            return;
+        };
+
+        match unacked_message {
+            UnackedMessage::Small { message, .. } => {
+                self.memory_usage_bytes -= message.len();
+                *message = Bytes::new();
+            }
+            UnackedMessage::Sliced { message, .. } => {
+                self.memory_usage_bytes -= message.len();
+                *unacked_message = UnackedMessage::Small {
+                    message: Bytes::new(),
+                    last_sent: None,
+                };
+            }
+        }
+    }
+
     pub fn process_message_ack(&mut self, message_id: u64) {
         if self.unacked_messages.contains_key(&message_id) {
             let unacked_message = self.unacked_messages.remove(&message_id).unwrap();
@@ -226,6 +400,11 @@ impl SendChannelReliable {
                 unreachable!("called ack on small message but found sliced");
             };
             self.memory_usage_bytes -= payload.len();
+            self.message_delivered(message_id);
+
+            if let Some(streams) = &mut self.streams {
+                streams.message_acked(message_id);
+            }
         }
     }
 
@@ -242,7 +421,9 @@ impl SendChannelReliable {
             ..
         } = unacked_message
         else {
-            unreachable!("called ack on sliced message but found small");
+            // Sliced messages are replaced by small ones when superseded in keyed channels
+            debug_assert!(self.keyed, "called ack on sliced message but found small");
+            return;
         };
 
         if acked[slice_index] {
@@ -255,6 +436,7 @@ impl SendChannelReliable {
         if *num_acked_slices == *num_slices {
             self.memory_usage_bytes -= message.len();
             self.unacked_messages.remove(&message_id);
+            self.message_delivered(message_id);
         }
     }
 }
@@ -275,6 +457,46 @@ impl ReceiveChannelReliable {
             reliable_order,
             memory_usage_bytes: 0,
             max_memory_usage_bytes,
+            stream: false,
+        }
+    }
+
+    pub fn new_keyed(max_memory_usage_bytes: usize) -> Self {
+        Self {
+            reliable_order: ReliableOrder::Keyed {
+                received_messages: BTreeSet::new(),
+                latest_message_ids: HashMap::new(),
+                latest_message_keys: BTreeMap::new(),
+            },
+            ..Self::new(max_memory_usage_bytes, false)
+        }
+    }
+
+    pub fn is_keyed(&self) -> bool {
+        matches!(self.reliable_order, ReliableOrder::Keyed { .. })
+    }
+
+    pub fn new_stream(max_memory_usage_bytes: usize) -> Self {
+        Self {
+            stream: true,
+            ..Self::new(max_memory_usage_bytes, true)
+        }
+    }
+
+    pub fn is_stream(&self) -> bool {
+        self.stream
+    }
+
+    fn already_received(&self, message_id: u64) -> bool {
+        if message_id < self.oldest_pending_message_id {
+            return true;
+        }
+
+        match &self.reliable_order {
+            ReliableOrder::Ordered => self.messages.contains_key(&message_id),
+            ReliableOrder::Unordered { received_messages, .. } | ReliableOrder::Keyed { received_messages, .. } => {
+                received_messages.contains(&message_id)
+            }
         }
     }
 
@@ -284,6 +506,10 @@ impl ReceiveChannelReliable {
             return Ok(());
         }
 
+        if self.stream {
+            decode_frame(&message)?;
+        }
+
         match &mut self.reliable_order {
             ReliableOrder::Ordered => {
                 if let btree_map::Entry::Vacant(entry) = self.messages.entry(message_id) {
@@ -313,23 +539,85 @@ impl ReceiveChannelReliable {
                     self.messages.insert(message_id, message);
                 }
             }
+            ReliableOrder::Keyed {
+                received_messages,
+                latest_message_ids,
+                latest_message_keys,
+            } => {
+                if !received_messages.insert(message_id) {
+                    return Ok(());
+                }
+
+                // Superseded messages can be left out of the delivery order,
+                // so we advance the oldest pending message as soon as they are received
+                let previous_oldest_pending_message_id = self.oldest_pending_message_id;
+                while received_messages.remove(&self.oldest_pending_message_id) {
+                    self.oldest_pending_message_id += 1;
+                }
+
+                // Keys are only needed to discard older messages received late, or to replace messages not yet read.
+                // All messages before the oldest pending one were received, so keys of the read messages it passed are removed,
+                // the keys of unread messages are removed when they are read
+                let stale_keys: Vec<(u64, u64)> = latest_message_keys
+                    .range(previous_oldest_pending_message_id..self.oldest_pending_message_id)
+                    .filter(|(latest_message_id, _)| !self.messages.contains_key(latest_message_id))
+                    .map(|(&latest_message_id, &key)| (latest_message_id, key))
+                    .collect();
+                for (latest_message_id, key) in stale_keys {
+                    latest_message_keys.remove(&latest_message_id);
+                    latest_message_ids.remove(&key);
+                }
+
+                if message.is_empty() {
+                    // Message was superseded by the sender, discard any slices received for it
+                    if let Some(slice_constructor) = self.slices.remove(&message_id) {
+                        self.memory_usage_bytes -= slice_constructor.received_bytes;
+                    }
+                    return Ok(());
+                }
+
+                let (key, _) = split_key(&message)?;
+                if let Some(&latest_message_id) = latest_message_ids.get(&key) {
+                    if latest_message_id > message_id {
+                        // A newer message with this key was already received
+                        return Ok(());
+                    }
+
+                    if let Some(old_message) = self.messages.remove(&latest_message_id) {
+                        self.memory_usage_bytes -= old_message.len();
+                    }
+                }
+
+                if self.memory_usage_bytes + message.len() > self.max_memory_usage_bytes {
+                    return Err(ChannelError::ReliableChannelMaxMemoryReached);
+                }
+                self.memory_usage_bytes += message.len();
+
+                if let Some(old_message_id) = latest_message_ids.insert(key, message_id) {
+                    latest_message_keys.remove(&old_message_id);
+                }
+                latest_message_keys.insert(message_id, key);
+                self.messages.insert(message_id, message);
+            }
         }
 
         Ok(())
     }
 
-    pub fn process_slice(&mut self, slice: Slice) -> Result<(), ChannelError> {
-        if self.messages.contains_key(&slice.message_id) || slice.message_id < self.oldest_pending_message_id {
+    /// Processes a slice of a message, the min slice size is the configured slice size of the connection,
+    /// all slices of a message except the last one have at least this size.
+    pub fn process_slice(&mut self, slice: Slice, min_slice_size: usize) -> Result<(), ChannelError> {
+        if self.already_received(slice.message_id) {
             // Message already assembled
             return Ok(());
         }
 
         if !self.slices.contains_key(&slice.message_id) {
-            let message_len = slice.num_slices * SLICE_SIZE;
-            if self.memory_usage_bytes + message_len > self.max_memory_usage_bytes {
+            // Check the message can fit in the channel before allocating its slices
+            let min_message_len = (slice.num_slices - 1).saturating_mul(min_slice_size) + 1;
+            if self.memory_usage_bytes + min_message_len > self.max_memory_usage_bytes {
                 return Err(ChannelError::ReliableChannelMaxMemoryReached);
             }
-            self.memory_usage_bytes += message_len;
         }
 
         let slice_constructor = self
@@ -337,11 +625,20 @@ impl ReceiveChannelReliable {
             .entry(slice.message_id)
             .or_insert_with(|| SliceConstructor::new(slice.message_id, slice.num_slices));
 
-        if let Some(message) = slice_constructor.process_slice(slice.slice_index, &slice.payload)? {
+        let is_new_slice = !slice_constructor.is_received(slice.slice_index);
+        if is_new_slice && self.memory_usage_bytes + slice.payload.len() > self.max_memory_usage_bytes {
+            return Err(ChannelError::ReliableChannelMaxMemoryReached);
+        }
+
+        let received_bytes = slice_constructor.received_bytes;
+        let message = slice_constructor.process_slice(slice.slice_index, slice.payload)?;
+        self.memory_usage_bytes += slice_constructor.received_bytes - received_bytes;
+
+        if let Some(message) = message {
             // Memory usage is re-added with the exactly message size
-            self.memory_usage_bytes -= slice.num_slices * SLICE_SIZE;
-            self.process_message(message, slice.message_id)?;
+            self.memory_usage_bytes -= slice_constructor.received_bytes;
             self.slices.remove(&slice.message_id);
+            self.process_message(message, slice.message_id)?;
         }
 
         Ok(())
@@ -375,8 +672,46 @@ impl ReceiveChannelReliable {
                 self.memory_usage_bytes -= message.len();
                 Some(message)
             }
+            ReliableOrder::Keyed { .. } => self.receive_keyed_message().map(|(_, message)| message),
         }
     }
+
+    pub fn receive_keyed_message(&mut self) -> Option<(u64, Bytes)> {
+        let (message_id, message) = self.messages.pop_first()?;
+        self.memory_usage_bytes -= message.len();
+
+        if let ReliableOrder::Keyed {
+            latest_message_ids,
+            latest_message_keys,
+            ..
+        } = &mut self.reliable_order
+        {
+            // Older messages with this key can no longer be received
+            if message_id < self.oldest_pending_message_id {
+                if let Some(key) = latest_message_keys.remove(&message_id) {
+                    latest_message_ids.remove(&key);
+                }
+            }
+        }
+
+        // Keys are validated when processing the message
+        let (key, message) = split_key(&message).expect("keyed message should have a valid key");
+        Some((key, message))
+    }
+
+    pub fn receive_stream_chunk(&mut self) -> Option<StreamChunk> {
+        let frame = self.receive_message()?;
+
+        // Frames are validated when processing the message
+        Some(decode_frame(&frame).expect("stream frame should be valid"))
+    }
+}
+
+fn split_key(message: &Bytes) -> Result<(u64, Bytes), ChannelError> {
+    let mut oct = octets::Octets::with_slice(message);
+    let key = oct.get_varint().map_err(|_| ChannelError::InvalidKeyedMessage)?;
+
+    Ok((key, message.slice(oct.off()..)))
 }
 
 #[cfg(test)]
@@ -385,6 +720,17 @@ mod tests {
 
     use super::*;
 
+    fn get_packets_to_send(
+        send: &mut SendChannelReliable,
+        sequence: &mut u64,
+        available_bytes: &mut u64,
+        current_time: Duration,
+    ) -> Vec<Packet> {
+        let mut packets = vec![];
+        send.get_packets_to_send(&mut packets, &mut PacketPool::default(), sequence, available_bytes, current_time);
+        packets
+    }
+
     #[test]
     fn small_packet() {
         let max_memory: usize = 10000;
@@ -401,7 +747,7 @@ mod tests {
         send.send_message(message1.clone().into()).unwrap();
         send.send_message(message2.clone().into()).unwrap();
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes, current_time);
         for packet in packets {
             let Packet::SmallReliable {
                 sequence: 0,
@@ -423,12 +769,12 @@ mod tests {
         assert_eq!(message2, new_message2);
 
         // Should not resend anything
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes, current_time);
         assert!(packets.is_empty());
 
         current_time += resend_time;
         // Should resend now
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes, current_time);
         assert_eq!(packets.len(), 1);
 
         // Should not resend after ack
@@ -436,7 +782,7 @@ mod tests {
         send.process_message_ack(0);
         send.process_message_ack(1);
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes, current_time);
         assert!(packets.is_empty());
     }
 
@@ -458,7 +804,7 @@ mod tests {
         send.send_message(message2.clone().into()).unwrap();
         send.send_message(message3.clone().into()).unwrap();
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes, current_time);
         assert_eq!(packets.len(), 1);
         let Packet::SmallReliable { messages, .. } = &packets[0] else {
             unreachable!();
@@ -481,7 +827,7 @@ mod tests {
         assert_eq!(message3, new_message3);
 
         match &recv.reliable_order {
-            ReliableOrder::Ordered => unreachable!(),
+            ReliableOrder::Ordered | ReliableOrder::Keyed { .. } => unreachable!(),
             ReliableOrder::Unordered {
                 most_recent_message_id,
                 received_messages,
@@ -492,12 +838,12 @@ mod tests {
         }
 
         // Should not resend anything
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes, current_time);
         assert!(packets.is_empty());
 
         current_time += resend_time;
         // Should resend now
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes, current_time);
         assert_eq!(packets.len(), 1);
 
         // Should not resend after ack
@@ -506,7 +852,7 @@ mod tests {
         send.process_message_ack(1);
         send.process_message_ack(2);
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes, current_time);
         assert!(packets.is_empty());
     }
 
@@ -520,28 +866,41 @@ mod tests {
         let mut recv = ReceiveChannelReliable::new(max_memory, true);
         let mut send = SendChannelReliable::new(0, resend_time, max_memory);
 
-        let message = vec![5; SLICE_SIZE * 3];
+        let message = vec![5; DEFAULT_SLICE_SIZE * 3];
 
         send.send_message(message.clone().into()).unwrap();
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes, current_time);
         for packet in packets {
             let Packet::ReliableSlice { channel_id: 0, slice, .. } = packet else {
                 unreachable!();
             };
-            recv.process_slice(slice).unwrap();
+            recv.process_slice(slice, DEFAULT_SLICE_SIZE).unwrap();
         }
 
         let new_message = recv.receive_message().unwrap();
         assert_eq!(message, new_message);
 
+        // Messages that could not fit in the channel are refused before allocating their slices
+        let slice = Slice {
+            message_id: 1,
+            slice_index: 0,
+            num_slices: 1_000_000,
+            payload: vec![0; DEFAULT_SLICE_SIZE].into(),
+        };
+        assert_eq!(
+            recv.process_slice(slice, DEFAULT_SLICE_SIZE),
+            Err(ChannelError::ReliableChannelMaxMemoryReached)
+        );
+        assert!(recv.slices.is_empty());
+
         // Should not resend anything
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes, current_time);
         assert!(packets.is_empty());
 
         current_time += resend_time;
         // Should resend now
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes, current_time);
         assert_eq!(packets.len(), 3);
 
         // Should not resend after ack
@@ -550,10 +909,91 @@ mod tests {
         send.process_slice_message_ack(0, 1);
         send.process_slice_message_ack(0, 2);
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes, current_time);
         assert!(packets.is_empty());
     }
 
+    #[test]
+    fn keyed_messages() {
+        let max_memory: usize = 10000;
+        let mut available_bytes = u64::MAX;
+        let mut sequence: u64 = 0;
+        let mut current_time: Duration = Duration::ZERO;
+        let resend_time = Duration::from_millis(100);
+        let mut recv = ReceiveChannelReliable::new_keyed(max_memory);
+        let mut send = SendChannelReliable::new_keyed(0, resend_time, max_memory);
+
+        let old_name = Bytes::from("old name");
+        let new_name = Bytes::from("new name");
+        let score = Bytes::from("score");
+
+        send.send_keyed_message(0, old_name).unwrap();
+        let old_packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes, current_time);
+
+        // Supersede the first message before it was acked
+        send.send_keyed_message(0, new_name.clone()).unwrap();
+        send.send_keyed_message(1, score.clone()).unwrap();
+
+        current_time += resend_time;
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes, current_time);
+        assert_eq!(packets.len(), 1);
+        let Packet::SmallReliable { messages, .. } = &packets[0] else {
+            unreachable!();
+        };
+
+        // Superseded message is sent empty
+        assert_eq!(messages.len(), 3);
+        assert!(messages[0].1.is_empty());
+        for (message_id, message) in messages {
+            recv.process_message(message.clone(), *message_id).unwrap();
+        }
+
+        // Old message arriving late is discarded
+        for packet in old_packets {
+            let Packet::SmallReliable { messages, .. } = packet else {
+                unreachable!();
+            };
+            for (message_id, message) in messages {
+                recv.process_message(message, message_id).unwrap();
+            }
+        }
+
+        assert_eq!(recv.receive_keyed_message(), Some((0, new_name)));
+        assert_eq!(recv.receive_keyed_message(), Some((1, score)));
+        assert_eq!(recv.receive_keyed_message(), None);
+        assert_eq!(recv.oldest_pending_message_id, 3);
+
+        // Newer message replaces the pending one in the receiver
+        send.send_keyed_message(1, Bytes::from("score 1")).unwrap();
+        send.send_keyed_message(1, Bytes::from("score 2")).unwrap();
+        recv.process_message(Bytes::from(vec![1, 1]), 3).unwrap();
+        recv.process_message(Bytes::from(vec![1, 2]), 4).unwrap();
+        assert_eq!(recv.receive_message(), Some(Bytes::from(vec![2])));
+        assert_eq!(recv.receive_message(), None);
+
+        // Keys of read messages are removed
+        let ReliableOrder::Keyed {
+            latest_message_ids,
+            latest_message_keys,
+            ..
+        } = &recv.reliable_order
+        else {
+            unreachable!();
+        };
+        assert!(latest_message_ids.is_empty());
+        assert!(latest_message_keys.is_empty());
+
+        send.process_message_ack(0);
+        send.process_message_ack(1);
+        send.process_message_ack(2);
+        send.process_message_ack(3);
+        assert_eq!(send.unacked_keys.len(), 1);
+        send.process_message_ack(4);
+        assert_eq!(send.memory_usage_bytes, 0);
+        assert!(send.unacked_keys.is_empty());
+        assert!(send.unacked_key_messages.is_empty());
+    }
+
     #[test]
     fn max_memory() {
         let mut available_bytes = u64::MAX;
@@ -568,7 +1008,7 @@ mod tests {
         // Can send one message without reaching memory limit
         send.send_message(message.clone().into()).unwrap();
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes, current_time);
         for packet in packets {
             let Packet::SmallReliable {
                 sequence: 0,
@@ -605,22 +1045,22 @@ mod tests {
 
         // No available bytes
         let mut available_bytes: u64 = 50;
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes, current_time);
         assert_eq!(packets.len(), 0);
 
         // Bytes for 1 message
         let mut available_bytes: u64 = 100;
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes, current_time);
         assert_eq!(packets.len(), 1);
 
         // Bytes for 1 message
         let mut available_bytes: u64 = 100;
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes, current_time);
         assert_eq!(packets.len(), 1);
 
         // No more messages to send
         let mut available_bytes: u64 = u64::MAX;
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes, current_time);
         assert_eq!(packets.len(), 0);
     }
 
@@ -640,7 +1080,7 @@ mod tests {
             send.send_message(message.clone()).unwrap();
         }
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes, current_time);
         assert_eq!(packets.len(), 2);
         let mut buffer = [0u8; 1400];
         for packet in packets {
@@ -1,14 +1,16 @@
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 
-use crate::{error::ChannelError, packet::SLICE_SIZE};
+use crate::error::ChannelError;
 
 #[derive(Debug, Clone)]
 pub struct SliceConstructor {
     message_id: u64,
     pub num_slices: usize,
     num_received_slices: usize,
-    received: Vec<bool>,
-    sliced_data: Vec<u8>,
+    // Slice size used by the sender for this message, all slices except the last one have this size
+    slice_size: Option<usize>,
+    slices: Vec<Option<Bytes>>,
+    pub received_bytes: usize,
 }
 
 impl SliceConstructor {
@@ -17,44 +19,43 @@ impl SliceConstructor {
             message_id,
             num_slices,
             num_received_slices: 0,
-            received: vec![false; num_slices],
-            sliced_data: vec![0; num_slices * SLICE_SIZE],
+            slice_size: None,
+            slices: vec![None; num_slices],
+            received_bytes: 0,
         }
     }
 
-    pub fn process_slice(&mut self, slice_index: usize, bytes: &[u8]) -> Result<Option<Bytes>, ChannelError> {
-        let is_last_slice = slice_index == self.num_slices - 1;
-        if is_last_slice {
-            if bytes.len() > SLICE_SIZE {
-                log::error!(
-                    "Invalid last slice_size for SliceMessage, got {}, expected less than {}.",
-                    bytes.len(),
-                    SLICE_SIZE,
-                );
-                return Err(ChannelError::InvalidSliceMessage);
-            }
-        } else if bytes.len() != SLICE_SIZE {
-            log::error!("Invalid slice_size for SliceMessage, got {}, expected {}.", bytes.len(), SLICE_SIZE);
+    pub fn is_received(&self, slice_index: usize) -> bool {
+        matches!(self.slices.get(slice_index), Some(Some(_)))
+    }
+
+    pub fn process_slice(&mut self, slice_index: usize, bytes: Bytes) -> Result<Option<Bytes>, ChannelError> {
+        if slice_index >= self.num_slices {
+            log::error!(
+                "Invalid slice_index for SliceMessage, got {}, expected less than {}.",
+                slice_index,
+                self.num_slices
+            );
             return Err(ChannelError::InvalidSliceMessage);
         }
 
-        if !self.received[slice_index] {
-            self.received[slice_index] = true;
-            self.num_received_slices += 1;
-
-            if is_last_slice {
-                let len = (self.num_slices - 1) * SLICE_SIZE + bytes.len();
-                self.sliced_data.resize(len, 0);
+        let is_last_slice = slice_index == self.num_slices - 1;
+        if !is_last_slice {
+            match self.slice_size {
+                None => self.slice_size = Some(bytes.len()),
+                Some(slice_size) if slice_size != bytes.len() => {
+                    log::error!("Invalid slice_size for SliceMessage, got {}, expected {}.", bytes.len(), slice_size);
+                    return Err(ChannelError::InvalidSliceMessage);
+                }
+                _ => {}
             }
+        }
 
-            let start = slice_index * SLICE_SIZE;
-            let end = if slice_index == self.num_slices - 1 {
-                (self.num_slices - 1) * SLICE_SIZE + bytes.len()
-            } else {
-                (slice_index + 1) * SLICE_SIZE
-            };
+        if self.slices[slice_index].is_none() {
+            self.num_received_slices += 1;
+            self.received_bytes += bytes.len();
+            self.slices[slice_index] = Some(bytes);
 
-            self.sliced_data[start..end].copy_from_slice(bytes);
             log::trace!(
                 "Received slice {} from message {}. ({}/{})",
                 slice_index,
@@ -64,10 +65,24 @@ impl SliceConstructor {
             );
         }
 
+        if let (Some(slice_size), Some(Some(last_slice))) = (self.slice_size, self.slices.last()) {
+            if last_slice.len() > slice_size {
+                log::error!(
+                    "Invalid last slice_size for SliceMessage, got {}, expected less than {}.",
+                    last_slice.len(),
+                    slice_size,
+                );
+                return Err(ChannelError::InvalidSliceMessage);
+            }
+        }
+
         if self.num_received_slices == self.num_slices {
             log::trace!("Received all slices for message {}.", self.message_id);
-            let payload = std::mem::take(&mut self.sliced_data);
-            return Ok(Some(payload.into()));
+            let mut payload = BytesMut::with_capacity(self.received_bytes);
+            for slice in self.slices.iter_mut() {
+                payload.extend_from_slice(&slice.take().unwrap());
+            }
+            return Ok(Some(payload.freeze()));
         }
 
         Ok(None)
@@ -0,0 +1,307 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    io::{ErrorKind, Read},
+};
+
+use bytes::Bytes;
+use octets::OctetsMut;
+
+use crate::{error::ChannelError, packet::MIN_SLICE_SIZE};
+
+const FRAME_DATA: u8 = 0;
+const FRAME_FINISHED: u8 = 1;
+const FRAME_ABORTED: u8 = 2;
+
+// Stream id (varint) + frame kind
+const MAX_FRAME_HEADER_SIZE: usize = 8 + 1;
+const _: () = assert!(MIN_SLICE_SIZE > MAX_FRAME_HEADER_SIZE);
+
+// Completed and aborted streams kept for their progress until removed, the oldest ones are dropped first
+const MAX_FINISHED_STREAMS: usize = 64;
+
+/// Current state of a stream being sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamState {
+    /// The stream is still being read or waiting for acknowledgement of its data.
+    Sending,
+    /// All the data in the stream was read and acknowledged by the remote connection.
+    Completed,
+    /// An error occurred while reading the stream, the remote connection receives a [StreamChunk::Aborted].
+    Aborted,
+}
+
+/// Progress of a stream being sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamProgress {
+    /// Number of bytes read from the stream and queued in the channel.
+    pub bytes_sent: u64,
+    /// Number of bytes acknowledged by the remote connection.
+    pub bytes_acked: u64,
+    /// Whether the stream is still being sent, completed or aborted.
+    pub state: StreamState,
+}
+
+/// Part of a stream received over a stream channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamChunk {
+    /// Next bytes of the stream.
+    Data { stream_id: u64, data: Bytes },
+    /// All data of the stream was received.
+    Finished { stream_id: u64 },
+    /// The sender failed to read the stream, no more data will be received for it.
+    Aborted { stream_id: u64 },
+}
+
+struct OutgoingStream {
+    stream_id: u64,
+    reader: Box<dyn Read + Send + Sync>,
+}
+
+impl fmt::Debug for OutgoingStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OutgoingStream")
+            .field("stream_id", &self.stream_id)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug)]
+struct StreamInfo {
+    progress: StreamProgress,
+    unacked_frames: usize,
+    finished_reading: bool,
+}
+
+/// Streams queued in a send channel, they are read one at a time in the order they were added.
+#[derive(Debug, Default)]
+pub(crate) struct SendStreams {
+    next_stream_id: u64,
+    pending: VecDeque<OutgoingStream>,
+    streams: HashMap<u64, StreamInfo>,
+    // Stream id and data length of each unacked frame
+    unacked_frames: HashMap<u64, (u64, usize)>,
+    // Completed and aborted streams, in the order they finished
+    finished_streams: VecDeque<u64>,
+}
+
+impl SendStreams {
+    pub fn add(&mut self, reader: Box<dyn Read + Send + Sync>) -> u64 {
+        let stream_id = self.next_stream_id;
+        self.next_stream_id += 1;
+
+        self.pending.push_back(OutgoingStream { stream_id, reader });
+        self.streams.insert(
+            stream_id,
+            StreamInfo {
+                progress: StreamProgress {
+                    bytes_sent: 0,
+                    bytes_acked: 0,
+                    state: StreamState::Sending,
+                },
+                unacked_frames: 0,
+                finished_reading: false,
+            },
+        );
+
+        stream_id
+    }
+
+    pub fn progress(&self, stream_id: u64) -> Option<StreamProgress> {
+        self.streams.get(&stream_id).map(|info| info.progress)
+    }
+
+    /// Removes a completed or aborted stream and returns its final progress, streams still being sent are not removed.
+    pub fn remove(&mut self, stream_id: u64) -> Option<StreamProgress> {
+        let progress = self.progress(stream_id)?;
+        if progress.state == StreamState::Sending {
+            return None;
+        }
+
+        self.streams.remove(&stream_id);
+        self.finished_streams.retain(|&finished_id| finished_id != stream_id);
+        Some(progress)
+    }
+
+    fn stream_finished(&mut self, stream_id: u64) {
+        self.finished_streams.push_back(stream_id);
+        if self.finished_streams.len() > MAX_FINISHED_STREAMS {
+            let oldest_stream_id = self.finished_streams.pop_front().unwrap();
+            self.streams.remove(&oldest_stream_id);
+        }
+    }
+
+    /// Reads the next frame from the current stream, frames are never bigger than the slice size,
+    /// this way chunks are delivered as soon as they arrive.
+    /// Returns None when there is no stream to read or the reader has no data available right now.
+    pub fn next_frame(&mut self, slice_size: usize) -> Option<(u64, Bytes, usize)> {
+        let stream = self.pending.front_mut()?;
+        let stream_id = stream.stream_id;
+
+        let mut buffer = vec![0u8; slice_size - MAX_FRAME_HEADER_SIZE];
+        let read = loop {
+            match stream.reader.read(&mut buffer) {
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                result => break result,
+            }
+        };
+
+        let info = self.streams.get_mut(&stream_id).unwrap();
+        let frame = match read {
+            Ok(0) => {
+                self.pending.pop_front();
+                info.finished_reading = true;
+                encode_frame(stream_id, FRAME_FINISHED, &[])
+            }
+            Ok(len) => {
+                info.progress.bytes_sent += len as u64;
+                return Some((stream_id, encode_frame(stream_id, FRAME_DATA, &buffer[..len]), len));
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => return None,
+            Err(e) => {
+                log::error!("Failed to read stream {stream_id}, aborting it: {e}");
+                self.pending.pop_front();
+                info.progress.state = StreamState::Aborted;
+                self.stream_finished(stream_id);
+                encode_frame(stream_id, FRAME_ABORTED, &[])
+            }
+        };
+
+        Some((stream_id, frame, 0))
+    }
+
+    pub fn frame_sent(&mut self, message_id: u64, stream_id: u64, data_len: usize) {
+        self.streams.get_mut(&stream_id).unwrap().unacked_frames += 1;
+        self.unacked_frames.insert(message_id, (stream_id, data_len));
+    }
+
+    pub fn message_acked(&mut self, message_id: u64) {
+        let Some((stream_id, data_len)) = self.unacked_frames.remove(&message_id) else {
+            return;
+        };
+
+        // Aborted streams can be removed before all their frames are acked
+        let Some(info) = self.streams.get_mut(&stream_id) else {
+            return;
+        };
+        info.unacked_frames -= 1;
+        info.progress.bytes_acked += data_len as u64;
+        if info.finished_reading && info.unacked_frames == 0 {
+            info.progress.state = StreamState::Completed;
+            self.stream_finished(stream_id);
+        }
+    }
+}
+
+fn encode_frame(stream_id: u64, kind: u8, data: &[u8]) -> Bytes {
+    let mut frame = vec![0u8; octets::varint_len(stream_id) + 1 + data.len()];
+    let mut oct = OctetsMut::with_slice(&mut frame);
+    oct.put_varint(stream_id).expect("stream frame buffer should fit the stream id");
+    oct.put_u8(kind).expect("stream frame buffer should fit the frame kind");
+    oct.put_bytes(data).expect("stream frame buffer should fit the data");
+
+    frame.into()
+}
+
+pub(crate) fn decode_frame(frame: &Bytes) -> Result<StreamChunk, ChannelError> {
+    let mut oct = octets::Octets::with_slice(frame);
+    let stream_id = oct.get_varint().map_err(|_| ChannelError::InvalidStreamMessage)?;
+    let kind = oct.get_u8().map_err(|_| ChannelError::InvalidStreamMessage)?;
+
+    match kind {
+        FRAME_DATA => Ok(StreamChunk::Data {
+            stream_id,
+            data: frame.slice(oct.off()..),
+        }),
+        FRAME_FINISHED if oct.cap() == 0 => Ok(StreamChunk::Finished { stream_id }),
+        FRAME_ABORTED if oct.cap() == 0 => Ok(StreamChunk::Aborted { stream_id }),
+        _ => Err(ChannelError::InvalidStreamMessage),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::packet::DEFAULT_SLICE_SIZE;
+
+    const MAX_STREAM_CHUNK_SIZE: usize = DEFAULT_SLICE_SIZE - MAX_FRAME_HEADER_SIZE;
+
+    #[test]
+    fn stream_frames() {
+        let mut streams = SendStreams::default();
+        let data: Vec<u8> = (0..MAX_STREAM_CHUNK_SIZE + 10).map(|i| i as u8).collect();
+        let stream_id = streams.add(Box::new(Cursor::new(data.clone())));
+
+        let (id, frame, len) = streams.next_frame(DEFAULT_SLICE_SIZE).unwrap();
+        assert_eq!(id, stream_id);
+        assert_eq!(len, MAX_STREAM_CHUNK_SIZE);
+        assert!(frame.len() <= DEFAULT_SLICE_SIZE);
+        assert_eq!(
+            decode_frame(&frame).unwrap(),
+            StreamChunk::Data {
+                stream_id,
+                data: Bytes::copy_from_slice(&data[..MAX_STREAM_CHUNK_SIZE])
+            }
+        );
+        streams.frame_sent(0, id, len);
+
+        let (_, frame, len) = streams.next_frame(DEFAULT_SLICE_SIZE).unwrap();
+        assert_eq!(len, 10);
+        streams.frame_sent(1, id, len);
+
+        let (_, frame_end, len) = streams.next_frame(DEFAULT_SLICE_SIZE).unwrap();
+        assert_eq!(decode_frame(&frame_end).unwrap(), StreamChunk::Finished { stream_id });
+        streams.frame_sent(2, id, len);
+        assert!(streams.next_frame(DEFAULT_SLICE_SIZE).is_none());
+
+        let progress = streams.progress(stream_id).unwrap();
+        assert_eq!(progress.bytes_sent, data.len() as u64);
+        assert_eq!(progress.bytes_acked, 0);
+        assert_eq!(progress.state, StreamState::Sending);
+
+        streams.message_acked(1);
+        streams.message_acked(2);
+        assert_eq!(streams.progress(stream_id).unwrap().bytes_acked, 10);
+        assert_eq!(streams.progress(stream_id).unwrap().state, StreamState::Sending);
+
+        streams.message_acked(0);
+        let progress = streams.progress(stream_id).unwrap();
+        assert_eq!(progress.bytes_acked, data.len() as u64);
+        assert_eq!(progress.state, StreamState::Completed);
+
+        assert_eq!(streams.progress(stream_id), Some(progress));
+
+        assert_eq!(streams.remove(stream_id), Some(progress));
+        assert!(streams.progress(stream_id).is_none());
+        assert!(streams.streams.is_empty());
+        assert!(streams.finished_streams.is_empty());
+
+        assert!(matches!(decode_frame(&frame).unwrap(), StreamChunk::Data { .. }));
+        assert_eq!(decode_frame(&Bytes::from_static(&[0, 3])), Err(ChannelError::InvalidStreamMessage));
+    }
+
+    #[test]
+    fn finished_streams_retention() {
+        let mut streams = SendStreams::default();
+        let mut message_id = 0;
+        let mut stream_ids = vec![];
+        for _ in 0..MAX_FINISHED_STREAMS + 1 {
+            let stream_id = streams.add(Box::new(Cursor::new(vec![1, 2, 3])));
+            while let Some((id, _, len)) = streams.next_frame(DEFAULT_SLICE_SIZE) {
+                streams.frame_sent(message_id, id, len);
+                streams.message_acked(message_id);
+                message_id += 1;
+            }
+            assert_eq!(streams.progress(stream_id).unwrap().state, StreamState::Completed);
+            stream_ids.push(stream_id);
+        }
+
+        // Only the latest finished streams are kept
+        assert!(streams.progress(stream_ids[0]).is_none());
+        assert_eq!(streams.streams.len(), MAX_FINISHED_STREAMS);
+        assert_eq!(streams.finished_streams.len(), MAX_FINISHED_STREAMS);
+        assert!(streams.progress(stream_ids[1]).is_some());
+    }
+}
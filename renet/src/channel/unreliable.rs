@@ -8,7 +8,7 @@ use bytes::Bytes;
 use crate::{
     channel::SliceConstructor,
     error::ChannelError,
-    packet::{Packet, Slice, SLICE_SIZE},
+    packet::{Packet, PacketPool, Slice, DEFAULT_SLICE_SIZE},
 };
 
 #[derive(Debug)]
@@ -18,6 +18,7 @@ pub struct SendChannelUnreliable {
     sliced_message_id: u64,
     max_memory_usage_bytes: usize,
     memory_usage_bytes: usize,
+    slice_size: usize,
 }
 
 #[derive(Debug)]
@@ -38,9 +39,14 @@ impl SendChannelUnreliable {
             sliced_message_id: 0,
             max_memory_usage_bytes,
             memory_usage_bytes: 0,
+            slice_size: DEFAULT_SLICE_SIZE,
         }
     }
 
+    pub fn set_slice_size(&mut self, slice_size: usize) {
+        self.slice_size = slice_size;
+    }
+
     pub fn can_send_message(&self, size_bytes: usize) -> bool {
         size_bytes + self.memory_usage_bytes <= self.max_memory_usage_bytes
     }
@@ -49,9 +55,15 @@ impl SendChannelUnreliable {
         self.max_memory_usage_bytes - self.memory_usage_bytes
     }
 
-    pub fn get_packets_to_send(&mut self, packet_sequence: &mut u64, available_bytes: &mut u64) -> Vec<Packet> {
-        let mut packets: Vec<Packet> = vec![];
-        let mut small_messages: Vec<Bytes> = vec![];
+    /// Appends the packets to be sent to `packets`, using the pool for the vectors of new packets.
+    pub fn get_packets_to_send(
+        &mut self,
+        packets: &mut Vec<Packet>,
+        pool: &mut PacketPool,
+        packet_sequence: &mut u64,
+        available_bytes: &mut u64,
+    ) {
+        let mut small_messages: Vec<Bytes> = pool.unreliable_messages();
         let mut small_messages_bytes = 0;
 
         while let Some(message) = self.unreliable_messages.pop_front() {
@@ -62,12 +74,12 @@ impl SendChannelUnreliable {
             }
 
             *available_bytes -= message.len() as u64;
-            if message.len() > SLICE_SIZE {
-                let num_slices = (message.len() + SLICE_SIZE - 1) / SLICE_SIZE;
+            if message.len() > self.slice_size {
+                let num_slices = (message.len() + self.slice_size - 1) / self.slice_size;
 
                 for slice_index in 0..num_slices {
-                    let start = slice_index * SLICE_SIZE;
-                    let end = if slice_index == num_slices - 1 { message.len() } else { (slice_index + 1) * SLICE_SIZE };
+                    let start = slice_index * self.slice_size;
+                    let end = if slice_index == num_slices - 1 { message.len() } else { (slice_index + 1) * self.slice_size };
                     let payload = message.slice(start..end);
 
                     let slice = Slice {
@@ -88,11 +100,11 @@ impl SendChannelUnreliable {
                 self.sliced_message_id += 1;
             } else {
                 let serialized_size = message.len() + octets::varint_len(message.len() as u64);
-                if small_messages_bytes + serialized_size > SLICE_SIZE {
+                if small_messages_bytes + serialized_size > self.slice_size {
                     packets.push(Packet::SmallUnreliable {
                         sequence: *packet_sequence,
                         channel_id: self.channel_id,
-                        messages: std::mem::take(&mut small_messages),
+                        messages: std::mem::replace(&mut small_messages, pool.unreliable_messages()),
                     });
                     *packet_sequence += 1;
                     small_messages_bytes = 0;
@@ -108,12 +120,12 @@ impl SendChannelUnreliable {
             packets.push(Packet::SmallUnreliable {
                 sequence: *packet_sequence,
                 channel_id: self.channel_id,
-                messages: std::mem::take(&mut small_messages),
+                messages: small_messages,
             });
             *packet_sequence += 1;
+        } else {
+            pool.recycle_unreliable_messages(small_messages);
         }
-
-        packets
     }
 
     pub fn send_message(&mut self, message: Bytes) {
@@ -155,18 +167,19 @@ impl ReceiveChannelUnreliable {
         self.messages.push_back(message);
     }
 
-    pub fn process_slice(&mut self, slice: Slice, current_time: Duration) -> Result<(), ChannelError> {
+    /// Processes a slice of a message, the min slice size is the configured slice size of the connection,
+    /// all slices of a message except the last one have at least this size.
+    pub fn process_slice(&mut self, slice: Slice, min_slice_size: usize, current_time: Duration) -> Result<(), ChannelError> {
         if !self.slices.contains_key(&slice.message_id) {
-            let message_len = slice.num_slices * SLICE_SIZE;
-            if self.memory_usage_bytes + message_len > self.max_memory_usage_bytes {
+            // Check the message can fit in the channel before allocating its slices
+            let min_message_len = (slice.num_slices - 1).saturating_mul(min_slice_size) + 1;
+            if self.memory_usage_bytes + min_message_len > self.max_memory_usage_bytes {
                 log::warn!(
                     "dropped unreliable slice message received because channel {} is memory limited",
                     self.channel_id
                 );
                 return Ok(());
             }
-
-            self.memory_usage_bytes += message_len;
         }
 
         let slice_constructor = self
@@ -174,10 +187,23 @@ impl ReceiveChannelUnreliable {
             .entry(slice.message_id)
             .or_insert_with(|| SliceConstructor::new(slice.message_id, slice.num_slices));
 
-        if let Some(message) = slice_constructor.process_slice(slice.slice_index, &slice.payload)? {
+        let is_new_slice = !slice_constructor.is_received(slice.slice_index);
+        if is_new_slice && self.memory_usage_bytes + slice.payload.len() > self.max_memory_usage_bytes {
+            log::warn!(
+                "dropped unreliable slice message received because channel {} is memory limited",
+                self.channel_id
+            );
+            return Ok(());
+        }
+
+        let received_bytes = slice_constructor.received_bytes;
+        let message = slice_constructor.process_slice(slice.slice_index, slice.payload)?;
+        self.memory_usage_bytes += slice_constructor.received_bytes - received_bytes;
+
+        if let Some(message) = message {
+            self.memory_usage_bytes -= slice_constructor.received_bytes;
             self.slices.remove(&slice.message_id);
             self.slices_last_received.remove(&slice.message_id);
-            self.memory_usage_bytes -= slice.num_slices * SLICE_SIZE;
             self.memory_usage_bytes += message.len();
             self.messages.push_back(message);
         } else {
@@ -203,7 +229,7 @@ impl ReceiveChannelUnreliable {
         for message_id in lost_messages.iter() {
             self.slices_last_received.remove(message_id);
             let slice = self.slices.remove(message_id).expect("discarded slice should exist");
-            self.memory_usage_bytes -= slice.num_slices * SLICE_SIZE;
+            self.memory_usage_bytes -= slice.received_bytes;
         }
     }
 
@@ -223,6 +249,12 @@ mod tests {
 
     use super::*;
 
+    fn get_packets_to_send(send: &mut SendChannelUnreliable, sequence: &mut u64, available_bytes: &mut u64) -> Vec<Packet> {
+        let mut packets = vec![];
+        send.get_packets_to_send(&mut packets, &mut PacketPool::default(), sequence, available_bytes);
+        packets
+    }
+
     #[test]
     fn small_packet() {
         let max_memory: usize = 10000;
@@ -237,7 +269,7 @@ mod tests {
         send.send_message(message1.clone().into());
         send.send_message(message2.clone().into());
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes);
         for packet in packets {
             let Packet::SmallUnreliable { messages, .. } = packet else {
                 unreachable!();
@@ -254,7 +286,7 @@ mod tests {
         assert_eq!(message1, new_message1);
         assert_eq!(message2, new_message2);
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes);
         assert!(packets.is_empty());
     }
 
@@ -267,16 +299,16 @@ mod tests {
         let mut recv = ReceiveChannelUnreliable::new(0, max_memory);
         let mut send = SendChannelUnreliable::new(0, max_memory);
 
-        let message = vec![5; SLICE_SIZE * 3];
+        let message = vec![5; DEFAULT_SLICE_SIZE * 3];
 
         send.send_message(message.clone().into());
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes);
         for packet in packets {
             let Packet::UnreliableSlice { slice, .. } = packet else {
                 unreachable!();
             };
-            recv.process_slice(slice, current_time).unwrap();
+            recv.process_slice(slice, DEFAULT_SLICE_SIZE, current_time).unwrap();
         }
 
         let new_message = recv.receive_message().unwrap();
@@ -284,8 +316,18 @@ mod tests {
 
         assert_eq!(message, new_message);
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes);
         assert!(packets.is_empty());
+
+        // Messages that could not fit in the channel are dropped before allocating their slices
+        let slice = Slice {
+            message_id: 1,
+            slice_index: 0,
+            num_slices: 1_000_000,
+            payload: vec![0; DEFAULT_SLICE_SIZE].into(),
+        };
+        recv.process_slice(slice, DEFAULT_SLICE_SIZE, current_time).unwrap();
+        assert!(recv.slices.is_empty());
     }
 
     #[test]
@@ -300,7 +342,7 @@ mod tests {
         send.send_message(message.clone().into());
         send.send_message(message.into());
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes);
         for packet in packets {
             let Packet::SmallUnreliable { messages, .. } = packet else {
                 unreachable!();
@@ -327,12 +369,12 @@ mod tests {
 
         // No available bytes
         let mut available_bytes: u64 = 50;
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes);
         assert_eq!(packets.len(), 0);
 
         // Available space but message was dropped
         let mut available_bytes: u64 = u64::MAX;
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes);
         assert_eq!(packets.len(), 0);
 
         send.send_message(message.clone());
@@ -340,12 +382,12 @@ mod tests {
 
         // Space for 1 message
         let mut available_bytes: u64 = 100;
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes);
         assert_eq!(packets.len(), 1);
 
         // Second message was dropped
         let mut available_bytes: u64 = u64::MAX;
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes);
         assert_eq!(packets.len(), 0);
     }
 
@@ -363,7 +405,7 @@ mod tests {
             send.send_message(message.clone());
         }
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = get_packets_to_send(&mut send, &mut sequence, &mut available_bytes);
         assert_eq!(packets.len(), 2);
         let mut buffer = [0u8; 1400];
         for packet in packets {
@@ -30,6 +30,10 @@ pub enum ChannelError {
     ReliableChannelMaxMemoryReached,
     /// Received an invalid slice message in the channel.
     InvalidSliceMessage,
+    /// Received a message without a key in a keyed channel.
+    InvalidKeyedMessage,
+    /// Received an invalid stream frame in a stream channel.
+    InvalidStreamMessage,
 }
 
 impl fmt::Display for ChannelError {
@@ -39,6 +43,8 @@ impl fmt::Display for ChannelError {
         match *self {
             ReliableChannelMaxMemoryReached => write!(fmt, "reliable channel memory usage was exausted"),
             InvalidSliceMessage => write!(fmt, "received an invalid slice packet"),
+            InvalidKeyedMessage => write!(fmt, "received a keyed message without a valid key"),
+            InvalidStreamMessage => write!(fmt, "received an invalid stream frame"),
         }
     }
 }
@@ -1,17 +1,21 @@
 mod channel;
 mod connection_stats;
 mod error;
+mod mtu_discovery;
 mod packet;
 mod remote_connection;
 mod server;
+mod transport_traits;
 
 #[cfg(feature = "transport")]
 pub mod transport;
 
-pub use channel::{ChannelConfig, DefaultChannel, SendType};
+pub use channel::{ChannelConfig, DefaultChannel, SendType, StreamChunk, StreamProgress, StreamState};
 pub use error::{ChannelError, ClientNotFound, DisconnectReason};
-pub use remote_connection::{ConnectionConfig, NetworkInfo, RenetClient};
-pub use server::{RenetServer, ServerEvent};
+pub use mtu_discovery::MtuDiscoveryConfig;
+pub use remote_connection::{ClientEvent, ConnectionConfig, NetworkInfo, RenetClient};
+pub use server::{ConnectionInfo, RenetServer, ServerEvent, TransportKind};
+pub use transport_traits::{ClientTransport, ServerTransport};
 
 pub use bytes::Bytes;
 
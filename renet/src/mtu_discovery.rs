@@ -0,0 +1,182 @@
+use std::time::Duration;
+
+use crate::packet::MAX_SLICE_PACKET_HEADER_SIZE;
+
+// Stop searching when the next probe would increase the slice size by less than this
+const MIN_PROBE_STEP: usize = 16;
+// Number of lost probes before considering a slice size unsupported
+const MAX_LOST_PROBES: usize = 2;
+// Number of consecutive big packets lost before going back to the configured slice size
+const MAX_LOST_BIG_PACKETS: usize = 3;
+
+/// Configuration for path MTU discovery.
+/// The connection starts with the configured slice size, and periodically sends probe packets to check
+/// if the network supports bigger packets. When a probe is acknowledged the slice size is increased,
+/// if packets bigger than the configured slice size start getting lost, it goes back to the configured value.
+#[derive(Debug, Clone)]
+pub struct MtuDiscoveryConfig {
+    /// Maximum slice size that can be reached.
+    /// Transports with a payload limit lower it to fit their payloads,
+    /// the netcode transport is limited to payloads of 1300 bytes including the packet header.
+    pub max_slice_size: usize,
+    /// Minimum time between probes.
+    pub probe_interval: Duration,
+}
+
+impl Default for MtuDiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            // Fits jumbo frames of 9000 bytes
+            max_slice_size: 8800,
+            probe_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct MtuDiscovery {
+    min_slice_size: usize,
+    max_slice_size: usize,
+    slice_size: usize,
+    probe_interval: Duration,
+    next_probe_time: Duration,
+    probe_in_flight: bool,
+    lost_probes: usize,
+    lost_big_packets: usize,
+}
+
+impl MtuDiscovery {
+    pub fn new(slice_size: usize, config: MtuDiscoveryConfig) -> Self {
+        Self {
+            min_slice_size: slice_size,
+            max_slice_size: config.max_slice_size.max(slice_size),
+            slice_size,
+            probe_interval: config.probe_interval,
+            next_probe_time: Duration::ZERO,
+            probe_in_flight: false,
+            lost_probes: 0,
+            lost_big_packets: 0,
+        }
+    }
+
+    pub fn slice_size(&self) -> usize {
+        self.slice_size
+    }
+
+    /// Limits the slice sizes probed, it never goes below the configured slice size.
+    pub fn limit_max_slice_size(&mut self, max_slice_size: usize) {
+        self.max_slice_size = self.max_slice_size.min(max_slice_size).max(self.min_slice_size);
+        self.slice_size = self.slice_size.min(self.max_slice_size);
+    }
+
+    /// Returns the slice size to be probed, if a probe should be sent now.
+    pub fn next_probe(&mut self, current_time: Duration) -> Option<usize> {
+        if self.probe_in_flight || current_time < self.next_probe_time {
+            return None;
+        }
+
+        let step = (self.max_slice_size - self.slice_size).div_ceil(2);
+        if step < MIN_PROBE_STEP {
+            return None;
+        }
+
+        self.probe_in_flight = true;
+        self.next_probe_time = current_time + self.probe_interval;
+        Some(self.slice_size + step)
+    }
+
+    pub fn probe_acked(&mut self, slice_size: usize) {
+        self.probe_in_flight = false;
+        self.lost_probes = 0;
+        self.slice_size = self.slice_size.max(slice_size);
+    }
+
+    pub fn probe_lost(&mut self, slice_size: usize) {
+        self.probe_in_flight = false;
+        self.lost_probes += 1;
+        if self.lost_probes >= MAX_LOST_PROBES {
+            self.lost_probes = 0;
+            self.max_slice_size = self.max_slice_size.min(slice_size - 1).max(self.slice_size);
+        }
+    }
+
+    fn is_big_packet(&self, packet_len: usize) -> bool {
+        packet_len > self.min_slice_size + MAX_SLICE_PACKET_HEADER_SIZE
+    }
+
+    pub fn packet_acked(&mut self, packet_len: usize) {
+        if self.is_big_packet(packet_len) {
+            self.lost_big_packets = 0;
+        }
+    }
+
+    pub fn packet_lost(&mut self, packet_len: usize) {
+        if !self.is_big_packet(packet_len) {
+            return;
+        }
+
+        self.lost_big_packets += 1;
+        if self.lost_big_packets >= MAX_LOST_BIG_PACKETS {
+            // The path may no longer support the current size, back off to the configured one
+            // and search again only below it.
+            log::debug!(
+                "Lost {} big packets, reducing slice size to {}",
+                self.lost_big_packets,
+                self.min_slice_size
+            );
+            self.lost_big_packets = 0;
+            self.max_slice_size = self.slice_size - 1;
+            self.slice_size = self.min_slice_size;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mtu_search() {
+        let config = MtuDiscoveryConfig {
+            max_slice_size: 2000,
+            probe_interval: Duration::from_secs(1),
+        };
+        let mut mtu = MtuDiscovery::new(1200, config);
+
+        let probe = mtu.next_probe(Duration::ZERO).unwrap();
+        assert_eq!(probe, 1600);
+        // Only one probe at a time
+        assert!(mtu.next_probe(Duration::from_secs(5)).is_none());
+
+        mtu.probe_acked(probe);
+        assert_eq!(mtu.slice_size(), 1600);
+        // Wait for the probe interval
+        assert!(mtu.next_probe(Duration::from_millis(500)).is_none());
+
+        let probe = mtu.next_probe(Duration::from_secs(1)).unwrap();
+        assert_eq!(probe, 1800);
+        mtu.probe_lost(probe);
+        let probe = mtu.next_probe(Duration::from_secs(2)).unwrap();
+        assert_eq!(probe, 1800);
+        mtu.probe_lost(probe);
+        assert_eq!(mtu.slice_size(), 1600);
+
+        // Search continues below the failed size
+        let probe = mtu.next_probe(Duration::from_secs(3)).unwrap();
+        assert_eq!(probe, 1700);
+        mtu.probe_acked(probe);
+
+        // Loss of big packets goes back to the initial slice size
+        for _ in 0..MAX_LOST_BIG_PACKETS {
+            mtu.packet_lost(1000);
+        }
+        assert_eq!(mtu.slice_size(), 1700);
+        for _ in 0..MAX_LOST_BIG_PACKETS {
+            mtu.packet_lost(1700);
+        }
+        assert_eq!(mtu.slice_size(), 1200);
+
+        let probe = mtu.next_probe(Duration::from_secs(4)).unwrap();
+        assert_eq!(probe, 1450);
+    }
+}
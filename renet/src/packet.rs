@@ -3,8 +3,17 @@ use std::{fmt, ops::Range};
 
 pub type Payload = Vec<u8>;
 
-// Sliced messages are split into SLICE_SIZE bytes chunks
-pub const SLICE_SIZE: usize = 1200;
+// Sliced messages are split into chunks of the connection slice size
+pub const DEFAULT_SLICE_SIZE: usize = 1200;
+
+// Upper limit for the slice size, slice packets must still fit in a single UDP datagram
+pub const MAX_SLICE_SIZE: usize = 65_000;
+
+// Lower limit for the slice size, stream frames must fit their header and at least one byte of data
+pub const MIN_SLICE_SIZE: usize = 16;
+
+// Bytes used by the header of a slice packet in the worst case
+pub const MAX_SLICE_PACKET_HEADER_SIZE: usize = 1 + 8 + 1 + 8 + 8 + 8 + 8;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Slice {
@@ -46,6 +55,24 @@ pub enum Packet {
         sequence: u64,
         ack_ranges: Vec<Range<u64>>,
     },
+    // Packet padded to the size of a slice packet with the given slice size,
+    // used to discover if the network supports bigger packets.
+    MtuProbe {
+        sequence: u64,
+        slice_size: usize,
+    },
+}
+
+// Maximum number of vectors kept in each list of the packet pool
+const MAX_POOLED_VECS: usize = 1024;
+
+/// Vectors used when generating packets, reused between ticks to avoid allocations.
+#[derive(Debug, Default)]
+pub(crate) struct PacketPool {
+    reliable_messages: Vec<Vec<(u64, Bytes)>>,
+    unreliable_messages: Vec<Vec<Bytes>>,
+    ack_ranges: Vec<Vec<Range<u64>>>,
+    message_ids: Vec<Vec<u64>>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -69,7 +96,7 @@ impl fmt::Display for SerializationError {
             InvalidNumSlices => write!(fmt, "invalid number of slices"),
             InvalidAckRange => write!(fmt, "invalid ack range"),
             InvalidPacketType => write!(fmt, "invalid packet type"),
-            SliceSizeAboveLimit => write!(fmt, "invalid slice size, it's above the limit of {} bytes", SLICE_SIZE),
+            SliceSizeAboveLimit => write!(fmt, "invalid slice size, it's above the limit of {} bytes", MAX_SLICE_SIZE),
             EmptySlice => write!(fmt, "invalid slice, slices cannot be empty"),
         }
     }
@@ -81,6 +108,53 @@ impl From<octets::BufferTooShortError> for SerializationError {
     }
 }
 
+fn recycle<T>(pool: &mut Vec<Vec<T>>, mut vec: Vec<T>) {
+    if pool.len() < MAX_POOLED_VECS && vec.capacity() > 0 {
+        vec.clear();
+        pool.push(vec);
+    }
+}
+
+impl PacketPool {
+    pub fn reliable_messages(&mut self) -> Vec<(u64, Bytes)> {
+        self.reliable_messages.pop().unwrap_or_default()
+    }
+
+    pub fn unreliable_messages(&mut self) -> Vec<Bytes> {
+        self.unreliable_messages.pop().unwrap_or_default()
+    }
+
+    pub fn ack_ranges(&mut self) -> Vec<Range<u64>> {
+        self.ack_ranges.pop().unwrap_or_default()
+    }
+
+    pub fn message_ids(&mut self) -> Vec<u64> {
+        self.message_ids.pop().unwrap_or_default()
+    }
+
+    pub fn recycle_reliable_messages(&mut self, messages: Vec<(u64, Bytes)>) {
+        recycle(&mut self.reliable_messages, messages);
+    }
+
+    pub fn recycle_unreliable_messages(&mut self, messages: Vec<Bytes>) {
+        recycle(&mut self.unreliable_messages, messages);
+    }
+
+    pub fn recycle_message_ids(&mut self, message_ids: Vec<u64>) {
+        recycle(&mut self.message_ids, message_ids);
+    }
+
+    /// Keeps the vectors of a packet that was already serialized.
+    pub fn recycle_packet(&mut self, packet: Packet) {
+        match packet {
+            Packet::SmallReliable { messages, .. } => self.recycle_reliable_messages(messages),
+            Packet::SmallUnreliable { messages, .. } => self.recycle_unreliable_messages(messages),
+            Packet::Ack { ack_ranges, .. } => recycle(&mut self.ack_ranges, ack_ranges),
+            Packet::ReliableSlice { .. } | Packet::UnreliableSlice { .. } | Packet::MtuProbe { .. } => {}
+        }
+    }
+}
+
 impl Packet {
     pub fn sequence(&self) -> u64 {
         match self {
@@ -88,7 +162,8 @@ impl Packet {
             | Packet::SmallUnreliable { sequence, .. }
             | Packet::UnreliableSlice { sequence, .. }
             | Packet::ReliableSlice { sequence, .. }
-            | Packet::Ack { sequence, .. } => *sequence,
+            | Packet::Ack { sequence, .. }
+            | Packet::MtuProbe { sequence, .. } => *sequence,
         }
     }
 
@@ -200,6 +275,15 @@ impl Packet {
                     previous_range_start = range.start;
                 }
             }
+            Packet::MtuProbe { sequence, slice_size } => {
+                b.put_u8(5)?;
+                b.put_varint(*sequence)?;
+                b.put_varint(*slice_size as u64)?;
+
+                // Pad until the probe has the size of a slice packet
+                let padding = (MAX_SLICE_PACKET_HEADER_SIZE + slice_size).saturating_sub(before - b.cap());
+                b.put_bytes(&vec![0; padding])?;
+            }
         }
 
         Ok(before - b.cap())
@@ -261,7 +345,7 @@ impl Packet {
                     return Err(SerializationError::EmptySlice);
                 }
 
-                if payload.len() > SLICE_SIZE {
+                if payload.len() > MAX_SLICE_SIZE {
                     return Err(SerializationError::SliceSizeAboveLimit);
                 }
 
@@ -346,6 +430,19 @@ impl Packet {
 
                 Ok(Packet::Ack { sequence, ack_ranges })
             }
+            5 => {
+                // MtuProbe
+                let sequence = b.get_varint()?;
+                let slice_size = b.get_varint()? as usize;
+                if slice_size > MAX_SLICE_SIZE {
+                    return Err(SerializationError::SliceSizeAboveLimit);
+                }
+
+                // Skip padding
+                b.skip(b.cap())?;
+
+                Ok(Packet::MtuProbe { sequence, slice_size })
+            }
             _ => Err(SerializationError::InvalidPacketType),
         }
     }
@@ -400,7 +497,7 @@ mod tests {
                 message_id: 0,
                 slice_index: 0,
                 num_slices: 1,
-                payload: vec![5; SLICE_SIZE].into(),
+                payload: vec![5; DEFAULT_SLICE_SIZE].into(),
             },
         };
 
@@ -423,7 +520,7 @@ mod tests {
                 message_id: 0,
                 slice_index: 0,
                 num_slices: 1,
-                payload: vec![5; SLICE_SIZE].into(),
+                payload: vec![5; DEFAULT_SLICE_SIZE].into(),
             },
         };
 
@@ -451,4 +548,22 @@ mod tests {
         let recv_packet = Packet::from_bytes(&mut b).unwrap();
         assert_eq!(packet, recv_packet);
     }
+
+    #[test]
+    fn serialize_mtu_probe_packet() {
+        let mut buffer = [0u8; 1500];
+
+        let packet = Packet::MtuProbe {
+            sequence: 0,
+            slice_size: 1300,
+        };
+
+        let mut b = octets::OctetsMut::with_slice(&mut buffer);
+        let len = packet.to_bytes(&mut b).unwrap();
+        assert_eq!(len, 1300 + MAX_SLICE_PACKET_HEADER_SIZE);
+
+        let mut b = octets::Octets::with_slice(&buffer[..len]);
+        let recv_packet = Packet::from_bytes(&mut b).unwrap();
+        assert_eq!(packet, recv_packet);
+    }
 }
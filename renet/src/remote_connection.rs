@@ -1,13 +1,15 @@
 use crate::channel::reliable::{ReceiveChannelReliable, SendChannelReliable};
 use crate::channel::unreliable::{ReceiveChannelUnreliable, SendChannelUnreliable};
-use crate::channel::{ChannelConfig, DefaultChannel, SendType};
+use crate::channel::{ChannelConfig, DefaultChannel, SendType, StreamChunk, StreamProgress};
 use crate::connection_stats::ConnectionStats;
-use crate::error::DisconnectReason;
-use crate::packet::{Packet, Payload};
+use crate::error::{ChannelError, DisconnectReason};
+use crate::mtu_discovery::{MtuDiscovery, MtuDiscoveryConfig};
+use crate::packet::{Packet, PacketPool, Payload, DEFAULT_SLICE_SIZE, MAX_SLICE_PACKET_HEADER_SIZE, MAX_SLICE_SIZE, MIN_SLICE_SIZE};
 use bytes::Bytes;
 use octets::OctetsMut;
 
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::io::Read;
 use std::ops::Range;
 use std::time::Duration;
 
@@ -27,11 +29,20 @@ pub struct ConnectionConfig {
     /// Each tick, the first channel can consume up to `available_bytes_per_tick`,
     /// used bytes are removed from it and passed to the next channel
     pub client_channels_config: Vec<ChannelConfig>,
+    /// Size in bytes of the slices that big messages are split into, small messages are also grouped in packets up to this size.
+    /// The transport must support payloads up to this value plus some bytes for the packet header,
+    /// the netcode transport supports the default value. Must be between 16 and 65000 bytes.
+    /// Default: 1200
+    pub slice_size: usize,
+    /// When set, the slice size is increased while the network supports bigger packets.
+    /// Default: None
+    pub mtu_discovery: Option<MtuDiscoveryConfig>,
 }
 
 #[derive(Debug, Clone)]
 struct PacketSent {
     sent_at: Duration,
+    len: usize,
     info: PacketSentInfo,
 }
 
@@ -53,6 +64,9 @@ enum PacketSentInfo {
     Ack {
         largest_acked_packet: u64,
     },
+    MtuProbe {
+        slice_size: usize,
+    },
 }
 
 #[derive(Debug)]
@@ -61,6 +75,25 @@ enum ChannelOrder {
     Unreliable(u8),
 }
 
+// Fraction of the maximum memory of a reliable send channel that triggers a memory warning
+const CHANNEL_MEMORY_WARNING_THRESHOLD: f64 = 0.8;
+
+/// Events that happened in the client connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Event))]
+pub enum ClientEvent {
+    /// The client connected to the server.
+    Connected,
+    /// The client was disconnected.
+    Disconnected { reason: DisconnectReason },
+    /// A reliable send channel is using more than 80% of its maximum memory,
+    /// sent messages are not being acknowledged fast enough and the client disconnects if it reaches the maximum.
+    /// Emitted again only after the memory usage goes below the threshold.
+    ChannelMemoryWarning { channel_id: u8, memory_usage_bytes: usize },
+    /// A message sent with [RenetClient::send_tracked_message] was acknowledged by the server.
+    MessageDelivered { channel_id: u8, message_id: u64 },
+}
+
 /// Describes the stats of a connection.
 pub struct NetworkInfo {
     /// Round-trip Time
@@ -84,8 +117,25 @@ pub struct RenetClient {
     receive_reliable_channels: HashMap<u8, ReceiveChannelReliable>,
     stats: ConnectionStats,
     available_bytes_per_tick: u64,
+    slice_size: usize,
+    // Configured slice size, slices received are never smaller except for the last slice of a message
+    min_slice_size: usize,
+    max_slice_size: usize,
+    mtu_discovery: Option<MtuDiscovery>,
+    // Reused between calls to avoid allocations when generating packets
+    packets: Vec<Packet>,
+    packet_buffer: Vec<u8>,
+    packet_pool: PacketPool,
+    connected: bool,
+    // Connections created by the server don't keep events
+    emit_events: bool,
+    events: VecDeque<ClientEvent>,
+    disconnect_event_emitted: bool,
+    channels_memory_warned: HashSet<u8>,
     pub(crate) disconnect_reason: Option<DisconnectReason>,
     rtt: f64,
+    // Kept to reset the client when the transport reconnects
+    config: Option<ConnectionConfig>,
 }
 
 impl Default for ConnectionConfig {
@@ -95,34 +145,59 @@ impl Default for ConnectionConfig {
             available_bytes_per_tick: 60_000,
             server_channels_config: DefaultChannel::config(),
             client_channels_config: DefaultChannel::config(),
+            slice_size: DEFAULT_SLICE_SIZE,
+            mtu_discovery: None,
         }
     }
 }
 
 impl RenetClient {
     pub fn new(config: ConnectionConfig) -> Self {
-        Self::from_channels(
+        let mut client = Self::from_channels(
             config.available_bytes_per_tick,
-            config.client_channels_config,
-            config.server_channels_config,
-        )
+            config.slice_size,
+            config.mtu_discovery.clone(),
+            config.client_channels_config.clone(),
+            config.server_channels_config.clone(),
+        );
+        client.config = Some(config);
+
+        client
     }
 
     // When creating a client from the server, the server_channels_config are used as send channels,
     // and the client_channels_config is used as recv channels.
     pub(crate) fn new_from_server(config: ConnectionConfig) -> Self {
-        Self::from_channels(
+        let mut connection = Self::from_channels(
             config.available_bytes_per_tick,
+            config.slice_size,
+            config.mtu_discovery,
             config.server_channels_config,
             config.client_channels_config,
-        )
+        );
+        connection.connected = true;
+        connection.emit_events = false;
+
+        connection
     }
 
     fn from_channels(
         available_bytes_per_tick: u64,
+        slice_size: usize,
+        mtu_discovery: Option<MtuDiscoveryConfig>,
         send_channels_config: Vec<ChannelConfig>,
         receive_channels_config: Vec<ChannelConfig>,
     ) -> Self {
+        assert!(
+            (MIN_SLICE_SIZE..=MAX_SLICE_SIZE).contains(&slice_size),
+            "slice size must be between {MIN_SLICE_SIZE} and {MAX_SLICE_SIZE} bytes"
+        );
+        // Both sides of the connection share the configuration, so this is the biggest slice the remote can send
+        let max_slice_size = mtu_discovery
+            .as_ref()
+            .map_or(slice_size, |config| config.max_slice_size.clamp(slice_size, MAX_SLICE_SIZE));
+        let mtu_discovery = mtu_discovery.map(|config| MtuDiscovery::new(slice_size, config));
+
         let mut send_unreliable_channels = HashMap::new();
         let mut send_reliable_channels = HashMap::new();
         let mut channel_send_order: Vec<ChannelOrder> = Vec::with_capacity(send_channels_config.len());
@@ -140,6 +215,22 @@ impl RenetClient {
                     let old = send_reliable_channels.insert(channel_config.channel_id, channel);
                     assert!(old.is_none(), "already exists send channel {}", channel_config.channel_id);
 
+                    channel_send_order.push(ChannelOrder::Reliable(channel_config.channel_id));
+                }
+                SendType::ReliableKeyed { resend_time } => {
+                    let channel =
+                        SendChannelReliable::new_keyed(channel_config.channel_id, resend_time, channel_config.max_memory_usage_bytes);
+                    let old = send_reliable_channels.insert(channel_config.channel_id, channel);
+                    assert!(old.is_none(), "already exists send channel {}", channel_config.channel_id);
+
+                    channel_send_order.push(ChannelOrder::Reliable(channel_config.channel_id));
+                }
+                SendType::ReliableStream { resend_time } => {
+                    let channel =
+                        SendChannelReliable::new_stream(channel_config.channel_id, resend_time, channel_config.max_memory_usage_bytes);
+                    let old = send_reliable_channels.insert(channel_config.channel_id, channel);
+                    assert!(old.is_none(), "already exists send channel {}", channel_config.channel_id);
+
                     channel_send_order.push(ChannelOrder::Reliable(channel_config.channel_id));
                 }
             }
@@ -164,10 +255,20 @@ impl RenetClient {
                     let old = receive_reliable_channels.insert(channel_config.channel_id, channel);
                     assert!(old.is_none(), "already exists receive channel {}", channel_config.channel_id);
                 }
+                SendType::ReliableKeyed { .. } => {
+                    let channel = ReceiveChannelReliable::new_keyed(channel_config.max_memory_usage_bytes);
+                    let old = receive_reliable_channels.insert(channel_config.channel_id, channel);
+                    assert!(old.is_none(), "already exists receive channel {}", channel_config.channel_id);
+                }
+                SendType::ReliableStream { .. } => {
+                    let channel = ReceiveChannelReliable::new_stream(channel_config.max_memory_usage_bytes);
+                    let old = receive_reliable_channels.insert(channel_config.channel_id, channel);
+                    assert!(old.is_none(), "already exists receive channel {}", channel_config.channel_id);
+                }
             }
         }
 
-        Self {
+        let mut connection = Self {
             packet_sequence: 0,
             current_time: Duration::ZERO,
             sent_packets: BTreeMap::new(),
@@ -180,7 +281,45 @@ impl RenetClient {
             stats: ConnectionStats::new(),
             rtt: 0.0,
             available_bytes_per_tick,
+            slice_size,
+            min_slice_size: slice_size,
+            max_slice_size,
+            mtu_discovery,
+            packets: Vec::new(),
+            // Extra space for the packet headers
+            packet_buffer: vec![0u8; max_slice_size + 200],
+            packet_pool: PacketPool::default(),
+            connected: false,
+            emit_events: true,
+            events: VecDeque::new(),
+            disconnect_event_emitted: false,
+            channels_memory_warned: HashSet::new(),
             disconnect_reason: None,
+            config: None,
+        };
+        connection.set_slice_size(slice_size);
+
+        connection
+    }
+
+    fn set_slice_size(&mut self, slice_size: usize) {
+        debug_assert!(slice_size >= MIN_SLICE_SIZE, "slice size must be at least {MIN_SLICE_SIZE} bytes");
+        self.slice_size = slice_size;
+        for channel in self.send_reliable_channels.values_mut() {
+            channel.set_slice_size(slice_size);
+        }
+        for channel in self.send_unreliable_channels.values_mut() {
+            channel.set_slice_size(slice_size);
+        }
+    }
+
+    fn update_slice_size(&mut self) {
+        if let Some(mtu_discovery) = &self.mtu_discovery {
+            let slice_size = mtu_discovery.slice_size();
+            if slice_size != self.slice_size {
+                log::debug!("Changing slice size from {} to {}", self.slice_size, slice_size);
+                self.set_slice_size(slice_size);
+            }
         }
     }
 
@@ -204,6 +343,21 @@ impl RenetClient {
         self.stats.bytes_received_per_second(self.current_time)
     }
 
+    /// Sets the maximum packet size supported by the transport, path MTU discovery never probes slice sizes
+    /// that would need bigger packets. Called by transports with a payload limit.
+    pub fn set_max_packet_size(&mut self, max_packet_size: usize) {
+        if let Some(mtu_discovery) = &mut self.mtu_discovery {
+            mtu_discovery.limit_max_slice_size(max_packet_size.saturating_sub(MAX_SLICE_PACKET_HEADER_SIZE));
+        }
+        self.update_slice_size();
+    }
+
+    /// Returns the size in bytes of the slices that big messages are split into.
+    /// It can increase over time when path MTU discovery is enabled.
+    pub fn slice_size(&self) -> usize {
+        self.slice_size
+    }
+
     /// Returns all network informations for the connection.
     pub fn network_info(&self) -> NetworkInfo {
         NetworkInfo {
@@ -214,6 +368,57 @@ impl RenetClient {
         }
     }
 
+    /// Returns a client event if available
+    ///
+    /// # Usage
+    /// ```
+    /// # use renet::{RenetClient, ConnectionConfig, ClientEvent};
+    /// # let mut client = RenetClient::new(ConnectionConfig::default());
+    /// while let Some(event) = client.get_event() {
+    ///     match event {
+    ///         ClientEvent::Connected => println!("Connected to the server."),
+    ///         ClientEvent::Disconnected { reason } => println!("Disconnected: {reason}"),
+    ///         _ => {}
+    ///     }
+    /// }
+    /// ```
+    pub fn get_event(&mut self) -> Option<ClientEvent> {
+        if let Some(reason) = self.disconnect_reason {
+            if !self.disconnect_event_emitted {
+                self.disconnect_event_emitted = true;
+                self.push_event(ClientEvent::Disconnected { reason });
+            }
+        }
+
+        self.events.pop_front()
+    }
+
+    fn push_event(&mut self, event: ClientEvent) {
+        if self.emit_events {
+            self.events.push_back(event);
+        }
+    }
+
+    /// Returns if the client is connected.
+    /// The client is marked as connected by the transport layer.
+    pub fn is_connected(&self) -> bool {
+        self.connected && !self.is_disconnected()
+    }
+
+    /// Marks the client as connected.
+    /// If the client is already connected or disconnected, it does nothing.
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer.
+    /// </p>
+    pub fn set_connected(&mut self) {
+        if self.connected || self.is_disconnected() {
+            return;
+        }
+
+        self.connected = true;
+        self.push_event(ClientEvent::Connected);
+    }
+
     /// Returns if the client is disconnected.
     ///
     /// Note: to check if a client is connecting you need to use the transport layer [NetcodeClientTransport::is_connecting][crate::transport::NetcodeClientTransport::is_connecting].
@@ -251,6 +456,19 @@ impl RenetClient {
         self.disconnect_reason = Some(DisconnectReason::Transport);
     }
 
+    /// Resets the client to a new connection, discarding the channels state and the messages not yet sent or received.
+    /// Events not yet consumed are kept.
+    #[cfg(feature = "transport")]
+    pub(crate) fn reset(&mut self) {
+        let Some(config) = self.config.take() else {
+            return;
+        };
+
+        let events = std::mem::take(&mut self.events);
+        *self = Self::new(config);
+        self.events = events;
+    }
+
     /// Returns the available memory in bytes for the given channel.
     pub fn channel_available_memory<I: Into<u8>>(&self, channel_id: I) -> usize {
         let channel_id = channel_id.into();
@@ -283,6 +501,12 @@ impl RenetClient {
 
         let channel_id = channel_id.into();
         if let Some(reliable_channel) = self.send_reliable_channels.get_mut(&channel_id) {
+            if reliable_channel.is_keyed() {
+                panic!("Called 'send_message' with keyed channel {channel_id}, use 'send_keyed_message' instead");
+            }
+            if reliable_channel.is_stream() {
+                panic!("Called 'send_message' with stream channel {channel_id}, use 'send_stream' instead");
+            }
             if let Err(error) = reliable_channel.send_message(message.into()) {
                 self.disconnect_reason = Some(DisconnectReason::SendChannelError { channel_id, error });
             }
@@ -293,6 +517,80 @@ impl RenetClient {
         }
     }
 
+    /// Send a message to the server over a reliable channel, returns the id of the message.
+    /// A [ClientEvent::MessageDelivered] with the id is emitted when the message is acknowledged by the server.
+    /// Returns None if the client is disconnected.
+    pub fn send_tracked_message<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, message: B) -> Option<u64> {
+        if self.is_disconnected() {
+            return None;
+        }
+
+        let channel_id = channel_id.into();
+        match self.send_reliable_channels.get_mut(&channel_id) {
+            Some(reliable_channel) if !reliable_channel.is_keyed() && !reliable_channel.is_stream() => {
+                match reliable_channel.send_tracked_message(message.into()) {
+                    Ok(message_id) => Some(message_id),
+                    Err(error) => {
+                        self.disconnect_reason = Some(DisconnectReason::SendChannelError { channel_id, error });
+                        None
+                    }
+                }
+            }
+            _ => panic!("Called 'send_tracked_message' with invalid channel {channel_id}"),
+        }
+    }
+
+    /// Send a message with a key to the server over a keyed channel.
+    /// If an older message with the same key was not acknowledged yet, it is replaced by this one.
+    pub fn send_keyed_message<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, key: u64, message: B) {
+        if self.is_disconnected() {
+            return;
+        }
+
+        let channel_id = channel_id.into();
+        match self.send_reliable_channels.get_mut(&channel_id) {
+            Some(reliable_channel) if reliable_channel.is_keyed() => {
+                if let Err(error) = reliable_channel.send_keyed_message(key, message.into()) {
+                    self.disconnect_reason = Some(DisconnectReason::SendChannelError { channel_id, error });
+                }
+            }
+            _ => panic!("Called 'send_keyed_message' with invalid channel {channel_id}"),
+        }
+    }
+
+    /// Queue a reader to be streamed to the server over a stream channel, returns the id of the stream.
+    /// Streams in the same channel are sent one after the other, in the order they were queued.
+    /// The reader is only read when the channel has memory available, a reader returning
+    /// [WouldBlock][std::io::ErrorKind::WouldBlock] is polled again on the next packet generation.
+    pub fn send_stream<I: Into<u8>, R: Read + Send + Sync + 'static>(&mut self, channel_id: I, reader: R) -> u64 {
+        let channel_id = channel_id.into();
+        match self.send_reliable_channels.get_mut(&channel_id) {
+            Some(reliable_channel) if reliable_channel.is_stream() => reliable_channel.send_stream(Box::new(reader)),
+            _ => panic!("Called 'send_stream' with invalid channel {channel_id}"),
+        }
+    }
+
+    /// Returns the progress of a stream sent over a stream channel.
+    /// Completed and aborted streams are kept until removed with [RenetClient::remove_stream],
+    /// only the latest finished streams are kept.
+    pub fn stream_progress<I: Into<u8>>(&self, channel_id: I, stream_id: u64) -> Option<StreamProgress> {
+        let channel_id = channel_id.into();
+        match self.send_reliable_channels.get(&channel_id) {
+            Some(reliable_channel) if reliable_channel.is_stream() => reliable_channel.stream_progress(stream_id),
+            _ => panic!("Called 'stream_progress' with invalid channel {channel_id}"),
+        }
+    }
+
+    /// Removes a completed or aborted stream sent over a stream channel, returns its final progress.
+    /// Streams still being sent are not removed and return None.
+    pub fn remove_stream<I: Into<u8>>(&mut self, channel_id: I, stream_id: u64) -> Option<StreamProgress> {
+        let channel_id = channel_id.into();
+        match self.send_reliable_channels.get_mut(&channel_id) {
+            Some(reliable_channel) if reliable_channel.is_stream() => reliable_channel.remove_stream(stream_id),
+            _ => panic!("Called 'remove_stream' with invalid channel {channel_id}"),
+        }
+    }
+
     /// Receive a message from the server over a channel.
     pub fn receive_message<I: Into<u8>>(&mut self, channel_id: I) -> Option<Bytes> {
         if self.is_disconnected() {
@@ -301,6 +599,9 @@ impl RenetClient {
 
         let channel_id = channel_id.into();
         if let Some(reliable_channel) = self.receive_reliable_channels.get_mut(&channel_id) {
+            if reliable_channel.is_stream() {
+                panic!("Called 'receive_message' with stream channel {channel_id}, use 'receive_stream_chunk' instead");
+            }
             reliable_channel.receive_message()
         } else if let Some(unreliable_channel) = self.receive_unreliable_channels.get_mut(&channel_id) {
             unreliable_channel.receive_message()
@@ -309,6 +610,34 @@ impl RenetClient {
         }
     }
 
+    /// Receive a message and its key from the server over a keyed channel.
+    /// Only the most recent message for each key is received.
+    pub fn receive_keyed_message<I: Into<u8>>(&mut self, channel_id: I) -> Option<(u64, Bytes)> {
+        if self.is_disconnected() {
+            return None;
+        }
+
+        let channel_id = channel_id.into();
+        match self.receive_reliable_channels.get_mut(&channel_id) {
+            Some(reliable_channel) if reliable_channel.is_keyed() => reliable_channel.receive_keyed_message(),
+            _ => panic!("Called 'receive_keyed_message' with invalid channel {channel_id}"),
+        }
+    }
+
+    /// Receive the next chunk of a stream from the server over a stream channel.
+    /// Chunks are received in order, as soon as they arrive.
+    pub fn receive_stream_chunk<I: Into<u8>>(&mut self, channel_id: I) -> Option<StreamChunk> {
+        if self.is_disconnected() {
+            return None;
+        }
+
+        let channel_id = channel_id.into();
+        match self.receive_reliable_channels.get_mut(&channel_id) {
+            Some(reliable_channel) if reliable_channel.is_stream() => reliable_channel.receive_stream_chunk(),
+            _ => panic!("Called 'receive_stream_chunk' with invalid channel {channel_id}"),
+        }
+    }
+
     /// Advances the client by the duration.
     /// Should be called every tick
     pub fn update(&mut self, duration: Duration) {
@@ -320,20 +649,46 @@ impl RenetClient {
         }
 
         // Discard lost packets
-        let mut lost_packets: Vec<u64> = Vec::new();
-        for (&sequence, sent_packet) in self.sent_packets.iter() {
+        while let Some(entry) = self.sent_packets.first_entry() {
             const DISCARD_AFTER: Duration = Duration::from_secs(3);
-            if self.current_time - sent_packet.sent_at >= DISCARD_AFTER {
-                lost_packets.push(sequence);
-            } else {
+            if self.current_time - entry.get().sent_at < DISCARD_AFTER {
                 // If the current packet is not lost, the next ones will not be lost
                 // since all the next packets were sent after this one.
                 break;
             }
+
+            let sent_packet = entry.remove();
+            if let Some(mtu_discovery) = &mut self.mtu_discovery {
+                match sent_packet.info {
+                    PacketSentInfo::MtuProbe { slice_size } => mtu_discovery.probe_lost(slice_size),
+                    _ => mtu_discovery.packet_lost(sent_packet.len),
+                }
+            }
+            if let PacketSentInfo::ReliableMessages { message_ids, .. } = sent_packet.info {
+                self.packet_pool.recycle_message_ids(message_ids);
+            }
         }
+        self.update_slice_size();
 
-        for sequence in lost_packets.iter() {
-            self.sent_packets.remove(sequence);
+        if self.emit_events {
+            self.check_channels_memory();
+        }
+    }
+
+    fn check_channels_memory(&mut self) {
+        for (&channel_id, channel) in self.send_reliable_channels.iter() {
+            let memory_usage_bytes = channel.memory_usage_bytes();
+            let threshold = channel.max_memory_usage_bytes() as f64 * CHANNEL_MEMORY_WARNING_THRESHOLD;
+            if memory_usage_bytes as f64 >= threshold {
+                if self.channels_memory_warned.insert(channel_id) {
+                    self.events.push_back(ClientEvent::ChannelMemoryWarning {
+                        channel_id,
+                        memory_usage_bytes,
+                    });
+                }
+            } else {
+                self.channels_memory_warned.remove(&channel_id);
+            }
         }
     }
 
@@ -388,7 +743,13 @@ impl RenetClient {
                     return;
                 };
 
-                if let Err(error) = channel.process_slice(slice) {
+                if slice.payload.len() > self.max_slice_size {
+                    let error = ChannelError::InvalidSliceMessage;
+                    self.disconnect_reason = Some(DisconnectReason::ReceiveChannelError { channel_id, error });
+                    return;
+                }
+
+                if let Err(error) = channel.process_slice(slice, self.min_slice_size) {
                     self.disconnect_reason = Some(DisconnectReason::ReceiveChannelError { channel_id, error });
                 }
             }
@@ -398,7 +759,13 @@ impl RenetClient {
                     return;
                 };
 
-                if let Err(error) = channel.process_slice(slice, self.current_time) {
+                if slice.payload.len() > self.max_slice_size {
+                    let error = ChannelError::InvalidSliceMessage;
+                    self.disconnect_reason = Some(DisconnectReason::ReceiveChannelError { channel_id, error });
+                    return;
+                }
+
+                if let Err(error) = channel.process_slice(slice, self.min_slice_size, self.current_time) {
                     self.disconnect_reason = Some(DisconnectReason::ReceiveChannelError { channel_id, error });
                 }
             }
@@ -424,12 +791,23 @@ impl RenetClient {
                         self.rtt = self.rtt * 0.875 + rtt * 0.125;
                     }
 
+                    if let Some(mtu_discovery) = &mut self.mtu_discovery {
+                        match sent_packet.info {
+                            PacketSentInfo::MtuProbe { slice_size } => mtu_discovery.probe_acked(slice_size),
+                            _ => mtu_discovery.packet_acked(sent_packet.len),
+                        }
+                    }
+
                     match sent_packet.info {
                         PacketSentInfo::ReliableMessages { channel_id, message_ids } => {
                             let reliable_channel = self.send_reliable_channels.get_mut(&channel_id).unwrap();
-                            for message_id in message_ids {
+                            for &message_id in message_ids.iter() {
                                 reliable_channel.process_message_ack(message_id);
                             }
+                            self.packet_pool.recycle_message_ids(message_ids);
+                            for message_id in reliable_channel.take_delivered_messages() {
+                                self.push_event(ClientEvent::MessageDelivered { channel_id, message_id });
+                            }
                         }
                         PacketSentInfo::ReliableSliceMessage {
                             channel_id,
@@ -438,13 +816,20 @@ impl RenetClient {
                         } => {
                             let reliable_channel = self.send_reliable_channels.get_mut(&channel_id).unwrap();
                             reliable_channel.process_slice_message_ack(message_id, slice_index);
+                            for message_id in reliable_channel.take_delivered_messages() {
+                                self.push_event(ClientEvent::MessageDelivered { channel_id, message_id });
+                            }
                         }
                         PacketSentInfo::Ack { largest_acked_packet } => {
                             self.acked_largest(largest_acked_packet);
                         }
-                        PacketSentInfo::None => {}
+                        PacketSentInfo::None | PacketSentInfo::MtuProbe { .. } => {}
                     }
                 }
+                self.update_slice_size();
+            }
+            Packet::MtuProbe { .. } => {
+                // Probes only need to be acked
             }
         }
     }
@@ -454,122 +839,115 @@ impl RenetClient {
     /// <strong>Note:</strong> This should only be called by the transport layer.
     /// </p>
     pub fn get_packets_to_send(&mut self) -> Vec<Payload> {
-        let mut packets: Vec<Packet> = vec![];
+        let mut packets = vec![];
+        self.for_each_packet_to_send(|packet| packets.push(packet.to_vec()));
         if self.is_disconnected() {
             return vec![];
         }
 
+        packets
+    }
+
+    /// Generates the packets to be sent to the server, calling `send` with each one of them.
+    /// The packets are serialized in a buffer that is reused between calls,
+    /// this avoids the allocations made by [get_packets_to_send][Self::get_packets_to_send].
+    /// If a packet fails to serialize the client is disconnected and no more packets are generated.
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer.
+    /// </p>
+    pub fn for_each_packet_to_send<F: FnMut(&[u8])>(&mut self, mut send: F) {
+        if self.is_disconnected() {
+            return;
+        }
+
+        let mut packets = std::mem::take(&mut self.packets);
         let mut available_bytes = self.available_bytes_per_tick;
         for order in self.channel_send_order.iter() {
             match order {
                 ChannelOrder::Reliable(channel_id) => {
                     let channel = self.send_reliable_channels.get_mut(channel_id).unwrap();
-                    packets.append(&mut channel.get_packets_to_send(&mut self.packet_sequence, &mut available_bytes, self.current_time));
+                    channel.get_packets_to_send(
+                        &mut packets,
+                        &mut self.packet_pool,
+                        &mut self.packet_sequence,
+                        &mut available_bytes,
+                        self.current_time,
+                    );
                 }
                 ChannelOrder::Unreliable(channel_id) => {
                     let channel = self.send_unreliable_channels.get_mut(channel_id).unwrap();
-                    packets.append(&mut channel.get_packets_to_send(&mut self.packet_sequence, &mut available_bytes));
+                    channel.get_packets_to_send(&mut packets, &mut self.packet_pool, &mut self.packet_sequence, &mut available_bytes);
                 }
             }
         }
 
         if !self.pending_acks.is_empty() {
+            let mut ack_ranges = self.packet_pool.ack_ranges();
+            ack_ranges.extend_from_slice(&self.pending_acks);
             let ack_packet = Packet::Ack {
                 sequence: self.packet_sequence,
-                ack_ranges: self.pending_acks.clone(),
+                ack_ranges,
             };
             self.packet_sequence += 1;
             packets.push(ack_packet);
         }
 
-        let sent_at = self.current_time;
-        for packet in packets.iter() {
-            match packet {
-                Packet::SmallReliable {
-                    sequence,
-                    channel_id,
-                    messages,
-                } => {
-                    self.sent_packets.insert(
-                        *sequence,
-                        PacketSent {
-                            sent_at,
-                            info: PacketSentInfo::ReliableMessages {
-                                channel_id: *channel_id,
-                                message_ids: messages.iter().map(|(id, _)| *id).collect(),
-                            },
-                        },
-                    );
-                }
-                Packet::ReliableSlice {
-                    sequence,
-                    channel_id,
-                    slice,
-                } => {
-                    self.sent_packets.insert(
-                        *sequence,
-                        PacketSent {
-                            sent_at,
-                            info: PacketSentInfo::ReliableSliceMessage {
-                                channel_id: *channel_id,
-                                message_id: slice.message_id,
-                                slice_index: slice.slice_index,
-                            },
-                        },
-                    );
-                }
-                Packet::SmallUnreliable { sequence, .. } => {
-                    self.sent_packets.insert(
-                        *sequence,
-                        PacketSent {
-                            sent_at,
-                            info: PacketSentInfo::None,
-                        },
-                    );
-                }
-                Packet::UnreliableSlice { sequence, .. } => {
-                    self.sent_packets.insert(
-                        *sequence,
-                        PacketSent {
-                            sent_at,
-                            info: PacketSentInfo::None,
-                        },
-                    );
-                }
-                Packet::Ack { sequence, ack_ranges } => {
-                    let last_range = ack_ranges.last().unwrap();
-                    let largest_acked_packet = last_range.end - 1;
-                    self.sent_packets.insert(
-                        *sequence,
-                        PacketSent {
-                            sent_at,
-                            info: PacketSentInfo::Ack { largest_acked_packet },
-                        },
-                    );
-                }
-            }
+        if let Some(slice_size) = self.mtu_discovery.as_mut().and_then(|mtu| mtu.next_probe(self.current_time)) {
+            packets.push(Packet::MtuProbe {
+                sequence: self.packet_sequence,
+                slice_size,
+            });
+            self.packet_sequence += 1;
         }
 
-        let mut buffer = [0u8; 1400];
-        let mut serialized_packets = Vec::with_capacity(packets.len());
+        let mut num_packets: u64 = 0;
         let mut bytes_sent: u64 = 0;
-        for packet in packets {
-            let mut oct = OctetsMut::with_slice(&mut buffer);
+        let sent_at = self.current_time;
+        for packet in packets.drain(..) {
+            let mut oct = OctetsMut::with_slice(&mut self.packet_buffer);
             let len = match packet.to_bytes(&mut oct) {
                 Err(err) => {
                     self.disconnect_reason = Some(DisconnectReason::PacketSerialization(err));
-                    return vec![];
+                    break;
                 }
                 Ok(len) => len,
             };
 
+            let info = match &packet {
+                Packet::SmallReliable { channel_id, messages, .. } => {
+                    let mut message_ids = self.packet_pool.message_ids();
+                    message_ids.extend(messages.iter().map(|(id, _)| *id));
+                    PacketSentInfo::ReliableMessages {
+                        channel_id: *channel_id,
+                        message_ids,
+                    }
+                }
+                Packet::ReliableSlice { channel_id, slice, .. } => PacketSentInfo::ReliableSliceMessage {
+                    channel_id: *channel_id,
+                    message_id: slice.message_id,
+                    slice_index: slice.slice_index,
+                },
+                Packet::SmallUnreliable { .. } | Packet::UnreliableSlice { .. } => PacketSentInfo::None,
+                Packet::Ack { ack_ranges, .. } => {
+                    let last_range = ack_ranges.last().unwrap();
+                    PacketSentInfo::Ack {
+                        largest_acked_packet: last_range.end - 1,
+                    }
+                }
+                Packet::MtuProbe { slice_size, .. } => PacketSentInfo::MtuProbe { slice_size: *slice_size },
+            };
+            self.sent_packets.insert(packet.sequence(), PacketSent { sent_at, len, info });
+            self.packet_pool.recycle_packet(packet);
+
+            num_packets += 1;
             bytes_sent += len as u64;
-            serialized_packets.push(buffer[..len].to_vec());
+            send(&self.packet_buffer[..len]);
         }
 
-        self.stats.sent_packets(serialized_packets.len() as u64, bytes_sent);
+        // Packets left after a serialization error are dropped with the drain
+        self.packets = packets;
 
-        serialized_packets
+        self.stats.sent_packets(num_packets, bytes_sent);
     }
 
     fn add_pending_ack(&mut self, sequence: u64) {
@@ -720,4 +1098,71 @@ mod tests {
         connection.update(Duration::from_secs(4));
         assert_eq!(connection.sent_packets.len(), 0);
     }
+
+    #[test]
+    fn server_connections_dont_queue_events() {
+        let mut server_connection = RenetClient::new_from_server(ConnectionConfig::default());
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        client.set_connected();
+
+        server_connection
+            .send_tracked_message(DefaultChannel::ReliableOrdered, vec![1])
+            .unwrap();
+        client.send_tracked_message(DefaultChannel::ReliableOrdered, vec![2]).unwrap();
+        for packet in server_connection.get_packets_to_send() {
+            client.process_packet(&packet);
+        }
+        for packet in client.get_packets_to_send() {
+            server_connection.process_packet(&packet);
+        }
+
+        // Only the client keeps events
+        assert!(server_connection.events.is_empty());
+        assert_eq!(client.get_event(), Some(ClientEvent::Connected));
+        assert_eq!(client.get_event(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "slice size must be between")]
+    fn slice_size_below_minimum() {
+        RenetClient::new(ConnectionConfig {
+            slice_size: MIN_SLICE_SIZE - 1,
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn min_slice_size_stream() {
+        let channels_config = vec![ChannelConfig {
+            channel_id: 0,
+            max_memory_usage_bytes: 10_000,
+            send_type: SendType::ReliableStream {
+                resend_time: Duration::from_millis(100),
+            },
+        }];
+        let config = ConnectionConfig {
+            slice_size: MIN_SLICE_SIZE,
+            server_channels_config: channels_config.clone(),
+            client_channels_config: channels_config,
+            ..Default::default()
+        };
+        let mut sender = RenetClient::new(config.clone());
+        let mut receiver = RenetClient::new(config);
+
+        let data: Vec<u8> = (0..100).collect();
+        sender.send_stream(0, std::io::Cursor::new(data.clone()));
+        let mut received = vec![];
+        for _ in 0..10 {
+            for packet in sender.get_packets_to_send() {
+                receiver.process_packet(&packet);
+            }
+            while let Some(chunk) = receiver.receive_stream_chunk(0) {
+                if let StreamChunk::Data { data, .. } = chunk {
+                    assert!(!data.is_empty());
+                    received.extend_from_slice(&data);
+                }
+            }
+        }
+        assert_eq!(received, data);
+    }
 }
@@ -1,17 +1,57 @@
+use crate::channel::{StreamChunk, StreamProgress};
 use crate::error::{ClientNotFound, DisconnectReason};
 use crate::packet::Payload;
 use crate::remote_connection::{ConnectionConfig, NetworkInfo, RenetClient};
 use crate::ClientId;
 use std::collections::{HashMap, VecDeque};
+use std::io::Read;
+use std::net::SocketAddr;
 use std::time::Duration;
 
 use bytes::Bytes;
 
+/// Transport layer that created a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TransportKind {
+    /// The netcode transport, `NetcodeServerTransport` from the transport module.
+    Netcode,
+    /// The steam transport, from the renet_steam crate.
+    Steam,
+    /// Any other transport, or connections added without a transport.
+    #[default]
+    Other,
+}
+
+/// Metadata provided by the transport layer when a client connects.
+/// New fields can be added in the future, create it with [ConnectionInfo::new] and set the fields that apply.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub struct ConnectionInfo {
+    /// Transport layer that created the connection.
+    pub transport: TransportKind,
+    /// Remote address of the client, if the transport has one.
+    pub addr: Option<SocketAddr>,
+    /// User data provided by the transport, for the netcode transport this is the user data from the connect token.
+    pub user_data: Option<Bytes>,
+    /// Maximum packet size supported by the transport, if it has a limit.
+    pub max_packet_size: Option<usize>,
+}
+
+impl ConnectionInfo {
+    /// Creates the info for a connection from the given transport, with the other fields empty.
+    pub fn new(transport: TransportKind) -> Self {
+        Self {
+            transport,
+            ..Default::default()
+        }
+    }
+}
+
 /// Connection and disconnection events in the server.
 #[derive(Debug)]
 #[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Event))]
 pub enum ServerEvent {
-    ClientConnected { client_id: ClientId },
+    ClientConnected { client_id: ClientId, info: ConnectionInfo },
     ClientDisconnected { client_id: ClientId, reason: DisconnectReason },
 }
 
@@ -37,13 +77,25 @@ impl RenetServer {
     /// <strong>Note:</strong> This should only be called by the transport layer.
     /// </p>
     pub fn add_connection(&mut self, client_id: ClientId) {
+        self.add_connection_with_info(client_id, ConnectionInfo::default());
+    }
+
+    /// Adds a new connection to the server with the metadata from the transport layer,
+    /// the info is sent in the [ServerEvent::ClientConnected] event. If a connection already exits it does nothing.
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer.
+    /// </p>
+    pub fn add_connection_with_info(&mut self, client_id: ClientId, info: ConnectionInfo) {
         if self.connections.contains_key(&client_id) {
             return;
         }
 
-        let connection = RenetClient::new_from_server(self.connection_config.clone());
+        let mut connection = RenetClient::new_from_server(self.connection_config.clone());
+        if let Some(max_packet_size) = info.max_packet_size {
+            connection.set_max_packet_size(max_packet_size);
+        }
         self.connections.insert(client_id, connection);
-        self.events.push_back(ServerEvent::ClientConnected { client_id })
+        self.events.push_back(ServerEvent::ClientConnected { client_id, info })
     }
 
     /// Returns a server event if available
@@ -54,8 +106,8 @@ impl RenetServer {
     /// # let mut server = RenetServer::new(ConnectionConfig::default());
     /// while let Some(event) = server.get_event() {
     ///     match event {
-    ///         ServerEvent::ClientConnected { client_id } => {
-    ///             println!("Client {client_id} connected.")
+    ///         ServerEvent::ClientConnected { client_id, info } => {
+    ///             println!("Client {client_id} connected from {:?}.", info.addr)
     ///         }
     ///         ServerEvent::ClientDisconnected { client_id, reason } => {
     ///             println!("Client {client_id} disconnected: {reason}");
@@ -173,6 +225,15 @@ impl RenetServer {
         }
     }
 
+    /// Send a message with a key to all clients over a keyed channel.
+    pub fn broadcast_keyed_message<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, key: u64, message: B) {
+        let channel_id = channel_id.into();
+        let message = message.into();
+        for connection in self.connections.values_mut() {
+            connection.send_keyed_message(channel_id, key, message.clone());
+        }
+    }
+
     /// Returns the available memory in bytes of a channel for the given client.
     /// Returns 0 if the client is not found.
     pub fn channel_available_memory<I: Into<u8>>(&self, client_id: ClientId, channel_id: I) -> usize {
@@ -199,6 +260,45 @@ impl RenetServer {
         }
     }
 
+    /// Send a message with a key to a client over a keyed channel.
+    /// If an older message with the same key was not acknowledged yet, it is replaced by this one.
+    pub fn send_keyed_message<I: Into<u8>, B: Into<Bytes>>(&mut self, client_id: ClientId, channel_id: I, key: u64, message: B) {
+        match self.connections.get_mut(&client_id) {
+            Some(connection) => connection.send_keyed_message(channel_id, key, message),
+            None => log::error!("Tried to send a message to invalid client {:?}", client_id),
+        }
+    }
+
+    /// Queue a reader to be streamed to a client over a stream channel, returns the id of the stream.
+    /// Returns None if the client is not found.
+    /// See [RenetClient::send_stream] for more details.
+    pub fn send_stream<I: Into<u8>, R: Read + Send + Sync + 'static>(
+        &mut self,
+        client_id: ClientId,
+        channel_id: I,
+        reader: R,
+    ) -> Option<u64> {
+        match self.connections.get_mut(&client_id) {
+            Some(connection) => Some(connection.send_stream(channel_id, reader)),
+            None => {
+                log::error!("Tried to send a stream to invalid client {:?}", client_id);
+                None
+            }
+        }
+    }
+
+    /// Returns the progress of a stream sent to a client over a stream channel.
+    /// See [RenetClient::stream_progress] for more details.
+    pub fn stream_progress<I: Into<u8>>(&self, client_id: ClientId, channel_id: I, stream_id: u64) -> Option<StreamProgress> {
+        self.connections.get(&client_id)?.stream_progress(channel_id, stream_id)
+    }
+
+    /// Removes a completed or aborted stream sent to a client, returns its final progress.
+    /// See [RenetClient::remove_stream] for more details.
+    pub fn remove_stream<I: Into<u8>>(&mut self, client_id: ClientId, channel_id: I, stream_id: u64) -> Option<StreamProgress> {
+        self.connections.get_mut(&client_id)?.remove_stream(channel_id, stream_id)
+    }
+
     /// Receive a message from a client over a channel.
     pub fn receive_message<I: Into<u8>>(&mut self, client_id: ClientId, channel_id: I) -> Option<Bytes> {
         if let Some(connection) = self.connections.get_mut(&client_id) {
@@ -207,6 +307,22 @@ impl RenetServer {
         None
     }
 
+    /// Receive a message and its key from a client over a keyed channel.
+    pub fn receive_keyed_message<I: Into<u8>>(&mut self, client_id: ClientId, channel_id: I) -> Option<(u64, Bytes)> {
+        if let Some(connection) = self.connections.get_mut(&client_id) {
+            return connection.receive_keyed_message(channel_id);
+        }
+        None
+    }
+
+    /// Receive the next chunk of a stream from a client over a stream channel.
+    pub fn receive_stream_chunk<I: Into<u8>>(&mut self, client_id: ClientId, channel_id: I) -> Option<StreamChunk> {
+        if let Some(connection) = self.connections.get_mut(&client_id) {
+            return connection.receive_stream_chunk(channel_id);
+        }
+        None
+    }
+
     /// Return ids for all connected clients (iterator)
     pub fn clients_id_iter(&self) -> impl Iterator<Item = ClientId> + '_ {
         self.connections.iter().filter(|(_, c)| !c.is_disconnected()).map(|(id, _)| *id)
@@ -259,6 +375,21 @@ impl RenetServer {
         }
     }
 
+    /// Generates the packets to be sent to the client, calling `send` with each one of them.
+    /// See [RenetClient::for_each_packet_to_send] for more details.
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer.
+    /// </p>
+    pub fn for_each_packet_to_send<F: FnMut(&[u8])>(&mut self, client_id: ClientId, send: F) -> Result<(), ClientNotFound> {
+        match self.connections.get_mut(&client_id) {
+            Some(connection) => {
+                connection.for_each_packet_to_send(send);
+                Ok(())
+            }
+            None => Err(ClientNotFound),
+        }
+    }
+
     /// Process a packet received from the client.
     /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
     /// <strong>Note:</strong> This should only be called by the transport layer.
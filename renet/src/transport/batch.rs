@@ -0,0 +1,238 @@
+//! Batched UDP I/O with `recvmmsg` and `sendmmsg`, receiving or sending up to [BATCH_SIZE] packets per syscall.
+//!
+//! UDP GSO is not used: it needs equally sized segments to the same destination, while the server
+//! sends packets of different sizes to many clients.
+use std::{
+    io, mem,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, UdpSocket},
+    ops::Range,
+    os::fd::AsRawFd,
+    ptr,
+};
+
+use renetcode::NETCODE_MAX_PACKET_BYTES;
+
+use super::TransportSocket;
+
+/// Maximum number of packets received or sent in one syscall.
+pub(crate) const BATCH_SIZE: usize = 32;
+
+/// Buffers reused between calls to receive and send packets in batches.
+pub(crate) struct BatchBuffers {
+    pub recv_buffers: [[u8; NETCODE_MAX_PACKET_BYTES]; BATCH_SIZE],
+    /// Length and source address of each received packet.
+    pub recv_packets: [(usize, SocketAddr); BATCH_SIZE],
+    send_data: Vec<u8>,
+    // Socket index, destination and range in send_data of each queued packet
+    send_packets: Vec<(usize, SocketAddr, Range<usize>)>,
+}
+
+impl std::fmt::Debug for BatchBuffers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BatchBuffers")
+            .field("queued_packets", &self.send_packets.len())
+            .finish()
+    }
+}
+
+impl BatchBuffers {
+    pub fn new() -> Box<Self> {
+        Box::new(Self {
+            recv_buffers: [[0; NETCODE_MAX_PACKET_BYTES]; BATCH_SIZE],
+            recv_packets: [(0, SocketAddr::from(([0, 0, 0, 0], 0))); BATCH_SIZE],
+            send_data: Vec::new(),
+            send_packets: Vec::new(),
+        })
+    }
+
+    /// Receives up to [BATCH_SIZE] packets from the socket in one syscall, returns how many were read from the socket
+    /// and how many of them are usable. The usable packets are in `recv_buffers` and their length and address in `recv_packets`.
+    pub fn recv(&mut self, socket: &UdpSocket) -> io::Result<(usize, usize)> {
+        // SAFETY: all-zero is a valid value for these plain C structs.
+        let mut addrs: [libc::sockaddr_storage; BATCH_SIZE] = unsafe { mem::zeroed() };
+        let mut iovecs: [libc::iovec; BATCH_SIZE] = unsafe { mem::zeroed() };
+        let mut msgs: [libc::mmsghdr; BATCH_SIZE] = unsafe { mem::zeroed() };
+        for (i, buffer) in self.recv_buffers.iter_mut().enumerate() {
+            iovecs[i] = libc::iovec {
+                iov_base: buffer.as_mut_ptr() as *mut libc::c_void,
+                iov_len: buffer.len(),
+            };
+            msgs[i].msg_hdr.msg_name = &mut addrs[i] as *mut libc::sockaddr_storage as *mut libc::c_void;
+            msgs[i].msg_hdr.msg_namelen = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+            msgs[i].msg_hdr.msg_iov = &mut iovecs[i];
+            msgs[i].msg_hdr.msg_iovlen = 1;
+        }
+
+        // SAFETY: every message points to a buffer and an address that outlive the call.
+        let received = unsafe { libc::recvmmsg(socket.as_raw_fd(), msgs.as_mut_ptr(), BATCH_SIZE as _, 0, ptr::null_mut()) };
+        if received < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut count = 0;
+        for i in 0..received as usize {
+            // UDP sockets only receive from ip addresses, ignore anything else
+            if let Some(addr) = from_sockaddr(&addrs[i]) {
+                if count != i {
+                    self.recv_buffers.copy_within(i..i + 1, count);
+                }
+                self.recv_packets[count] = (msgs[i].msg_len as usize, addr);
+                count += 1;
+            }
+        }
+
+        Ok((received as usize, count))
+    }
+
+    /// Queues a packet to be sent from the socket at the index by [BatchBuffers::flush].
+    pub fn queue(&mut self, socket_index: usize, addr: SocketAddr, packet: &[u8]) {
+        let start = self.send_data.len();
+        self.send_data.extend_from_slice(packet);
+        self.send_packets.push((socket_index, addr, start..self.send_data.len()));
+    }
+
+    /// Sends the queued packets, consecutive packets from the same socket are sent in one syscall.
+    /// Packets that fail to send are logged and dropped. Every socket must have a [TransportSocket::as_udp_socket].
+    pub fn flush<S: TransportSocket>(&mut self, sockets: &[S]) {
+        let mut start = 0;
+        while start < self.send_packets.len() {
+            let socket_index = self.send_packets[start].0;
+            let end = self.send_packets[start..]
+                .iter()
+                .take(BATCH_SIZE)
+                .position(|(index, ..)| *index != socket_index)
+                .map_or_else(|| (start + BATCH_SIZE).min(self.send_packets.len()), |len| start + len);
+
+            let socket = sockets[socket_index]
+                .as_udp_socket()
+                .expect("batched I/O is only enabled for UdpSocket");
+            match self.send(socket, start..end) {
+                Ok(sent) => start += sent,
+                Err(e) => {
+                    let addr = self.send_packets[start].1;
+                    log::error!("Failed to send packet to {addr}: {e}");
+                    start += 1;
+                }
+            }
+        }
+
+        self.send_data.clear();
+        self.send_packets.clear();
+    }
+
+    fn send(&mut self, socket: &UdpSocket, packets: Range<usize>) -> io::Result<usize> {
+        // SAFETY: all-zero is a valid value for these plain C structs.
+        let mut addrs: [libc::sockaddr_storage; BATCH_SIZE] = unsafe { mem::zeroed() };
+        let mut iovecs: [libc::iovec; BATCH_SIZE] = unsafe { mem::zeroed() };
+        let mut msgs: [libc::mmsghdr; BATCH_SIZE] = unsafe { mem::zeroed() };
+        let len = packets.len();
+        for (i, (_, addr, range)) in self.send_packets[packets].iter().enumerate() {
+            let data = &mut self.send_data[range.clone()];
+            iovecs[i] = libc::iovec {
+                iov_base: data.as_mut_ptr() as *mut libc::c_void,
+                iov_len: data.len(),
+            };
+            msgs[i].msg_hdr.msg_name = &mut addrs[i] as *mut libc::sockaddr_storage as *mut libc::c_void;
+            msgs[i].msg_hdr.msg_namelen = to_sockaddr(addr, &mut addrs[i]);
+            msgs[i].msg_hdr.msg_iov = &mut iovecs[i];
+            msgs[i].msg_hdr.msg_iovlen = 1;
+        }
+
+        // SAFETY: every message points to a buffer and an address that outlive the call.
+        let sent = unsafe { libc::sendmmsg(socket.as_raw_fd(), msgs.as_mut_ptr(), len as _, 0) };
+        if sent < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(sent as usize)
+    }
+}
+
+fn from_sockaddr(storage: &libc::sockaddr_storage) -> Option<SocketAddr> {
+    match storage.ss_family as libc::c_int {
+        libc::AF_INET => {
+            // SAFETY: the family says the storage holds a sockaddr_in, that fits in sockaddr_storage.
+            let addr = unsafe { &*(storage as *const libc::sockaddr_storage as *const libc::sockaddr_in) };
+            let ip = Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr));
+            Some(SocketAddrV4::new(ip, u16::from_be(addr.sin_port)).into())
+        }
+        libc::AF_INET6 => {
+            // SAFETY: the family says the storage holds a sockaddr_in6, that fits in sockaddr_storage.
+            let addr = unsafe { &*(storage as *const libc::sockaddr_storage as *const libc::sockaddr_in6) };
+            let ip = Ipv6Addr::from(addr.sin6_addr.s6_addr);
+            Some(SocketAddrV6::new(ip, u16::from_be(addr.sin6_port), addr.sin6_flowinfo, addr.sin6_scope_id).into())
+        }
+        _ => None,
+    }
+}
+
+fn to_sockaddr(addr: &SocketAddr, storage: &mut libc::sockaddr_storage) -> libc::socklen_t {
+    match addr {
+        SocketAddr::V4(addr) => {
+            // SAFETY: sockaddr_in fits in sockaddr_storage, that is aligned for any socket address.
+            let sockaddr = unsafe { &mut *(storage as *mut libc::sockaddr_storage as *mut libc::sockaddr_in) };
+            sockaddr.sin_family = libc::AF_INET as libc::sa_family_t;
+            sockaddr.sin_port = addr.port().to_be();
+            sockaddr.sin_addr = libc::in_addr {
+                s_addr: u32::from(*addr.ip()).to_be(),
+            };
+            mem::size_of::<libc::sockaddr_in>() as libc::socklen_t
+        }
+        SocketAddr::V6(addr) => {
+            // SAFETY: sockaddr_in6 fits in sockaddr_storage, that is aligned for any socket address.
+            let sockaddr = unsafe { &mut *(storage as *mut libc::sockaddr_storage as *mut libc::sockaddr_in6) };
+            sockaddr.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            sockaddr.sin6_port = addr.port().to_be();
+            sockaddr.sin6_flowinfo = addr.flowinfo();
+            sockaddr.sin6_addr = libc::in6_addr {
+                s6_addr: addr.ip().octets(),
+            };
+            sockaddr.sin6_scope_id = addr.scope_id();
+            mem::size_of::<libc::sockaddr_in6>() as libc::socklen_t
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn batch_send_recv() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver.set_nonblocking(true).unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut batch = BatchBuffers::new();
+        assert_eq!(batch.recv(&receiver).unwrap_err().kind(), io::ErrorKind::WouldBlock);
+
+        // More packets than a single batch
+        let receiver_addr = receiver.local_addr().unwrap();
+        for i in 0..BATCH_SIZE + 8 {
+            batch.queue(0, receiver_addr, &vec![i as u8; i + 1]);
+        }
+        // Shared sockets forward the UdpSocket used for batching
+        batch.flush(&[Arc::new(sender.try_clone().unwrap())]);
+
+        let mut received = vec![];
+        while received.len() < BATCH_SIZE + 8 {
+            let count = match batch.recv(&receiver) {
+                Ok((received, count)) => {
+                    assert_eq!(received, count);
+                    count
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(e) => panic!("{e}"),
+            };
+            for i in 0..count {
+                let (len, addr) = batch.recv_packets[i];
+                assert_eq!(addr, sender.local_addr().unwrap());
+                received.push(batch.recv_buffers[i][..len].to_vec());
+            }
+        }
+
+        for (i, packet) in received.iter().enumerate() {
+            assert_eq!(*packet, vec![i as u8; i + 1]);
+        }
+    }
+}
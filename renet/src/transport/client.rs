@@ -1,35 +1,193 @@
 use std::{
-    io,
+    collections::VecDeque,
+    fmt, io,
     net::{SocketAddr, UdpSocket},
     time::Duration,
 };
 
-use renetcode::{ClientAuthentication, DisconnectReason, NetcodeClient, NetcodeError, NETCODE_MAX_PACKET_BYTES};
+use renetcode::{
+    ClientAuthentication, ClientConfig, DisconnectReason, NetcodeClient, NetcodeError, NETCODE_MAX_PACKET_BYTES, NETCODE_MAX_PAYLOAD_BYTES,
+};
+
+use crate::{remote_connection::RenetClient, ClientTransport};
+
+use super::{NetcodeTransportError, TransportSocket};
+
+/// Policy to reconnect when the connection with the server is lost or can't be established,
+/// see [NetcodeClientTransport::set_reconnect_policy].
+///
+/// Each attempt starts from the server address after the last one tried, so the client fails over
+/// to the other addresses in the connect token.
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    /// Maximum number of attempts, the count is reset after the client reconnects.
+    pub max_attempts: u32,
+    /// Delay before the first attempt.
+    pub initial_backoff: Duration,
+    /// Maximum delay between attempts.
+    pub max_backoff: Duration,
+    /// Multiplier applied to the delay after each failed attempt.
+    pub backoff_multiplier: f64,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            backoff_multiplier: 2.0,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Returns the delay before the attempt, starting from 0.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let backoff = self.initial_backoff.as_secs_f64() * self.backoff_multiplier.max(1.0).powi(attempt.min(i32::MAX as u32) as i32);
+        Duration::from_secs_f64(backoff.min(self.max_backoff.as_secs_f64()))
+    }
+}
+
+/// Events emitted by the [NetcodeClientTransport] when reconnecting, see [NetcodeClientTransport::get_event].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Event))]
+pub enum ReconnectEvent {
+    /// The connection was lost or could not be established, the first attempt starts after the delay.
+    ConnectionLost { reason: DisconnectReason, retry_in: Duration },
+    /// Started an attempt to connect to the server address.
+    Attempt { attempt: u32, server_addr: SocketAddr },
+    /// The attempt failed, the next one starts after the delay.
+    AttemptFailed {
+        attempt: u32,
+        reason: DisconnectReason,
+        retry_in: Duration,
+    },
+    /// Connected to the server address, the [RenetClient] was reset for the new connection.
+    Reconnected { attempt: u32, server_addr: SocketAddr },
+    /// All the attempts failed, the transport stays disconnected.
+    GaveUp { reason: DisconnectReason },
+}
 
-use crate::remote_connection::RenetClient;
+/// Callback that can return a new authentication before a reconnect attempt, receives the attempt number.
+type TokenProviderFn = Box<dyn FnMut(u32) -> Option<ClientAuthentication> + Send + Sync + 'static>;
 
-use super::NetcodeTransportError;
+struct TokenProvider(TokenProviderFn);
+
+impl fmt::Debug for TokenProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TokenProvider")
+    }
+}
 
+#[derive(Debug)]
+struct Reconnect {
+    policy: ReconnectPolicy,
+    attempt: u32,
+    // Time waited and delay until the next attempt, while waiting to reconnect
+    backoff: Option<(Duration, Duration)>,
+}
+
+/// Client transport using netcode over a [TransportSocket], by default an [UdpSocket].
 #[derive(Debug)]
 #[cfg_attr(feature = "bevy", derive(bevy_ecs::system::Resource))]
-pub struct NetcodeClientTransport {
-    socket: UdpSocket,
+pub struct NetcodeClientTransport<S: TransportSocket = UdpSocket> {
+    socket: S,
     netcode_client: NetcodeClient,
     buffer: [u8; NETCODE_MAX_PACKET_BYTES],
+    // Kept to create a new netcode client when reconnecting
+    authentication: ClientAuthentication,
+    config: ClientConfig,
+    reconnect: Option<Reconnect>,
+    token_provider: Option<TokenProvider>,
+    events: VecDeque<ReconnectEvent>,
 }
 
-impl NetcodeClientTransport {
-    pub fn new(current_time: Duration, authentication: ClientAuthentication, socket: UdpSocket) -> Result<Self, NetcodeError> {
-        socket.set_nonblocking(true)?;
-        let netcode_client = NetcodeClient::new(current_time, authentication)?;
+impl<S: TransportSocket> NetcodeClientTransport<S> {
+    pub fn new(current_time: Duration, authentication: ClientAuthentication, socket: S) -> Result<Self, NetcodeError> {
+        Self::with_config(current_time, authentication, ClientConfig::default(), socket)
+    }
+
+    pub fn with_config(
+        current_time: Duration,
+        authentication: ClientAuthentication,
+        config: ClientConfig,
+        socket: S,
+    ) -> Result<Self, NetcodeError> {
+        socket.enable_nonblocking()?;
+        let netcode_client = NetcodeClient::with_config(current_time, authentication.clone(), config.clone())?;
 
         Ok(Self {
             buffer: [0u8; NETCODE_MAX_PACKET_BYTES],
             socket,
             netcode_client,
+            authentication,
+            config,
+            reconnect: None,
+            token_provider: None,
+            events: VecDeque::new(),
         })
     }
 
+    /// Reconnects with the policy when the connection is lost or can't be established, instead of staying disconnected.
+    /// Disconnections requested by the client are never retried.
+    ///
+    /// With [ClientAuthentication::Secure], each attempt needs a new connect token from the
+    /// [token provider][NetcodeClientTransport::set_token_provider], without one the transport gives up.
+    pub fn set_reconnect_policy(&mut self, policy: ReconnectPolicy) {
+        self.reconnect = Some(Reconnect {
+            policy,
+            attempt: 0,
+            backoff: None,
+        });
+    }
+
+    /// Removes the reconnect policy, the transport stays disconnected after the connection is lost.
+    pub fn clear_reconnect_policy(&mut self) {
+        self.reconnect = None;
+    }
+
+    /// Sets a callback called before each reconnect attempt with the attempt number, it can return a new
+    /// authentication, e.g. a fresh connect token requested from the backend.
+    /// When it returns `None`, an unsecure authentication is used again, a secure one gives up reconnecting
+    /// since the keys of a connect token can't be used for more than one connection.
+    pub fn set_token_provider<F>(&mut self, provider: F)
+    where
+        F: FnMut(u32) -> Option<ClientAuthentication> + Send + Sync + 'static,
+    {
+        self.token_provider = Some(TokenProvider(Box::new(provider)));
+    }
+
+    /// Removes the token provider callback, only unsecure authentications can reconnect without it.
+    pub fn clear_token_provider(&mut self) {
+        self.token_provider = None;
+    }
+
+    /// Returns if the transport is waiting to start the next reconnect attempt.
+    pub fn is_reconnecting(&self) -> bool {
+        self.reconnect.as_ref().is_some_and(|reconnect| reconnect.backoff.is_some())
+    }
+
+    /// Returns a reconnect event if available.
+    ///
+    /// # Usage
+    /// ```
+    /// # use renet::transport::{NetcodeClientTransport, ReconnectEvent};
+    /// # fn handle(transport: &mut NetcodeClientTransport) {
+    /// while let Some(event) = transport.get_event() {
+    ///     match event {
+    ///         ReconnectEvent::ConnectionLost { reason, retry_in } => println!("Connection lost: {reason}, retrying in {retry_in:?}"),
+    ///         ReconnectEvent::Reconnected { server_addr, .. } => println!("Reconnected to {server_addr}"),
+    ///         ReconnectEvent::GaveUp { reason } => println!("Failed to reconnect: {reason}"),
+    ///         _ => {}
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn get_event(&mut self) -> Option<ReconnectEvent> {
+        self.events.pop_front()
+    }
+
     pub fn addr(&self) -> io::Result<SocketAddr> {
         self.socket.local_addr()
     }
@@ -39,7 +197,7 @@ impl NetcodeClientTransport {
     }
 
     pub fn is_connecting(&self) -> bool {
-        self.netcode_client.is_connecting()
+        self.netcode_client.is_connecting() || self.is_reconnecting()
     }
 
     pub fn is_connected(&self) -> bool {
@@ -47,7 +205,12 @@ impl NetcodeClientTransport {
     }
 
     pub fn is_disconnected(&self) -> bool {
-        self.netcode_client.is_disconnected()
+        self.netcode_client.is_disconnected() && !self.is_reconnecting()
+    }
+
+    /// Returns the position in the server waiting queue, if the server is full and placed the client in the queue.
+    pub fn queue_position(&self) -> Option<u32> {
+        self.netcode_client.queue_position()
     }
 
     /// Returns the duration since the client last received a packet.
@@ -57,17 +220,23 @@ impl NetcodeClientTransport {
     }
 
     /// Disconnect the client from the transport layer.
-    /// This sends the disconnect packet instantly, use this when closing/exiting games,
+    /// This sends the disconnect packets instantly, use this when closing/exiting games,
     /// should use [RenetClient::disconnect][crate::RenetClient::disconnect] otherwise.
+    /// The number of disconnect packets sent is configured in [ClientConfig::disconnect_packets].
     pub fn disconnect(&mut self) {
+        self.reconnect = None;
         if self.netcode_client.is_disconnected() {
             return;
         }
 
+        let disconnect_packets = self.netcode_client.disconnect_packets();
         match self.netcode_client.disconnect() {
             Ok((addr, packet)) => {
-                if let Err(e) = self.socket.send_to(packet, addr) {
-                    log::error!("Failed to send disconnect packet: {e}");
+                for _ in 0..disconnect_packets {
+                    if let Err(e) = self.socket.send_to(packet, addr) {
+                        log::error!("Failed to send disconnect packet: {e}");
+                        break;
+                    }
                 }
             }
             Err(e) => log::error!("Failed to generate disconnect packet: {e}"),
@@ -76,6 +245,10 @@ impl NetcodeClientTransport {
 
     /// If the client is disconnected, returns the reason.
     pub fn disconnect_reason(&self) -> Option<DisconnectReason> {
+        if self.is_reconnecting() {
+            return None;
+        }
+
         self.netcode_client.disconnect_reason()
     }
 
@@ -83,20 +256,43 @@ impl NetcodeClientTransport {
     /// Should be called every tick
     pub fn send_packets(&mut self, connection: &mut RenetClient) -> Result<(), NetcodeTransportError> {
         if let Some(reason) = self.netcode_client.disconnect_reason() {
+            if self.is_reconnecting() {
+                return Ok(());
+            }
+
             return Err(NetcodeError::Disconnected(reason).into());
         }
 
-        let packets = connection.get_packets_to_send();
-        for packet in packets {
-            let (addr, payload) = self.netcode_client.generate_payload_packet(&packet)?;
-            self.socket.send_to(payload, addr)?;
-        }
+        // Path MTU discovery must not probe above the netcode payload limit
+        connection.set_max_packet_size(NETCODE_MAX_PAYLOAD_BYTES);
 
-        Ok(())
+        let mut result = Ok(());
+        connection.for_each_packet_to_send(|packet| {
+            if result.is_err() {
+                return;
+            }
+
+            result = match self.netcode_client.generate_payload_packet(packet) {
+                Ok((addr, payload)) => self.socket.send_to(payload, addr).map(|_| ()).map_err(NetcodeTransportError::IO),
+                Err(e) => Err(e.into()),
+            };
+        });
+
+        result
     }
 
     /// Advances the transport by the duration, and receive packets from the network.
-    pub fn update(&mut self, duration: Duration, client: &mut RenetClient) -> Result<(), NetcodeTransportError> {
+    pub fn update(&mut self, mut duration: Duration, client: &mut RenetClient) -> Result<(), NetcodeTransportError> {
+        if let Some(reason) = self.netcode_client.disconnect_reason() {
+            if self.update_reconnect(duration, reason, client)? {
+                if self.netcode_client.is_disconnected() {
+                    return Ok(());
+                }
+                // The new netcode client already starts at the current time
+                duration = Duration::ZERO;
+            }
+        }
+
         if let Some(reason) = self.netcode_client.disconnect_reason() {
             // Mark the client as disconnected if an error occured in the transport layer
             if !client.is_disconnected() {
@@ -107,8 +303,11 @@ impl NetcodeClientTransport {
         }
 
         if let Some(error) = client.disconnect_reason() {
+            let disconnect_packets = self.netcode_client.disconnect_packets();
             let (addr, disconnect_packet) = self.netcode_client.disconnect()?;
-            self.socket.send_to(disconnect_packet, addr)?;
+            for _ in 0..disconnect_packets {
+                self.socket.send_to(disconnect_packet, addr)?;
+            }
             return Err(error.into());
         }
 
@@ -132,10 +331,122 @@ impl NetcodeClientTransport {
             }
         }
 
+        if self.netcode_client.is_connected() {
+            client.set_connected();
+            if let Some(reconnect) = self.reconnect.as_mut().filter(|reconnect| reconnect.attempt > 0) {
+                self.events.push_back(ReconnectEvent::Reconnected {
+                    attempt: reconnect.attempt,
+                    server_addr: self.netcode_client.server_addr(),
+                });
+                reconnect.attempt = 0;
+            }
+        }
+
         if let Some((packet, addr)) = self.netcode_client.update(duration) {
             self.socket.send_to(packet, addr)?;
         }
 
         Ok(())
     }
+
+    /// Schedules or starts a reconnect attempt after the netcode client disconnected.
+    /// Returns false if the client should stay disconnected.
+    fn update_reconnect(&mut self, duration: Duration, reason: DisconnectReason, client: &mut RenetClient) -> Result<bool, NetcodeError> {
+        let Some(reconnect) = self.reconnect.as_mut() else {
+            return Ok(false);
+        };
+        if reason == DisconnectReason::DisconnectedByClient || client.is_disconnected() {
+            return Ok(false);
+        }
+
+        // Reconnecting with the same secure connect token would reuse its keys with the packet sequence starting from 0 again
+        let needs_new_token = matches!(self.authentication, ClientAuthentication::Secure { .. });
+        let Some((waited, delay)) = reconnect.backoff else {
+            if needs_new_token && self.token_provider.is_none() {
+                log::error!("Cannot reconnect with a secure connect token without a token provider");
+                self.events.push_back(ReconnectEvent::GaveUp { reason });
+                self.reconnect = None;
+                return Ok(false);
+            }
+
+            if reconnect.attempt >= reconnect.policy.max_attempts {
+                self.events.push_back(ReconnectEvent::GaveUp { reason });
+                self.reconnect = None;
+                return Ok(false);
+            }
+
+            let retry_in = reconnect.policy.backoff(reconnect.attempt);
+            let event = match reconnect.attempt {
+                0 => ReconnectEvent::ConnectionLost { reason, retry_in },
+                attempt => ReconnectEvent::AttemptFailed { attempt, reason, retry_in },
+            };
+            self.events.push_back(event);
+            reconnect.backoff = Some((Duration::ZERO, retry_in));
+            // Messages from the lost connection can't be delivered in the new one
+            client.reset();
+            return Ok(true);
+        };
+
+        let waited = waited + duration;
+        if waited < delay {
+            reconnect.backoff = Some((waited, delay));
+            return Ok(true);
+        }
+
+        reconnect.backoff = None;
+        reconnect.attempt += 1;
+        let attempt = reconnect.attempt;
+        match self.token_provider.as_mut().and_then(|provider| (provider.0)(attempt)) {
+            Some(authentication) => self.authentication = authentication,
+            None if needs_new_token => {
+                log::error!("Cannot reconnect, the token provider returned no connect token for attempt {attempt}");
+                self.events.push_back(ReconnectEvent::GaveUp { reason });
+                self.reconnect = None;
+                return Ok(false);
+            }
+            None => {}
+        }
+
+        let current_time = self.netcode_client.current_time() + waited;
+        let next_server_addr_index = self.netcode_client.server_addr_index() + 1;
+        let mut netcode_client = NetcodeClient::with_config(current_time, self.authentication.clone(), self.config.clone())?;
+        // Wraps around to the first address when there are no more addresses
+        let _ = netcode_client.set_server_addr_index(next_server_addr_index);
+
+        self.events.push_back(ReconnectEvent::Attempt {
+            attempt,
+            server_addr: netcode_client.server_addr(),
+        });
+        self.netcode_client = netcode_client;
+
+        Ok(true)
+    }
+}
+
+impl<S: TransportSocket> ClientTransport for NetcodeClientTransport<S> {
+    type Error = NetcodeTransportError;
+
+    fn update(&mut self, duration: Duration, client: &mut RenetClient) -> Result<(), Self::Error> {
+        NetcodeClientTransport::update(self, duration, client)
+    }
+
+    fn send_packets(&mut self, client: &mut RenetClient) -> Result<(), Self::Error> {
+        NetcodeClientTransport::send_packets(self, client)
+    }
+
+    fn is_connecting(&self) -> bool {
+        NetcodeClientTransport::is_connecting(self)
+    }
+
+    fn is_connected(&self) -> bool {
+        NetcodeClientTransport::is_connected(self)
+    }
+
+    fn is_disconnected(&self) -> bool {
+        NetcodeClientTransport::is_disconnected(self)
+    }
+
+    fn disconnect(&mut self) {
+        NetcodeClientTransport::disconnect(self)
+    }
 }
@@ -1,14 +1,25 @@
 use std::{error::Error, fmt};
 
+#[cfg(all(feature = "batch_io", target_os = "linux"))]
+mod batch;
 mod client;
 mod server;
+mod socket;
+#[cfg(feature = "tokio")]
+mod tokio_transport;
 
 pub use client::*;
 pub use server::*;
+pub use socket::TransportSocket;
+#[cfg(feature = "tokio")]
+pub use tokio_transport::{TokioClient, TokioClientEvent, TokioServer, TokioServerEvent};
 
+#[cfg(feature = "serde")]
+pub use renetcode::{decode_user_data, encode_user_data, UserDataError};
 pub use renetcode::{
-    generate_random_bytes, ClientAuthentication, ConnectToken, DisconnectReason as NetcodeDisconnectReason, NetcodeError,
-    ServerAuthentication, ServerConfig, TokenGenerationError, NETCODE_KEY_BYTES, NETCODE_USER_DATA_BYTES,
+    generate_random_bytes, CipherSuite, ClientAuthentication, ClientConfig, ConnectToken, ConnectionApproval, ConnectionRequest,
+    DisconnectReason as NetcodeDisconnectReason, IpRange, NetcodeError, PacketFilterStats, RateLimitConfig, ServerAuthentication,
+    ServerConfig, TokenGenerationError, NETCODE_KEY_BYTES, NETCODE_MAX_PENDING_CLIENTS, NETCODE_SEND_RATE, NETCODE_USER_DATA_BYTES,
 };
 
 #[derive(Debug)]
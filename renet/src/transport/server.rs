@@ -1,42 +1,129 @@
 use std::{
+    collections::HashMap,
     io,
     net::{SocketAddr, UdpSocket},
     time::Duration,
 };
 
-use renetcode::{NetcodeServer, ServerConfig, ServerResult, NETCODE_MAX_PACKET_BYTES, NETCODE_USER_DATA_BYTES};
+use bytes::Bytes;
+use renetcode::{
+    ConnectionApproval, ConnectionRequest, IpRange, NetcodeError, NetcodeServer, PacketFilterStats, RateLimitConfig, ServerConfig,
+    ServerResult, NETCODE_KEY_BYTES, NETCODE_MAX_PACKET_BYTES, NETCODE_MAX_PAYLOAD_BYTES, NETCODE_USER_DATA_BYTES,
+};
 
 use crate::ClientId;
-use crate::RenetServer;
+use crate::{ConnectionInfo, RenetClient, RenetServer, ServerTransport, TransportKind};
 
-use super::NetcodeTransportError;
+#[cfg(all(feature = "batch_io", target_os = "linux"))]
+use super::batch::{BatchBuffers, BATCH_SIZE};
+use super::{NetcodeTransportError, TransportSocket};
 
+/// Server transport using netcode over [TransportSocket]s, by default [UdpSocket]s.
 #[derive(Debug)]
 #[cfg_attr(feature = "bevy", derive(bevy_ecs::system::Resource))]
-pub struct NetcodeServerTransport {
-    socket: UdpSocket,
+pub struct NetcodeServerTransport<S: TransportSocket = UdpSocket> {
+    sockets: ServerSockets<S>,
     netcode_server: NetcodeServer,
     buffer: [u8; NETCODE_MAX_PACKET_BYTES],
+    #[cfg(all(feature = "batch_io", target_os = "linux"))]
+    batch: Option<Box<BatchBuffers>>,
+}
+
+/// The sockets of the server and the socket each connected client uses.
+#[derive(Debug)]
+struct ServerSockets<S> {
+    sockets: Vec<S>,
+    local_addresses: Vec<SocketAddr>,
+    client_sockets: HashMap<SocketAddr, usize>,
+}
+
+impl<S: TransportSocket> ServerSockets<S> {
+    /// Returns the socket used to send packets to the address, packets in reply to a received packet
+    /// are sent from the socket it arrived on.
+    fn socket_index(&self, addr: SocketAddr, received_on: Option<usize>) -> usize {
+        received_on
+            .or_else(|| self.client_sockets.get(&addr).copied())
+            .or_else(|| self.local_addresses.iter().position(|local| local.is_ipv4() == addr.is_ipv4()))
+            .unwrap_or(0)
+    }
+
+    fn send_to(&self, packet: &[u8], addr: SocketAddr, received_on: Option<usize>) -> io::Result<usize> {
+        self.sockets[self.socket_index(addr, received_on)].send_to(packet, addr)
+    }
+
+    /// Batched I/O needs the file descriptor, so it is only supported when every socket has a [UdpSocket].
+    #[cfg(all(feature = "batch_io", target_os = "linux"))]
+    fn supports_batch_io(&self) -> bool {
+        self.sockets.iter().all(|socket| socket.as_udp_socket().is_some())
+    }
+
+    #[cfg(all(feature = "batch_io", target_os = "linux"))]
+    fn udp_socket(&self, socket_index: usize) -> &UdpSocket {
+        self.sockets[socket_index]
+            .as_udp_socket()
+            .expect("batched I/O is only enabled for UdpSocket")
+    }
 }
 
-impl NetcodeServerTransport {
-    pub fn new(server_config: ServerConfig, socket: UdpSocket) -> Result<Self, std::io::Error> {
-        socket.set_nonblocking(true)?;
+impl<S: TransportSocket> NetcodeServerTransport<S> {
+    pub fn new(server_config: ServerConfig, socket: S) -> Result<Self, std::io::Error> {
+        Self::new_with_sockets(server_config, vec![socket])
+    }
+
+    /// Creates a transport that receives packets from all the sockets, e.g. an IPv4 and an IPv6 socket
+    /// or one socket per network interface. The packets to a client are sent from the socket it connected with.
+    /// The [ServerConfig::public_addresses] should list the public address of each socket.
+    pub fn new_with_sockets(server_config: ServerConfig, sockets: Vec<S>) -> Result<Self, std::io::Error> {
+        if sockets.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "server transport needs at least one socket",
+            ));
+        }
+
+        let mut local_addresses = Vec::with_capacity(sockets.len());
+        for socket in sockets.iter() {
+            socket.enable_nonblocking()?;
+            local_addresses.push(socket.local_addr()?);
+        }
 
         let netcode_server = NetcodeServer::new(server_config);
+        let sockets = ServerSockets {
+            sockets,
+            local_addresses,
+            client_sockets: HashMap::new(),
+        };
 
         Ok(Self {
-            socket,
+            #[cfg(all(feature = "batch_io", target_os = "linux"))]
+            batch: sockets.supports_batch_io().then(BatchBuffers::new),
+            sockets,
             netcode_server,
             buffer: [0; NETCODE_MAX_PACKET_BYTES],
         })
     }
 
+    /// Enables or disables batched I/O, receiving and sending many packets per syscall with `recvmmsg` and `sendmmsg`.
+    /// Enabled by default with the `batch_io` feature, only supported when the sockets have a [TransportSocket::as_udp_socket].
+    #[cfg(all(feature = "batch_io", target_os = "linux"))]
+    pub fn set_batch_io(&mut self, enabled: bool) {
+        match (enabled && self.sockets.supports_batch_io(), self.batch.is_some()) {
+            (true, false) => self.batch = Some(BatchBuffers::new()),
+            (false, true) => self.batch = None,
+            _ => {}
+        }
+    }
+
     /// Returns the server public address
     pub fn addresses(&self) -> Vec<SocketAddr> {
         self.netcode_server.addresses()
     }
 
+    /// Returns the local address of each socket, in the order they were given.
+    pub fn local_addresses(&self) -> Vec<SocketAddr> {
+        self.sockets.local_addresses.clone()
+    }
+
     /// Returns the maximum number of clients that can be connected.
     pub fn max_clients(&self) -> usize {
         self.netcode_server.max_clients()
@@ -47,6 +134,17 @@ impl NetcodeServerTransport {
         self.netcode_server.connected_clients()
     }
 
+    /// Sets the maximum number of clients that can wait in the queue when the server is full, 0 disables the queue.
+    /// See [NetcodeServer::set_max_queued_clients].
+    pub fn set_max_queued_clients(&mut self, max_queued_clients: usize) {
+        self.netcode_server.set_max_queued_clients(max_queued_clients);
+    }
+
+    /// Returns the current number of clients waiting in the queue.
+    pub fn queued_clients(&self) -> usize {
+        self.netcode_server.queued_clients()
+    }
+
     /// Returns the user data for client if connected.
     pub fn user_data(&self, client_id: ClientId) -> Option<[u8; NETCODE_USER_DATA_BYTES]> {
         self.netcode_server.user_data(client_id.raw())
@@ -57,13 +155,134 @@ impl NetcodeServerTransport {
         self.netcode_server.client_addr(client_id.raw())
     }
 
+    /// Adds a private key accepted when decrypting connect tokens, replacing any key with the same id.
+    /// See [NetcodeServer::add_private_key].
+    pub fn add_private_key(&mut self, key_id: u32, private_key: [u8; NETCODE_KEY_BYTES]) {
+        self.netcode_server.add_private_key(key_id, private_key);
+    }
+
+    /// Removes a private key, returns false if there was no key with this id.
+    pub fn remove_private_key(&mut self, key_id: u32) -> bool {
+        self.netcode_server.remove_private_key(key_id)
+    }
+
+    /// Sets a callback to approve or deny new connection requests before they use a slot in the server.
+    /// See [NetcodeServer::set_connection_approval].
+    pub fn set_connection_approval<F>(&mut self, approval: F)
+    where
+        F: FnMut(&ConnectionRequest) -> ConnectionApproval + Send + Sync + 'static,
+    {
+        self.netcode_server.set_connection_approval(approval);
+    }
+
+    /// Removes the connection approval callback.
+    pub fn clear_connection_approval(&mut self) {
+        self.netcode_server.clear_connection_approval();
+    }
+
+    /// Bans a client id, connection requests with this client id are denied.
+    /// See [NetcodeServer::ban_client_id].
+    pub fn ban_client_id(&mut self, client_id: ClientId, duration: Option<Duration>) {
+        self.netcode_server.ban_client_id(client_id.raw(), duration);
+    }
+
+    /// Removes the ban from a client id, returns false if it was not banned.
+    pub fn unban_client_id(&mut self, client_id: ClientId) -> bool {
+        self.netcode_server.unban_client_id(client_id.raw())
+    }
+
+    /// Bans an ip address or a range of addresses.
+    /// See [NetcodeServer::ban_ip_range].
+    pub fn ban_ip_range(&mut self, range: impl Into<IpRange>, duration: Option<Duration>) {
+        self.netcode_server.ban_ip_range(range, duration);
+    }
+
+    /// Removes the ban from an ip address or range, returns false if it was not banned.
+    pub fn unban_ip_range(&mut self, range: impl Into<IpRange>) -> bool {
+        self.netcode_server.unban_ip_range(range)
+    }
+
+    /// Enables rate limiting of connection requests and invalid packets per ip address, or disables it with None.
+    pub fn set_rate_limit(&mut self, rate_limit: Option<RateLimitConfig>) {
+        self.netcode_server.set_rate_limit(rate_limit);
+    }
+
+    /// Returns counters of packets dropped by the ban lists and rate limits.
+    pub fn packet_filter_stats(&self) -> PacketFilterStats {
+        self.netcode_server.packet_filter_stats()
+    }
+
+    /// Sets how many times the disconnect packet is sent to a disconnected client, the minimum is 1.
+    /// See [NetcodeServer::set_disconnect_packets].
+    pub fn set_disconnect_packets(&mut self, disconnect_packets: usize) {
+        self.netcode_server.set_disconnect_packets(disconnect_packets);
+    }
+
+    /// Connects a loopback client, a client running in the same process as the server, like the host in a listen server.
+    /// The loopback client uses a slot in the server and is added to the [RenetServer] like any other client,
+    /// its packets are exchanged in-process with [NetcodeServerTransport::update_loopback_client].
+    /// See [NetcodeServer::connect_loopback_client].
+    pub fn connect_loopback_client(
+        &mut self,
+        client_id: ClientId,
+        user_data: Option<&[u8; NETCODE_USER_DATA_BYTES]>,
+        server: &mut RenetServer,
+        client: &mut RenetClient,
+    ) -> Result<(), NetcodeError> {
+        self.netcode_server.connect_loopback_client(client_id.raw(), user_data)?;
+
+        let info = ConnectionInfo {
+            transport: TransportKind::Netcode,
+            addr: None,
+            user_data: user_data.map(|user_data| Bytes::copy_from_slice(user_data)),
+            max_packet_size: None,
+        };
+        server.add_connection_with_info(client_id, info);
+        client.set_connected();
+
+        Ok(())
+    }
+
+    /// Returns whether the client is a connected loopback client.
+    pub fn is_loopback_client(&self, client_id: ClientId) -> bool {
+        self.netcode_server.is_loopback_client(client_id.raw())
+    }
+
+    /// Exchanges the packets between the server and the loopback client, should be called every tick
+    /// instead of sending packets from the client. Disconnects the loopback client from the server
+    /// when the client is disconnected, and the client when it was disconnected by the server.
+    pub fn update_loopback_client(&mut self, client_id: ClientId, server: &mut RenetServer, client: &mut RenetClient) {
+        if !self.netcode_server.is_loopback_client(client_id.raw()) {
+            if !client.is_disconnected() {
+                client.disconnect_due_to_transport();
+            }
+            return;
+        }
+
+        if client.is_disconnected() {
+            let server_result = self.netcode_server.disconnect(client_id.raw());
+            handle_server_result(server_result, &mut self.sockets, None, server, 0);
+            return;
+        }
+
+        if let Err(e) = server.for_each_packet_to_send(client_id, |packet| client.process_packet(packet)) {
+            log::error!("Failed to send packets to loopback client {client_id}: {e}");
+        }
+
+        client.for_each_packet_to_send(|packet| {
+            let server_result = self.netcode_server.process_loopback_payload(client_id.raw(), packet);
+            handle_server_result(server_result, &mut self.sockets, None, server, 0);
+        });
+    }
+
     /// Disconnects all connected clients.
-    /// This sends the disconnect packet instantly, use this when closing/exiting games,
+    /// This sends the disconnect packets instantly, use this when closing/exiting games,
     /// should use [RenetServer::disconnect_all][crate::RenetServer::disconnect_all] otherwise.
     pub fn disconnect_all(&mut self, server: &mut RenetServer) {
+        let disconnect_packets = self.netcode_server.disconnect_packets();
         for client_id in self.netcode_server.clients_id() {
             let server_result = self.netcode_server.disconnect(client_id);
-            handle_server_result(server_result, &self.socket, server);
+            handle_server_result(server_result, &mut self.sockets, None, server, disconnect_packets);
         }
     }
 
@@ -76,28 +295,55 @@ impl NetcodeServerTransport {
     /// Advances the transport by the duration, and receive packets from the network.
     pub fn update(&mut self, duration: Duration, server: &mut RenetServer) -> Result<(), NetcodeTransportError> {
         self.netcode_server.update(duration);
+        let disconnect_packets = self.netcode_server.disconnect_packets();
+
+        for socket_index in 0..self.sockets.sockets.len() {
+            #[cfg(all(feature = "batch_io", target_os = "linux"))]
+            if let Some(batch) = self.batch.as_mut() {
+                loop {
+                    let (received, usable) = match batch.recv(self.sockets.udp_socket(socket_index)) {
+                        Ok(received) => received,
+                        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                        Err(ref e) if e.kind() == io::ErrorKind::Interrupted => break,
+                        Err(ref e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
+                        Err(e) => return Err(e.into()),
+                    };
 
-        loop {
-            match self.socket.recv_from(&mut self.buffer) {
-                Ok((len, addr)) => {
-                    let server_result = self.netcode_server.process_packet(addr, &mut self.buffer[..len]);
-                    handle_server_result(server_result, &self.socket, server);
+                    for (buffer, (len, addr)) in batch.recv_buffers.iter_mut().zip(batch.recv_packets).take(usable) {
+                        let server_result = self.netcode_server.process_packet(addr, &mut buffer[..len]);
+                        handle_server_result(server_result, &mut self.sockets, Some(socket_index), server, disconnect_packets);
+                    }
+
+                    // A full batch can have unusable packets, only a partial read means the socket is empty
+                    if received < BATCH_SIZE {
+                        break;
+                    }
                 }
-                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
-                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => break,
-                Err(ref e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
-                Err(e) => return Err(e.into()),
-            };
+                continue;
+            }
+
+            loop {
+                match self.sockets.sockets[socket_index].recv_from(&mut self.buffer) {
+                    Ok((len, addr)) => {
+                        let server_result = self.netcode_server.process_packet(addr, &mut self.buffer[..len]);
+                        handle_server_result(server_result, &mut self.sockets, Some(socket_index), server, disconnect_packets);
+                    }
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => break,
+                    Err(ref e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
+                    Err(e) => return Err(e.into()),
+                };
+            }
         }
 
         for client_id in self.netcode_server.clients_id() {
             let server_result = self.netcode_server.update_client(client_id);
-            handle_server_result(server_result, &self.socket, server);
+            handle_server_result(server_result, &mut self.sockets, None, server, disconnect_packets);
         }
 
         for disconnection_id in server.disconnections_id() {
             let server_result = self.netcode_server.disconnect(disconnection_id.raw());
-            handle_server_result(server_result, &self.socket, server);
+            handle_server_result(server_result, &mut self.sockets, None, server, disconnect_packets);
         }
 
         Ok(())
@@ -105,29 +351,77 @@ impl NetcodeServerTransport {
 
     /// Send packets to connected clients.
     pub fn send_packets(&mut self, server: &mut RenetServer) {
-        'clients: for client_id in server.clients_id() {
-            let packets = server.get_packets_to_send(client_id).unwrap();
-            for packet in packets {
-                match self.netcode_server.generate_payload_packet(client_id.raw(), &packet) {
+        for client_id in server.clients_id() {
+            // Loopback clients receive their packets in update_loopback_client
+            if self.netcode_server.is_loopback_client(client_id.raw()) {
+                continue;
+            }
+
+            let mut failed = false;
+            let result = server.for_each_packet_to_send(client_id, |packet| {
+                if failed {
+                    return;
+                }
+
+                match self.netcode_server.generate_payload_packet(client_id.raw(), packet) {
                     Ok((addr, payload)) => {
-                        if let Err(e) = self.socket.send_to(payload, addr) {
+                        #[cfg(all(feature = "batch_io", target_os = "linux"))]
+                        if let Some(batch) = self.batch.as_mut() {
+                            batch.queue(self.sockets.socket_index(addr, None), addr, payload);
+                            return;
+                        }
+
+                        if let Err(e) = self.sockets.send_to(payload, addr, None) {
                             log::error!("Failed to send packet to client {client_id} ({addr}): {e}");
-                            continue 'clients;
+                            failed = true;
                         }
                     }
                     Err(e) => {
                         log::error!("Failed to encrypt payload packet for client {client_id}: {e}");
-                        continue 'clients;
+                        failed = true;
                     }
                 }
-            }
+            });
+            result.unwrap();
+        }
+
+        #[cfg(all(feature = "batch_io", target_os = "linux"))]
+        if let Some(batch) = self.batch.as_mut() {
+            batch.flush(&self.sockets.sockets);
         }
     }
 }
 
-fn handle_server_result(server_result: ServerResult, socket: &UdpSocket, reliable_server: &mut RenetServer) {
-    let send_packet = |packet: &[u8], addr: SocketAddr| {
-        if let Err(err) = socket.send_to(packet, addr) {
+impl<S: TransportSocket> ServerTransport for NetcodeServerTransport<S> {
+    type Error = NetcodeTransportError;
+
+    fn update(&mut self, duration: Duration, server: &mut RenetServer) -> Result<(), Self::Error> {
+        NetcodeServerTransport::update(self, duration, server)
+    }
+
+    fn send_packets(&mut self, server: &mut RenetServer) {
+        NetcodeServerTransport::send_packets(self, server)
+    }
+
+    fn disconnect_all(&mut self, server: &mut RenetServer) {
+        NetcodeServerTransport::disconnect_all(self, server)
+    }
+
+    fn max_clients(&self) -> usize {
+        NetcodeServerTransport::max_clients(self)
+    }
+}
+
+/// Handles the result from the netcode server, `received_on` is the socket index if the result is from a received packet.
+fn handle_server_result<S: TransportSocket>(
+    server_result: ServerResult,
+    sockets: &mut ServerSockets<S>,
+    received_on: Option<usize>,
+    reliable_server: &mut RenetServer,
+    disconnect_packets: usize,
+) {
+    let send_packet = |sockets: &ServerSockets<S>, packet: &[u8], addr: SocketAddr| {
+        if let Err(err) = sockets.send_to(packet, addr, received_on) {
             log::error!("Failed to send packet to {addr}: {err}");
         }
     };
@@ -135,7 +429,7 @@ fn handle_server_result(server_result: ServerResult, socket: &UdpSocket, reliabl
     match server_result {
         ServerResult::None => {}
         ServerResult::PacketToSend { payload, addr } => {
-            send_packet(payload, addr);
+            send_packet(sockets, payload, addr);
         }
         ServerResult::Payload { client_id, payload } => {
             let client_id = ClientId::from_raw(client_id);
@@ -145,18 +439,29 @@ fn handle_server_result(server_result: ServerResult, socket: &UdpSocket, reliabl
         }
         ServerResult::ClientConnected {
             client_id,
-            user_data: _,
+            user_data,
             addr,
             payload,
         } => {
-            reliable_server.add_connection(ClientId::from_raw(client_id));
-            send_packet(payload, addr);
+            let info = ConnectionInfo {
+                transport: TransportKind::Netcode,
+                addr: Some(addr),
+                user_data: Some(Bytes::copy_from_slice(user_data.as_slice())),
+                max_packet_size: Some(NETCODE_MAX_PAYLOAD_BYTES),
+            };
+            reliable_server.add_connection_with_info(ClientId::from_raw(client_id), info);
+            let socket_index = sockets.socket_index(addr, received_on);
+            sockets.client_sockets.insert(addr, socket_index);
+            send_packet(sockets, payload, addr);
         }
         ServerResult::ClientDisconnected { client_id, addr, payload } => {
             reliable_server.remove_connection(ClientId::from_raw(client_id));
             if let Some(payload) = payload {
-                send_packet(payload, addr);
+                for _ in 0..disconnect_packets {
+                    send_packet(sockets, payload, addr);
+                }
             }
+            sockets.client_sockets.remove(&addr);
         }
     }
 }
@@ -0,0 +1,81 @@
+use std::{
+    fmt, io,
+    net::{SocketAddr, UdpSocket},
+    sync::Arc,
+};
+
+/// Datagram socket used by the netcode transports to exchange packets, implemented for [UdpSocket].
+///
+/// Implement it to run the transports over other channels, like an in-memory network for tests,
+/// a relay or tunnel, or to wrap another socket to record metrics.
+pub trait TransportSocket: fmt::Debug + Send + Sync + 'static {
+    /// Called once when the transport is created, [TransportSocket::recv_from] must not block after it.
+    fn enable_nonblocking(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Returns the local address of the socket.
+    fn local_addr(&self) -> io::Result<SocketAddr>;
+
+    /// Sends the packet to the address, returns the number of bytes sent.
+    fn send_to(&self, packet: &[u8], addr: SocketAddr) -> io::Result<usize>;
+
+    /// Receives a packet in the buffer, returns its length and the address it came from.
+    /// Returns an error with [io::ErrorKind::WouldBlock] when there are no packets available.
+    fn recv_from(&self, buffer: &mut [u8]) -> io::Result<(usize, SocketAddr)>;
+
+    /// Returns the [UdpSocket] used by this socket, if any.
+    /// The server transport only uses batched I/O when every socket returns one.
+    ///
+    /// With the `batch_io` feature, packets are sent and received directly on the returned [UdpSocket],
+    /// so [TransportSocket::send_to] and [TransportSocket::recv_from] are not called.
+    /// Wrappers that do extra work in those methods, e.g. filtering or counting packets, should keep returning `None`.
+    fn as_udp_socket(&self) -> Option<&UdpSocket> {
+        None
+    }
+}
+
+impl TransportSocket for UdpSocket {
+    fn enable_nonblocking(&self) -> io::Result<()> {
+        self.set_nonblocking(true)
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        UdpSocket::local_addr(self)
+    }
+
+    fn send_to(&self, packet: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        UdpSocket::send_to(self, packet, addr)
+    }
+
+    fn recv_from(&self, buffer: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        UdpSocket::recv_from(self, buffer)
+    }
+
+    fn as_udp_socket(&self) -> Option<&UdpSocket> {
+        Some(self)
+    }
+}
+
+/// Shares the socket with other users, e.g. to wait for it to become readable in an async runtime.
+impl<T: TransportSocket> TransportSocket for Arc<T> {
+    fn enable_nonblocking(&self) -> io::Result<()> {
+        T::enable_nonblocking(self)
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        T::local_addr(self)
+    }
+
+    fn send_to(&self, packet: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        T::send_to(self, packet, addr)
+    }
+
+    fn recv_from(&self, buffer: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        T::recv_from(self, buffer)
+    }
+
+    fn as_udp_socket(&self) -> Option<&UdpSocket> {
+        T::as_udp_socket(self)
+    }
+}
@@ -0,0 +1,405 @@
+use std::{io, net::SocketAddr, sync::Arc, time::Duration};
+
+use bytes::Bytes;
+use tokio::{
+    net::UdpSocket,
+    sync::mpsc,
+    task::JoinHandle,
+    time::{self, Instant, MissedTickBehavior},
+};
+
+use crate::{ChannelConfig, ClientEvent, ClientId, ConnectionConfig, DisconnectReason, RenetClient, RenetServer, SendType, ServerEvent};
+
+use super::{
+    ClientAuthentication, ClientConfig, NetcodeClientTransport, NetcodeError, NetcodeServerTransport, NetcodeTransportError, ServerConfig,
+    TransportSocket,
+};
+
+// Events waiting to be received, the tasks wait for room in the channel when it's full
+const EVENTS_CAPACITY: usize = 1024;
+
+impl TransportSocket for UdpSocket {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        UdpSocket::local_addr(self)
+    }
+
+    fn send_to(&self, packet: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        self.try_send_to(packet, addr)
+    }
+
+    fn recv_from(&self, buffer: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.try_recv_from(buffer)
+    }
+}
+
+/// Events from a [TokioServer].
+#[derive(Debug)]
+pub enum TokioServerEvent {
+    ClientConnected {
+        client_id: ClientId,
+        addr: Option<SocketAddr>,
+    },
+    ClientDisconnected {
+        client_id: ClientId,
+        reason: DisconnectReason,
+    },
+    /// A message received from the client.
+    Message {
+        client_id: ClientId,
+        channel_id: u8,
+        message: Bytes,
+    },
+}
+
+/// Events from a [TokioClient].
+#[derive(Debug)]
+pub enum TokioClientEvent {
+    Connected,
+    /// A message received from the server.
+    Message {
+        channel_id: u8,
+        message: Bytes,
+    },
+    /// The client was disconnected and its task finished.
+    Disconnected(NetcodeTransportError),
+}
+
+enum ServerCommand {
+    SendMessage {
+        client_id: ClientId,
+        channel_id: u8,
+        message: Bytes,
+    },
+    BroadcastMessage {
+        channel_id: u8,
+        message: Bytes,
+    },
+    Disconnect(ClientId),
+    Shutdown,
+}
+
+enum ClientCommand {
+    SendMessage { channel_id: u8, message: Bytes },
+    Disconnect,
+}
+
+/// A [RenetServer] with a [NetcodeServerTransport] running in a tokio task.
+///
+/// The task waits for incoming packets instead of polling, and updates the server when packets arrive,
+/// when a command is sent and at least once every tick. Events and received messages are sent to the
+/// channel returned by [TokioServer::spawn], the task waits when the channel is full so it must be received regularly.
+#[derive(Debug)]
+pub struct TokioServer {
+    commands: mpsc::UnboundedSender<ServerCommand>,
+    task: JoinHandle<Result<(), NetcodeTransportError>>,
+}
+
+impl TokioServer {
+    /// Spawns the server task, must be called from a tokio runtime.
+    /// The tick is the maximum interval between updates, and how often the reliable messages are resent.
+    /// Returns an error if a channel is a keyed or stream channel, only plain messages are supported.
+    pub fn spawn(
+        server_config: ServerConfig,
+        connection_config: ConnectionConfig,
+        socket: UdpSocket,
+        tick: Duration,
+    ) -> io::Result<(Self, mpsc::Receiver<TokioServerEvent>)> {
+        check_channels(&connection_config)?;
+        let socket = Arc::new(socket);
+        let transport = NetcodeServerTransport::new(server_config, socket.clone())?;
+        let receive_channels = message_channels(&connection_config.client_channels_config);
+        let server = RenetServer::new(connection_config);
+
+        let (commands, commands_receiver) = mpsc::unbounded_channel();
+        let (events, events_receiver) = mpsc::channel(EVENTS_CAPACITY);
+        let task = tokio::spawn(run_server(
+            transport,
+            server,
+            socket,
+            receive_channels,
+            tick,
+            commands_receiver,
+            events,
+        ));
+
+        Ok((Self { commands, task }, events_receiver))
+    }
+
+    /// Queues a message to the client, see [RenetServer::send_message].
+    pub fn send_message<I: Into<u8>, B: Into<Bytes>>(&self, client_id: ClientId, channel_id: I, message: B) {
+        self.send_command(ServerCommand::SendMessage {
+            client_id,
+            channel_id: channel_id.into(),
+            message: message.into(),
+        });
+    }
+
+    /// Queues a message to all the connected clients, see [RenetServer::broadcast_message].
+    pub fn broadcast_message<I: Into<u8>, B: Into<Bytes>>(&self, channel_id: I, message: B) {
+        self.send_command(ServerCommand::BroadcastMessage {
+            channel_id: channel_id.into(),
+            message: message.into(),
+        });
+    }
+
+    /// Disconnects the client.
+    pub fn disconnect(&self, client_id: ClientId) {
+        self.send_command(ServerCommand::Disconnect(client_id));
+    }
+
+    /// Returns whether the server task finished, because of an error or a shutdown.
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Disconnects all the clients and waits for the server task to finish.
+    /// Returns the error that stopped the task, if any.
+    pub async fn shutdown(self) -> Result<(), NetcodeTransportError> {
+        self.send_command(ServerCommand::Shutdown);
+        self.task.await.unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))
+    }
+
+    fn send_command(&self, command: ServerCommand) {
+        if self.commands.send(command).is_err() {
+            log::error!("Failed to send command to the server, the server task finished");
+        }
+    }
+}
+
+/// A [RenetClient] with a [NetcodeClientTransport] running in a tokio task.
+///
+/// The task waits for incoming packets instead of polling, and updates the client when packets arrive,
+/// when a command is sent and at least once every tick. Events and received messages are sent to the
+/// channel returned by [TokioClient::spawn], the task waits when the channel is full so it must be received regularly.
+/// The task finishes after [TokioClientEvent::Disconnected].
+#[derive(Debug)]
+pub struct TokioClient {
+    commands: mpsc::UnboundedSender<ClientCommand>,
+    task: JoinHandle<()>,
+}
+
+impl TokioClient {
+    /// Spawns the client task, must be called from a tokio runtime.
+    /// The tick is the maximum interval between updates, and how often the reliable messages are resent.
+    /// Returns an error if a channel is a keyed or stream channel, only plain messages are supported.
+    pub fn spawn(
+        current_time: Duration,
+        authentication: ClientAuthentication,
+        client_config: ClientConfig,
+        connection_config: ConnectionConfig,
+        socket: UdpSocket,
+        tick: Duration,
+    ) -> Result<(Self, mpsc::Receiver<TokioClientEvent>), NetcodeError> {
+        check_channels(&connection_config)?;
+        let socket = Arc::new(socket);
+        let transport = NetcodeClientTransport::with_config(current_time, authentication, client_config, socket.clone())?;
+        let receive_channels = message_channels(&connection_config.server_channels_config);
+        let client = RenetClient::new(connection_config);
+
+        let (commands, commands_receiver) = mpsc::unbounded_channel();
+        let (events, events_receiver) = mpsc::channel(EVENTS_CAPACITY);
+        let task = tokio::spawn(run_client(
+            transport,
+            client,
+            socket,
+            receive_channels,
+            tick,
+            commands_receiver,
+            events,
+        ));
+
+        Ok((Self { commands, task }, events_receiver))
+    }
+
+    /// Queues a message to the server, see [RenetClient::send_message].
+    pub fn send_message<I: Into<u8>, B: Into<Bytes>>(&self, channel_id: I, message: B) {
+        let command = ClientCommand::SendMessage {
+            channel_id: channel_id.into(),
+            message: message.into(),
+        };
+        if self.commands.send(command).is_err() {
+            log::error!("Failed to send message, the client task finished");
+        }
+    }
+
+    /// Disconnects from the server, the task finishes after sending the disconnect packets.
+    pub fn disconnect(&self) {
+        let _ = self.commands.send(ClientCommand::Disconnect);
+    }
+
+    /// Returns whether the client task finished.
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Waits for the client task to finish.
+    pub async fn join(self) {
+        self.task.await.unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))
+    }
+}
+
+// Keyed and stream channels have their own send and receive methods, the tasks only handle plain messages
+fn check_channels(connection_config: &ConnectionConfig) -> io::Result<()> {
+    let channels_config = connection_config
+        .server_channels_config
+        .iter()
+        .chain(connection_config.client_channels_config.iter());
+    for config in channels_config {
+        if matches!(config.send_type, SendType::ReliableKeyed { .. } | SendType::ReliableStream { .. }) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "channel {} is a keyed or stream channel, not supported by the tokio transports",
+                    config.channel_id
+                ),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn message_channels(channels_config: &[ChannelConfig]) -> Vec<u8> {
+    channels_config.iter().map(|config| config.channel_id).collect()
+}
+
+async fn run_server(
+    mut transport: NetcodeServerTransport<Arc<UdpSocket>>,
+    mut server: RenetServer,
+    socket: Arc<UdpSocket>,
+    receive_channels: Vec<u8>,
+    tick: Duration,
+    mut commands: mpsc::UnboundedReceiver<ServerCommand>,
+    events: mpsc::Sender<TokioServerEvent>,
+) -> Result<(), NetcodeTransportError> {
+    let mut interval = time::interval(tick);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut last_update = Instant::now();
+
+    loop {
+        // Packets are only sent when the interval elapses, received packets and commands only update the server.
+        // Sending on every wakeup would send an ack for each received packet and go over the bandwidth budget of the tick
+        let tick_elapsed = tokio::select! {
+            _ = interval.tick() => true,
+            readable = socket.readable() => {
+                readable?;
+                false
+            }
+            command = commands.recv() => {
+                match command {
+                    Some(ServerCommand::SendMessage { client_id, channel_id, message }) => server.send_message(client_id, channel_id, message),
+                    Some(ServerCommand::BroadcastMessage { channel_id, message }) => server.broadcast_message(channel_id, message),
+                    Some(ServerCommand::Disconnect(client_id)) => server.disconnect(client_id),
+                    // Shutdown when requested or when the handle was dropped
+                    Some(ServerCommand::Shutdown) | None => {
+                        transport.disconnect_all(&mut server);
+                        return Ok(());
+                    }
+                }
+                false
+            }
+        };
+
+        let now = Instant::now();
+        let duration = now - last_update;
+        last_update = now;
+
+        server.update(duration);
+        transport.update(duration, &mut server)?;
+
+        while let Some(event) = server.get_event() {
+            let event = match event {
+                ServerEvent::ClientConnected { client_id, info } => TokioServerEvent::ClientConnected {
+                    client_id,
+                    addr: info.addr,
+                },
+                ServerEvent::ClientDisconnected { client_id, reason } => TokioServerEvent::ClientDisconnected { client_id, reason },
+            };
+            let _ = events.send(event).await;
+        }
+
+        for client_id in server.clients_id() {
+            for &channel_id in receive_channels.iter() {
+                while let Some(message) = server.receive_message(client_id, channel_id) {
+                    let _ = events
+                        .send(TokioServerEvent::Message {
+                            client_id,
+                            channel_id,
+                            message,
+                        })
+                        .await;
+                }
+            }
+        }
+
+        if tick_elapsed {
+            transport.send_packets(&mut server);
+        }
+    }
+}
+
+async fn run_client(
+    mut transport: NetcodeClientTransport<Arc<UdpSocket>>,
+    mut client: RenetClient,
+    socket: Arc<UdpSocket>,
+    receive_channels: Vec<u8>,
+    tick: Duration,
+    mut commands: mpsc::UnboundedReceiver<ClientCommand>,
+    events: mpsc::Sender<TokioClientEvent>,
+) {
+    let mut interval = time::interval(tick);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut last_update = Instant::now();
+
+    loop {
+        // Like the server, packets are only sent when the interval elapses
+        let tick_elapsed = tokio::select! {
+            _ = interval.tick() => true,
+            readable = socket.readable() => {
+                if let Err(e) = readable {
+                    transport.disconnect();
+                    let _ = events.send(TokioClientEvent::Disconnected(e.into())).await;
+                    return;
+                }
+                false
+            }
+            command = commands.recv() => {
+                match command {
+                    Some(ClientCommand::SendMessage { channel_id, message }) => client.send_message(channel_id, message),
+                    // Disconnect when requested or when the handle was dropped
+                    Some(ClientCommand::Disconnect) | None => client.disconnect(),
+                }
+                false
+            }
+        };
+
+        let now = Instant::now();
+        let duration = now - last_update;
+        last_update = now;
+
+        client.update(duration);
+        if let Err(e) = transport.update(duration, &mut client) {
+            let _ = events.send(TokioClientEvent::Disconnected(e)).await;
+            return;
+        }
+
+        while let Some(event) = client.get_event() {
+            if event == ClientEvent::Connected {
+                let _ = events.send(TokioClientEvent::Connected).await;
+            }
+        }
+
+        for &channel_id in receive_channels.iter() {
+            while let Some(message) = client.receive_message(channel_id) {
+                let _ = events.send(TokioClientEvent::Message { channel_id, message }).await;
+            }
+        }
+
+        if tick_elapsed && transport.is_connected() {
+            if let Err(e) = transport.send_packets(&mut client) {
+                let _ = events.send(TokioClientEvent::Disconnected(e)).await;
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use std::time::Duration;
+
+use crate::{RenetClient, RenetServer};
+
+/// Common interface of the server transports, so game code and plugins can be written once
+/// and the transport chosen by configuration.
+///
+/// Implemented by `NetcodeServerTransport` and the Steam transport from `renet_steam`.
+pub trait ServerTransport {
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    /// Advances the transport by the duration, and receive packets from the network.
+    /// Transports that don't track time themselves ignore the duration.
+    fn update(&mut self, duration: Duration, server: &mut RenetServer) -> Result<(), Self::Error>;
+
+    /// Send packets to connected clients.
+    fn send_packets(&mut self, server: &mut RenetServer);
+
+    /// Disconnects all connected clients immediately, use this when closing/exiting games.
+    fn disconnect_all(&mut self, server: &mut RenetServer);
+
+    /// Returns the maximum number of clients that can be connected.
+    fn max_clients(&self) -> usize;
+}
+
+/// Common interface of the client transports, so game code and plugins can be written once
+/// and the transport chosen by configuration.
+///
+/// Implemented by `NetcodeClientTransport` and the Steam transport from `renet_steam`.
+pub trait ClientTransport {
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    /// Advances the transport by the duration, and receive packets from the network.
+    /// Transports that don't track time themselves ignore the duration.
+    fn update(&mut self, duration: Duration, client: &mut RenetClient) -> Result<(), Self::Error>;
+
+    /// Send packets to the server.
+    fn send_packets(&mut self, client: &mut RenetClient) -> Result<(), Self::Error>;
+
+    fn is_connecting(&self) -> bool;
+
+    fn is_connected(&self) -> bool;
+
+    fn is_disconnected(&self) -> bool;
+
+    /// Disconnects from the server immediately, use this when closing/exiting games.
+    fn disconnect(&mut self);
+}
@@ -1,5 +1,10 @@
+use std::{io::Cursor, time::Duration};
+
 use bytes::Bytes;
-use renet::{ClientId, ConnectionConfig, DefaultChannel, RenetClient, RenetServer};
+use renet::{
+    ChannelConfig, ClientEvent, ClientId, ConnectionConfig, ConnectionInfo, DefaultChannel, DisconnectReason, MtuDiscoveryConfig,
+    RenetClient, RenetServer, SendType, ServerEvent, StreamChunk, StreamState, TransportKind,
+};
 
 pub fn init_log() {
     let _ = env_logger::builder().is_test(true).try_init();
@@ -54,3 +59,887 @@ fn test_remote_connection_reliable_channel() {
 
     assert_eq!(count, 10);
 }
+
+#[test]
+fn test_remote_connection_stream_channel() {
+    init_log();
+    let channels_config = vec![ChannelConfig {
+        channel_id: 0,
+        max_memory_usage_bytes: 20_000,
+        send_type: SendType::ReliableStream {
+            resend_time: Duration::from_millis(100),
+        },
+    }];
+    let connection_config = ConnectionConfig {
+        server_channels_config: channels_config.clone(),
+        client_channels_config: channels_config,
+        ..Default::default()
+    };
+    let mut server = RenetServer::new(connection_config.clone());
+    let mut client = RenetClient::new(connection_config);
+
+    let client_id = ClientId::from_raw(0);
+    server.add_connection(client_id);
+
+    let data: Vec<u8> = (0..200_000).map(|i| (i % 251) as u8).collect();
+    let stream_id = server.send_stream(client_id, 0, Cursor::new(data.clone())).unwrap();
+
+    let mut received: Vec<u8> = vec![];
+    let mut finished = false;
+    for _ in 0..100 {
+        for packet in server.get_packets_to_send(client_id).unwrap() {
+            client.process_packet(&packet);
+        }
+        for packet in client.get_packets_to_send() {
+            server.process_packet_from(&packet, client_id).unwrap();
+        }
+
+        while let Some(chunk) = client.receive_stream_chunk(0) {
+            match chunk {
+                StreamChunk::Data { stream_id: id, data } => {
+                    assert_eq!(id, stream_id);
+                    received.extend_from_slice(&data);
+                }
+                StreamChunk::Finished { stream_id: id } => {
+                    assert_eq!(id, stream_id);
+                    finished = true;
+                }
+                StreamChunk::Aborted { .. } => unreachable!(),
+            }
+        }
+
+        // Only the data that fits in the channel memory is read each tick
+        let stream_progress = server.stream_progress(client_id, 0, stream_id).unwrap();
+        assert!(stream_progress.bytes_sent - stream_progress.bytes_acked <= 20_000);
+
+        if finished {
+            break;
+        }
+    }
+
+    assert_eq!(client.disconnect_reason(), None);
+    assert!(finished);
+    assert_eq!(received, data);
+
+    let progress = server.stream_progress(client_id, 0, stream_id).unwrap();
+    assert_eq!(progress.bytes_sent, data.len() as u64);
+    assert_eq!(progress.bytes_acked, data.len() as u64);
+    assert_eq!(progress.state, StreamState::Completed);
+
+    // Finished streams are kept until removed
+    assert_eq!(server.stream_progress(client_id, 0, stream_id), Some(progress));
+    assert_eq!(server.remove_stream(client_id, 0, stream_id), Some(progress));
+    assert!(server.stream_progress(client_id, 0, stream_id).is_none());
+}
+
+#[test]
+fn test_remote_connection_mtu_discovery() {
+    init_log();
+    let connection_config = ConnectionConfig {
+        mtu_discovery: Some(MtuDiscoveryConfig {
+            max_slice_size: 2000,
+            probe_interval: Duration::from_millis(100),
+        }),
+        ..Default::default()
+    };
+    let mut server = RenetServer::new(connection_config.clone());
+    let mut client = RenetClient::new(connection_config);
+
+    let client_id = ClientId::from_raw(0);
+    server.add_connection(client_id);
+    assert_eq!(client.slice_size(), 1200);
+
+    for _ in 0..20 {
+        client.update(Duration::from_millis(100));
+        server.update(Duration::from_millis(100));
+        for packet in client.get_packets_to_send() {
+            server.process_packet_from(&packet, client_id).unwrap();
+        }
+        for packet in server.get_packets_to_send(client_id).unwrap() {
+            client.process_packet(&packet);
+        }
+    }
+
+    // The search stops when the next step would be too small
+    assert!(client.slice_size() > 1950);
+
+    // Big messages are sliced with the discovered size
+    let message = Bytes::from(vec![7; 10_000]);
+    client.send_message(DefaultChannel::ReliableOrdered, message.clone());
+    let packets = client.get_packets_to_send();
+    // 6 slices and 1 ack packet
+    assert_eq!(packets.len(), 7);
+    for packet in packets {
+        server.process_packet_from(&packet, client_id).unwrap();
+    }
+
+    assert_eq!(server.receive_message(client_id, DefaultChannel::ReliableOrdered).unwrap(), message);
+    assert_eq!(client.disconnect_reason(), None);
+    assert!(server.is_connected(client_id));
+}
+
+#[test]
+fn test_remote_connection_for_each_packet_to_send() {
+    init_log();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let mut client = RenetClient::new(ConnectionConfig::default());
+
+    let client_id = ClientId::from_raw(0);
+    server.add_connection(client_id);
+
+    for _ in 0..3 {
+        for i in 0..100u32 {
+            server.send_message(client_id, DefaultChannel::ReliableOrdered, i.to_le_bytes().to_vec());
+        }
+        server.send_message(client_id, DefaultChannel::Unreliable, Bytes::from("test".repeat(1000)));
+
+        server
+            .for_each_packet_to_send(client_id, |packet| {
+                assert!(packet.len() < 1300);
+                client.process_packet(packet);
+            })
+            .unwrap();
+        client.for_each_packet_to_send(|packet| server.process_packet_from(packet, client_id).unwrap());
+
+        for i in 0..100u32 {
+            let message = client.receive_message(DefaultChannel::ReliableOrdered).unwrap();
+            assert_eq!(message, i.to_le_bytes().as_slice());
+        }
+        assert!(client.receive_message(DefaultChannel::ReliableOrdered).is_none());
+        assert_eq!(client.receive_message(DefaultChannel::Unreliable).unwrap().len(), 4000);
+    }
+
+    // All messages were acked, only the ack packet is sent
+    server.update(Duration::from_secs(1));
+    let mut num_packets = 0;
+    server.for_each_packet_to_send(client_id, |_| num_packets += 1).unwrap();
+    assert_eq!(num_packets, 1);
+    assert_eq!(client.disconnect_reason(), None);
+}
+
+#[test]
+fn test_remote_connection_client_events() {
+    init_log();
+    let channels_config = vec![ChannelConfig {
+        channel_id: 0,
+        max_memory_usage_bytes: 1000,
+        send_type: SendType::ReliableOrdered {
+            resend_time: Duration::from_millis(300),
+        },
+    }];
+    let connection_config = ConnectionConfig {
+        server_channels_config: channels_config.clone(),
+        client_channels_config: channels_config,
+        ..Default::default()
+    };
+    let mut server = RenetServer::new(connection_config.clone());
+    let mut client = RenetClient::new(connection_config);
+
+    let client_id = ClientId::from_raw(0);
+    server.add_connection(client_id);
+
+    assert!(!client.is_connected());
+    client.set_connected();
+    assert!(client.is_connected());
+    assert_eq!(client.get_event(), Some(ClientEvent::Connected));
+
+    let message_id = client.send_tracked_message(0, Bytes::from("tracked")).unwrap();
+    for packet in client.get_packets_to_send() {
+        server.process_packet_from(&packet, client_id).unwrap();
+    }
+    for packet in server.get_packets_to_send(client_id).unwrap() {
+        client.process_packet(&packet);
+    }
+    assert_eq!(server.receive_message(client_id, 0).unwrap(), "tracked");
+    assert_eq!(
+        client.get_event(),
+        Some(ClientEvent::MessageDelivered { channel_id: 0, message_id })
+    );
+    assert_eq!(client.get_event(), None);
+
+    // Messages are not acked, filling the channel memory
+    for _ in 0..9 {
+        client.send_message(0, vec![0u8; 100]);
+    }
+    client.update(Duration::ZERO);
+    assert_eq!(
+        client.get_event(),
+        Some(ClientEvent::ChannelMemoryWarning {
+            channel_id: 0,
+            memory_usage_bytes: 900
+        })
+    );
+    // The warning is only emitted once
+    client.update(Duration::ZERO);
+    assert_eq!(client.get_event(), None);
+
+    client.disconnect();
+    assert!(!client.is_connected());
+    assert_eq!(
+        client.get_event(),
+        Some(ClientEvent::Disconnected {
+            reason: DisconnectReason::DisconnectedByClient
+        })
+    );
+    assert_eq!(client.get_event(), None);
+}
+
+#[test]
+fn test_server_client_connected_info() {
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let client_id = ClientId::from_raw(0);
+    let mut info = ConnectionInfo::new(TransportKind::Netcode);
+    info.addr = Some("127.0.0.1:5000".parse().unwrap());
+    info.user_data = Some(Bytes::from_static(&[1, 2, 3]));
+    server.add_connection_with_info(client_id, info.clone());
+    // Adding an existing connection does nothing
+    server.add_connection(client_id);
+
+    match server.get_event() {
+        Some(ServerEvent::ClientConnected {
+            client_id: id,
+            info: event_info,
+        }) => {
+            assert_eq!(id, client_id);
+            assert_eq!(event_info, info);
+        }
+        event => panic!("unexpected event {event:?}"),
+    }
+    assert!(server.get_event().is_none());
+}
+
+#[test]
+fn test_netcode_mtu_discovery() {
+    use renet::transport::{ClientAuthentication, NetcodeClientTransport, NetcodeServerTransport, ServerAuthentication, ServerConfig};
+    use std::net::UdpSocket;
+
+    init_log();
+    // The default max slice size is above the netcode payload limit
+    let connection_config = ConnectionConfig {
+        mtu_discovery: Some(MtuDiscoveryConfig {
+            probe_interval: Duration::from_millis(10),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let server_addr = socket.local_addr().unwrap();
+    let server_config = ServerConfig::new(Duration::ZERO, 1, 0, vec![server_addr], ServerAuthentication::Unsecure);
+    let mut server_transport = NetcodeServerTransport::new(server_config, socket).unwrap();
+    let mut server = RenetServer::new(connection_config.clone());
+
+    let client_id = ClientId::from_raw(4);
+    let authentication = ClientAuthentication::Unsecure {
+        protocol_id: 0,
+        client_id: client_id.raw(),
+        server_addr,
+        user_data: None,
+    };
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut client_transport = NetcodeClientTransport::new(Duration::ZERO, authentication, socket).unwrap();
+    let mut client = RenetClient::new(connection_config);
+
+    let message = Bytes::from(vec![3; 20_000]);
+    let mut received = false;
+    let delta = Duration::from_millis(10);
+    for tick in 0..300 {
+        client.update(delta);
+        client_transport.update(delta, &mut client).unwrap();
+        // Probes above the netcode payload limit would fail to send
+        if client_transport.is_connected() {
+            client_transport.send_packets(&mut client).unwrap();
+        }
+        server.update(delta);
+        server_transport.update(delta, &mut server).unwrap();
+        server_transport.send_packets(&mut server);
+
+        if tick == 200 {
+            assert!(client.is_connected());
+            server.send_message(client_id, DefaultChannel::ReliableOrdered, message.clone());
+        }
+        if let Some(received_message) = client.receive_message(DefaultChannel::ReliableOrdered) {
+            assert_eq!(received_message, message);
+            received = true;
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    // The slice size grows up to the netcode payload limit minus the packet header
+    assert!(received);
+    assert!(client.slice_size() > 1200);
+    assert!(client.slice_size() <= 1300 - 42);
+}
+
+#[test]
+fn test_netcode_loopback_client() {
+    use renet::transport::{NetcodeServerTransport, ServerAuthentication, ServerConfig};
+    use std::net::UdpSocket;
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let server_config = ServerConfig::new(
+        Duration::ZERO,
+        1,
+        0,
+        vec![socket.local_addr().unwrap()],
+        ServerAuthentication::Unsecure,
+    );
+    let mut transport = NetcodeServerTransport::new(server_config, socket).unwrap();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let mut client = RenetClient::new(ConnectionConfig::default());
+    let client_id = ClientId::from_raw(7);
+
+    transport
+        .connect_loopback_client(client_id, None, &mut server, &mut client)
+        .unwrap();
+    assert!(client.is_connected());
+    assert!(transport.is_loopback_client(client_id));
+    assert_eq!(transport.connected_clients(), 1);
+    assert!(matches!(server.get_event(), Some(ServerEvent::ClientConnected { client_id: id, .. }) if id == client_id));
+
+    server.send_message(client_id, DefaultChannel::ReliableOrdered, Bytes::from_static(b"from server"));
+    client.send_message(DefaultChannel::ReliableOrdered, Bytes::from_static(b"from client"));
+    for _ in 0..2 {
+        transport.update(Duration::from_millis(16), &mut server).unwrap();
+        server.update(Duration::from_millis(16));
+        client.update(Duration::from_millis(16));
+        transport.update_loopback_client(client_id, &mut server, &mut client);
+        transport.send_packets(&mut server);
+    }
+    assert_eq!(client.receive_message(DefaultChannel::ReliableOrdered).unwrap(), "from server");
+    assert_eq!(
+        server.receive_message(client_id, DefaultChannel::ReliableOrdered).unwrap(),
+        "from client"
+    );
+
+    // The loopback client never times out
+    transport.update(Duration::from_secs(60), &mut server).unwrap();
+    assert!(server.is_connected(client_id));
+
+    server.disconnect(client_id);
+    transport.update(Duration::ZERO, &mut server).unwrap();
+    transport.update_loopback_client(client_id, &mut server, &mut client);
+    assert!(!server.is_connected(client_id));
+    assert!(client.is_disconnected());
+    assert_eq!(transport.connected_clients(), 0);
+}
+
+#[test]
+fn test_netcode_client_reconnect() {
+    use renet::transport::{
+        ClientAuthentication, ConnectToken, NetcodeClientTransport, NetcodeDisconnectReason, NetcodeServerTransport, ReconnectEvent,
+        ReconnectPolicy, ServerAuthentication, ServerConfig,
+    };
+    use std::{
+        net::UdpSocket,
+        sync::{
+            atomic::{AtomicU32, Ordering},
+            Arc,
+        },
+    };
+
+    init_log();
+    let private_key = b"an example very very secret key.";
+    let new_server = || {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_config = ServerConfig::new(
+            Duration::ZERO,
+            1,
+            0,
+            vec![socket.local_addr().unwrap()],
+            ServerAuthentication::Secure { private_key: *private_key },
+        );
+        let transport = NetcodeServerTransport::new(server_config, socket).unwrap();
+        (transport, RenetServer::new(ConnectionConfig::default()))
+    };
+    let (mut transport_a, mut server_a) = new_server();
+    let (mut transport_b, mut server_b) = new_server();
+    let addr_a = transport_a.addresses()[0];
+    let addr_b = transport_b.addresses()[0];
+
+    let client_id = ClientId::from_raw(5);
+    let connect_token =
+        ConnectToken::generate(Duration::ZERO, 0, 300, client_id.raw(), 1, vec![addr_a, addr_b], None, private_key).unwrap();
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut client_transport = NetcodeClientTransport::new(Duration::ZERO, ClientAuthentication::Secure { connect_token }, socket).unwrap();
+    let mut client = RenetClient::new(ConnectionConfig::default());
+    client_transport.set_reconnect_policy(ReconnectPolicy {
+        max_attempts: 1,
+        initial_backoff: Duration::from_millis(100),
+        ..Default::default()
+    });
+    // Secure reconnects need a new connect token for each attempt
+    let token_requests = Arc::new(AtomicU32::new(0));
+    let provider_requests = token_requests.clone();
+    client_transport.set_token_provider(move |attempt| {
+        assert_eq!(attempt, 1);
+        provider_requests.fetch_add(1, Ordering::Relaxed);
+        let connect_token =
+            ConnectToken::generate(Duration::ZERO, 0, 300, client_id.raw(), 1, vec![addr_a, addr_b], None, private_key).unwrap();
+        Some(ClientAuthentication::Secure { connect_token })
+    });
+
+    let delta = Duration::from_millis(10);
+    for _ in 0..100 {
+        client_transport.update(delta, &mut client).unwrap();
+        client_transport.send_packets(&mut client).unwrap();
+        transport_a.update(delta, &mut server_a).unwrap();
+        transport_a.send_packets(&mut server_a);
+        if client.is_connected() && server_a.is_connected(client_id) {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    assert!(client.is_connected());
+    assert!(client_transport.get_event().is_none());
+
+    // The first server goes away without notifying the client
+    drop(transport_a);
+    client_transport.update(Duration::from_secs(2), &mut client).unwrap();
+    client_transport.update(Duration::ZERO, &mut client).unwrap();
+    assert_eq!(
+        client_transport.get_event(),
+        Some(ReconnectEvent::ConnectionLost {
+            reason: NetcodeDisconnectReason::ConnectionTimedOut,
+            retry_in: Duration::from_millis(100)
+        })
+    );
+    assert!(client_transport.is_reconnecting());
+    assert!(!client_transport.is_disconnected());
+    assert!(!client.is_connected() && !client.is_disconnected());
+    client_transport.send_packets(&mut client).unwrap();
+
+    // Fails over to the second server
+    for _ in 0..100 {
+        client_transport.update(delta, &mut client).unwrap();
+        client_transport.send_packets(&mut client).unwrap();
+        transport_b.update(delta, &mut server_b).unwrap();
+        transport_b.send_packets(&mut server_b);
+        if client.is_connected() && server_b.is_connected(client_id) {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    assert!(client.is_connected());
+    assert_eq!(token_requests.load(Ordering::Relaxed), 1);
+    assert_eq!(
+        client_transport.get_event(),
+        Some(ReconnectEvent::Attempt {
+            attempt: 1,
+            server_addr: addr_b
+        })
+    );
+    assert_eq!(
+        client_transport.get_event(),
+        Some(ReconnectEvent::Reconnected {
+            attempt: 1,
+            server_addr: addr_b
+        })
+    );
+    assert!(matches!(client.get_event(), Some(ClientEvent::Connected)));
+    assert!(matches!(client.get_event(), Some(ClientEvent::Connected)));
+
+    // Gives up after the attempt against both addresses fails
+    drop(transport_b);
+    client_transport.update(Duration::from_secs(2), &mut client).unwrap();
+    client_transport.update(Duration::ZERO, &mut client).unwrap();
+    client_transport.update(Duration::from_millis(100), &mut client).unwrap();
+    for _ in 0..2 {
+        client_transport.update(Duration::from_secs(2), &mut client).unwrap();
+    }
+    assert!(client_transport.update(Duration::ZERO, &mut client).is_err());
+    assert!(client_transport.is_disconnected());
+    assert!(client.is_disconnected());
+
+    let events: Vec<ReconnectEvent> = std::iter::from_fn(|| client_transport.get_event()).collect();
+    assert_eq!(
+        events,
+        vec![
+            ReconnectEvent::ConnectionLost {
+                reason: NetcodeDisconnectReason::ConnectionTimedOut,
+                retry_in: Duration::from_millis(100)
+            },
+            ReconnectEvent::Attempt {
+                attempt: 1,
+                server_addr: addr_a
+            },
+            ReconnectEvent::GaveUp {
+                reason: NetcodeDisconnectReason::ConnectionRequestTimedOut
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_netcode_client_secure_reconnect_without_token() {
+    use renet::transport::{
+        ClientAuthentication, ConnectToken, NetcodeClientTransport, NetcodeDisconnectReason, ReconnectEvent, ReconnectPolicy,
+    };
+    use std::net::UdpSocket;
+
+    init_log();
+    let private_key = b"an example very very secret key.";
+    // Nobody answers on the server address
+    let server_addr = UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let connect_token = ConnectToken::generate(Duration::ZERO, 0, 300, 5, 1, vec![server_addr], None, private_key).unwrap();
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut client_transport = NetcodeClientTransport::new(Duration::ZERO, ClientAuthentication::Secure { connect_token }, socket).unwrap();
+    let mut client = RenetClient::new(ConnectionConfig::default());
+    client_transport.set_reconnect_policy(ReconnectPolicy::default());
+
+    // The token can't be used again, so the transport gives up instead of reconnecting with it
+    client_transport.update(Duration::from_secs(2), &mut client).unwrap();
+    assert!(client_transport.update(Duration::ZERO, &mut client).is_err());
+    assert!(client_transport.is_disconnected());
+    assert_eq!(
+        client_transport.get_event(),
+        Some(ReconnectEvent::GaveUp {
+            reason: NetcodeDisconnectReason::ConnectionRequestTimedOut
+        })
+    );
+    assert!(client_transport.get_event().is_none());
+}
+
+#[test]
+fn test_netcode_server_multiple_sockets() {
+    use renet::transport::{ClientAuthentication, NetcodeClientTransport, NetcodeServerTransport, ServerAuthentication, ServerConfig};
+    use std::net::UdpSocket;
+
+    init_log();
+    let sockets = vec![UdpSocket::bind("127.0.0.1:0").unwrap(), UdpSocket::bind("127.0.0.1:0").unwrap()];
+    let public_addresses: Vec<_> = sockets.iter().map(|socket| socket.local_addr().unwrap()).collect();
+    let server_config = || ServerConfig::new(Duration::ZERO, 2, 0, public_addresses.clone(), ServerAuthentication::Unsecure);
+    assert!(NetcodeServerTransport::<UdpSocket>::new_with_sockets(server_config(), vec![]).is_err());
+    let mut transport = NetcodeServerTransport::new_with_sockets(server_config(), sockets).unwrap();
+    assert_eq!(transport.local_addresses(), public_addresses);
+    let mut server = RenetServer::new(ConnectionConfig::default());
+
+    // Clients discard packets from other addresses, so each one only connects if the replies come from its server address
+    let mut clients: Vec<_> = public_addresses
+        .iter()
+        .enumerate()
+        .map(|(i, server_addr)| {
+            let authentication = ClientAuthentication::Unsecure {
+                protocol_id: 0,
+                client_id: i as u64,
+                server_addr: *server_addr,
+                user_data: None,
+            };
+            let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let client_transport = NetcodeClientTransport::new(Duration::ZERO, authentication, socket).unwrap();
+            (client_transport, RenetClient::new(ConnectionConfig::default()))
+        })
+        .collect();
+
+    let delta = Duration::from_millis(10);
+    for _ in 0..100 {
+        for (client_transport, client) in clients.iter_mut() {
+            client_transport.update(delta, client).unwrap();
+            client_transport.send_packets(client).unwrap();
+        }
+        transport.update(delta, &mut server).unwrap();
+        transport.send_packets(&mut server);
+        if clients.iter().all(|(_, client)| client.is_connected()) {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    assert!(clients.iter().all(|(_, client)| client.is_connected()));
+    assert_eq!(transport.connected_clients(), 2);
+
+    server.broadcast_message(DefaultChannel::ReliableOrdered, Bytes::from_static(b"hello"));
+    transport.send_packets(&mut server);
+    let mut received = [false; 2];
+    for _ in 0..100 {
+        std::thread::sleep(Duration::from_millis(1));
+        for (i, (client_transport, client)) in clients.iter_mut().enumerate() {
+            client_transport.update(delta, client).unwrap();
+            if let Some(message) = client.receive_message(DefaultChannel::ReliableOrdered) {
+                assert_eq!(message, "hello");
+                received[i] = true;
+            }
+        }
+        if received.iter().all(|r| *r) {
+            break;
+        }
+    }
+    assert_eq!(received, [true; 2]);
+}
+
+#[test]
+fn test_netcode_memory_socket() {
+    use renet::transport::{
+        ClientAuthentication, NetcodeClientTransport, NetcodeServerTransport, ServerAuthentication, ServerConfig, TransportSocket,
+    };
+    use std::{
+        collections::{HashMap, VecDeque},
+        io,
+        net::SocketAddr,
+        sync::{Arc, Mutex},
+    };
+
+    // Packets queued by destination address
+    type Network = Arc<Mutex<HashMap<SocketAddr, VecDeque<(SocketAddr, Vec<u8>)>>>>;
+
+    #[derive(Debug)]
+    struct MemorySocket {
+        addr: SocketAddr,
+        network: Network,
+    }
+
+    impl MemorySocket {
+        fn new(addr: &str, network: &Network) -> Self {
+            let addr = addr.parse().unwrap();
+            network.lock().unwrap().insert(addr, VecDeque::new());
+            Self {
+                addr,
+                network: network.clone(),
+            }
+        }
+    }
+
+    impl TransportSocket for MemorySocket {
+        fn local_addr(&self) -> io::Result<SocketAddr> {
+            Ok(self.addr)
+        }
+
+        fn send_to(&self, packet: &[u8], addr: SocketAddr) -> io::Result<usize> {
+            if let Some(queue) = self.network.lock().unwrap().get_mut(&addr) {
+                queue.push_back((self.addr, packet.to_vec()));
+            }
+            Ok(packet.len())
+        }
+
+        fn recv_from(&self, buffer: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+            match self.network.lock().unwrap().get_mut(&self.addr).unwrap().pop_front() {
+                Some((addr, packet)) => {
+                    buffer[..packet.len()].copy_from_slice(&packet);
+                    Ok((packet.len(), addr))
+                }
+                None => Err(io::ErrorKind::WouldBlock.into()),
+            }
+        }
+    }
+
+    init_log();
+    let network = Network::default();
+    let server_socket = MemorySocket::new("10.0.0.1:5000", &network);
+    let server_addr = server_socket.addr;
+    let server_config = ServerConfig::new(Duration::ZERO, 1, 0, vec![server_addr], ServerAuthentication::Unsecure);
+    let mut server_transport = NetcodeServerTransport::new(server_config, server_socket).unwrap();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+
+    let client_id = ClientId::from_raw(3);
+    let authentication = ClientAuthentication::Unsecure {
+        protocol_id: 0,
+        client_id: client_id.raw(),
+        server_addr,
+        user_data: None,
+    };
+    let client_socket = MemorySocket::new("10.0.0.2:6000", &network);
+    let mut client_transport = NetcodeClientTransport::new(Duration::ZERO, authentication, client_socket).unwrap();
+    assert_eq!(client_transport.addr().unwrap(), "10.0.0.2:6000".parse().unwrap());
+    let mut client = RenetClient::new(ConnectionConfig::default());
+
+    // The packets are delivered instantly, so a few ticks are enough to connect
+    let delta = Duration::from_millis(10);
+    for _ in 0..10 {
+        client_transport.update(delta, &mut client).unwrap();
+        client_transport.send_packets(&mut client).unwrap();
+        server_transport.update(delta, &mut server).unwrap();
+        server_transport.send_packets(&mut server);
+    }
+    assert!(client.is_connected());
+    assert!(server.is_connected(client_id));
+    assert_eq!(server_transport.client_addr(client_id), Some("10.0.0.2:6000".parse().unwrap()));
+
+    client.send_message(DefaultChannel::ReliableOrdered, Bytes::from_static(b"over memory"));
+    client_transport.send_packets(&mut client).unwrap();
+    server_transport.update(delta, &mut server).unwrap();
+    assert_eq!(
+        server.receive_message(client_id, DefaultChannel::ReliableOrdered).unwrap(),
+        "over memory"
+    );
+}
+
+#[test]
+fn test_transport_traits() {
+    use renet::transport::{ClientAuthentication, NetcodeClientTransport, NetcodeServerTransport, ServerAuthentication, ServerConfig};
+    use renet::{ClientTransport, ServerTransport};
+    use std::{fmt::Debug, net::UdpSocket};
+
+    // Game code written once, without knowing the transport
+    fn tick<S: ServerTransport, C: ClientTransport>(
+        server_transport: &mut S,
+        server: &mut RenetServer,
+        client_transport: &mut C,
+        client: &mut RenetClient,
+        delta: Duration,
+    ) where
+        S::Error: Debug,
+        C::Error: Debug,
+    {
+        client.update(delta);
+        client_transport.update(delta, client).unwrap();
+        client_transport.send_packets(client).unwrap();
+        server.update(delta);
+        server_transport.update(delta, server).unwrap();
+        server_transport.send_packets(server);
+    }
+
+    init_log();
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let server_addr = socket.local_addr().unwrap();
+    let server_config = ServerConfig::new(Duration::ZERO, 2, 0, vec![server_addr], ServerAuthentication::Unsecure);
+    let mut server_transport = NetcodeServerTransport::new(server_config, socket).unwrap();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    assert_eq!(ServerTransport::max_clients(&server_transport), 2);
+
+    let client_id = ClientId::from_raw(9);
+    let authentication = ClientAuthentication::Unsecure {
+        protocol_id: 0,
+        client_id: client_id.raw(),
+        server_addr,
+        user_data: None,
+    };
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut client_transport = NetcodeClientTransport::new(Duration::ZERO, authentication, socket).unwrap();
+    let mut client = RenetClient::new(ConnectionConfig::default());
+    assert!(ClientTransport::is_connecting(&client_transport));
+
+    let delta = Duration::from_millis(10);
+    for _ in 0..200 {
+        tick(&mut server_transport, &mut server, &mut client_transport, &mut client, delta);
+        if client.is_connected() && server.is_connected(client_id) {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    assert!(ClientTransport::is_connected(&client_transport));
+    assert!(server.is_connected(client_id));
+
+    ServerTransport::disconnect_all(&mut server_transport, &mut server);
+    assert!(!server.is_connected(client_id));
+    for _ in 0..200 {
+        client_transport.update(delta, &mut client).unwrap_or_default();
+        if ClientTransport::is_disconnected(&client_transport) {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    assert!(ClientTransport::is_disconnected(&client_transport));
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn test_tokio_transports() {
+    use renet::transport::{
+        ClientAuthentication, ClientConfig, NetcodeTransportError, ServerAuthentication, ServerConfig, TokioClient, TokioClientEvent,
+        TokioServer, TokioServerEvent,
+    };
+    use tokio::{net::UdpSocket, time::timeout};
+
+    init_log();
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let server_addr = socket.local_addr().unwrap();
+    let server_config = ServerConfig::new(Duration::ZERO, 1, 0, vec![server_addr], ServerAuthentication::Unsecure);
+    let tick = Duration::from_millis(10);
+    let (server, mut server_events) = TokioServer::spawn(server_config, ConnectionConfig::default(), socket, tick).unwrap();
+
+    let client_id = ClientId::from_raw(9);
+    let authentication = ClientAuthentication::Unsecure {
+        protocol_id: 0,
+        client_id: client_id.raw(),
+        server_addr,
+        user_data: None,
+    };
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let (client, mut client_events) = TokioClient::spawn(
+        Duration::ZERO,
+        authentication,
+        ClientConfig::default(),
+        ConnectionConfig::default(),
+        socket,
+        tick,
+    )
+    .unwrap();
+
+    let wait = Duration::from_secs(5);
+    assert!(matches!(
+        timeout(wait, client_events.recv()).await.unwrap(),
+        Some(TokioClientEvent::Connected)
+    ));
+    assert!(matches!(
+        timeout(wait, server_events.recv()).await.unwrap(),
+        Some(TokioServerEvent::ClientConnected { client_id: id, addr: Some(_) }) if id == client_id
+    ));
+
+    client.send_message(DefaultChannel::ReliableOrdered, "from client");
+    match timeout(wait, server_events.recv()).await.unwrap() {
+        Some(TokioServerEvent::Message {
+            client_id: id,
+            channel_id,
+            message,
+        }) => {
+            assert_eq!(id, client_id);
+            assert_eq!(channel_id, u8::from(DefaultChannel::ReliableOrdered));
+            assert_eq!(message, "from client");
+        }
+        event => panic!("unexpected event {event:?}"),
+    }
+
+    server.send_message(client_id, DefaultChannel::ReliableOrdered, "from server");
+    match timeout(wait, client_events.recv()).await.unwrap() {
+        Some(TokioClientEvent::Message { message, .. }) => assert_eq!(message, "from server"),
+        event => panic!("unexpected event {event:?}"),
+    }
+
+    client.disconnect();
+    assert!(matches!(
+        timeout(wait, client_events.recv()).await.unwrap(),
+        Some(TokioClientEvent::Disconnected(NetcodeTransportError::Renet(
+            DisconnectReason::DisconnectedByClient
+        )))
+    ));
+    client.join().await;
+    assert!(matches!(
+        timeout(wait, server_events.recv()).await.unwrap(),
+        Some(TokioServerEvent::ClientDisconnected { client_id: id, .. }) if id == client_id
+    ));
+
+    server.shutdown().await.unwrap();
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn test_tokio_transports_reject_keyed_and_stream_channels() {
+    use renet::transport::{ServerAuthentication, ServerConfig, TokioServer};
+    use tokio::net::UdpSocket;
+
+    for send_type in [
+        SendType::ReliableKeyed {
+            resend_time: Duration::from_millis(300),
+        },
+        SendType::ReliableStream {
+            resend_time: Duration::from_millis(300),
+        },
+    ] {
+        let mut connection_config = ConnectionConfig::default();
+        connection_config.client_channels_config.push(ChannelConfig {
+            channel_id: 3,
+            max_memory_usage_bytes: 5 * 1024 * 1024,
+            send_type,
+        });
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server_config = ServerConfig::new(
+            Duration::ZERO,
+            1,
+            0,
+            vec![socket.local_addr().unwrap()],
+            ServerAuthentication::Unsecure,
+        );
+        let error = TokioServer::spawn(server_config, connection_config, socket, Duration::from_millis(10)).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    }
+}
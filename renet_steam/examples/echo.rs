@@ -92,7 +92,7 @@ fn run_server(steam_client: Client<ClientManager>, single: SingleClient, with_lo
 
         while let Some(event) = server.get_event() {
             match event {
-                ServerEvent::ClientConnected { client_id } => {
+                ServerEvent::ClientConnected { client_id, .. } => {
                     println!("Client {} connected.", client_id)
                 }
                 ServerEvent::ClientDisconnected { client_id, reason } => {
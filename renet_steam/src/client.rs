@@ -1,5 +1,6 @@
 use super::MAX_MESSAGE_BATCH_SIZE;
-use renet::RenetClient;
+use renet::{ClientTransport, RenetClient};
+use std::time::Duration;
 use steamworks::{
     networking_sockets::{InvalidHandle, NetConnection, NetworkingSockets},
     networking_types::{NetConnectionEnd, NetworkingConnectionState, NetworkingIdentity, SendFlags},
@@ -121,6 +122,10 @@ impl SteamClientTransport {
             return;
         };
 
+        if self.is_connected() {
+            client.set_connected();
+        }
+
         let ConnectionState::Connected { connection } = &mut self.state else {
             unreachable!()
         };
@@ -151,3 +156,33 @@ impl SteamClientTransport {
         connection.flush_messages()
     }
 }
+
+impl ClientTransport for SteamClientTransport {
+    type Error = SteamError;
+
+    /// Steam tracks time itself, the duration is ignored.
+    fn update(&mut self, _duration: Duration, client: &mut RenetClient) -> Result<(), Self::Error> {
+        SteamClientTransport::update(self, client);
+        Ok(())
+    }
+
+    fn send_packets(&mut self, client: &mut RenetClient) -> Result<(), Self::Error> {
+        SteamClientTransport::send_packets(self, client)
+    }
+
+    fn is_connecting(&self) -> bool {
+        SteamClientTransport::is_connecting(self)
+    }
+
+    fn is_connected(&self) -> bool {
+        SteamClientTransport::is_connected(self)
+    }
+
+    fn is_disconnected(&self) -> bool {
+        SteamClientTransport::is_disconnected(self)
+    }
+
+    fn disconnect(&mut self) {
+        SteamClientTransport::disconnect(self)
+    }
+}
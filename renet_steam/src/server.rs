@@ -1,6 +1,10 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::Infallible,
+    time::Duration,
+};
 
-use renet::{ClientId, RenetServer};
+use renet::{ClientId, ConnectionInfo, RenetServer, ServerTransport, TransportKind};
 use steamworks::{
     networking_sockets::{InvalidHandle, ListenSocket, NetConnection},
     networking_types::{ListenSocketEvent, NetConnectionEnd, NetworkingConfigEntry, SendFlags},
@@ -92,7 +96,7 @@ impl<T: Manager + 'static> SteamServerTransport<T> {
                 ListenSocketEvent::Connected(event) => {
                     if let Some(steam_id) = event.remote().steam_id() {
                         let client_id = ClientId::from_raw(steam_id.raw());
-                        server.add_connection(client_id);
+                        server.add_connection_with_info(client_id, ConnectionInfo::new(TransportKind::Steam));
                         self.connections.insert(client_id, event.take_connection());
                     }
                 }
@@ -172,3 +176,26 @@ impl<T: Manager + 'static> SteamServerTransport<T> {
         }
     }
 }
+
+impl<T: Manager + 'static> ServerTransport for SteamServerTransport<T> {
+    type Error = Infallible;
+
+    /// Steam tracks time itself, the duration is ignored.
+    fn update(&mut self, _duration: Duration, server: &mut RenetServer) -> Result<(), Self::Error> {
+        SteamServerTransport::update(self, server);
+        Ok(())
+    }
+
+    fn send_packets(&mut self, server: &mut RenetServer) {
+        SteamServerTransport::send_packets(self, server)
+    }
+
+    /// Disconnects all clients without flushing the last packets.
+    fn disconnect_all(&mut self, server: &mut RenetServer) {
+        SteamServerTransport::disconnect_all(self, server, false)
+    }
+
+    fn max_clients(&self) -> usize {
+        SteamServerTransport::max_clients(self)
+    }
+}
@@ -0,0 +1,124 @@
+use std::{collections::HashMap, error::Error, fmt, net::SocketAddr};
+
+use renetcode::{generate_random_bytes, NETCODE_USER_DATA_BYTES};
+
+/// A request for a connect token, as received by the [TokenService][crate::TokenService].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenRequest {
+    /// Address of the caller, when known.
+    pub remote_addr: Option<SocketAddr>,
+    /// Value of the bearer token in the `Authorization` header.
+    pub api_key: Option<String>,
+    /// Body of the request, can be used by custom authenticators (session tickets, platform tokens, ...).
+    pub body: Vec<u8>,
+}
+
+/// The client id and user data that will be written in the issued connect token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientGrant {
+    pub client_id: u64,
+    pub user_data: Option<[u8; NETCODE_USER_DATA_BYTES]>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthenticationError {
+    /// The request has no credentials.
+    MissingCredentials,
+    /// The credentials in the request are not accepted.
+    InvalidCredentials,
+}
+
+impl Error for AuthenticationError {}
+
+impl fmt::Display for AuthenticationError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use AuthenticationError::*;
+
+        match *self {
+            MissingCredentials => write!(fmt, "missing credentials"),
+            InvalidCredentials => write!(fmt, "invalid credentials"),
+        }
+    }
+}
+
+/// Validates the caller of a token request and decides the client id and user data of its token.
+/// Implemented for closures with the same signature as [Authenticator::authenticate].
+pub trait Authenticator: Send + Sync + 'static {
+    fn authenticate(&self, request: &TokenRequest) -> Result<ClientGrant, AuthenticationError>;
+}
+
+impl<F> Authenticator for F
+where
+    F: Fn(&TokenRequest) -> Result<ClientGrant, AuthenticationError> + Send + Sync + 'static,
+{
+    fn authenticate(&self, request: &TokenRequest) -> Result<ClientGrant, AuthenticationError> {
+        self(request)
+    }
+}
+
+/// Accepts requests with one of the configured api keys as bearer token.
+/// Each token is issued with a random client id and the user data configured for the api key.
+#[derive(Debug, Default, Clone)]
+pub struct StaticApiKeys {
+    keys: HashMap<String, Option<[u8; NETCODE_USER_DATA_BYTES]>>,
+}
+
+impl StaticApiKeys {
+    /// Adds an api key, the tokens issued for it will contain the given user data.
+    pub fn insert(&mut self, api_key: impl Into<String>, user_data: Option<[u8; NETCODE_USER_DATA_BYTES]>) {
+        self.keys.insert(api_key.into(), user_data);
+    }
+
+    /// Removes an api key, returns false if the key was not present.
+    pub fn remove(&mut self, api_key: &str) -> bool {
+        self.keys.remove(api_key).is_some()
+    }
+}
+
+impl Authenticator for StaticApiKeys {
+    fn authenticate(&self, request: &TokenRequest) -> Result<ClientGrant, AuthenticationError> {
+        let api_key = request.api_key.as_deref().ok_or(AuthenticationError::MissingCredentials)?;
+        let user_data = self.keys.get(api_key).ok_or(AuthenticationError::InvalidCredentials)?;
+
+        Ok(ClientGrant {
+            client_id: u64::from_le_bytes(generate_random_bytes()),
+            user_data: *user_data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(api_key: Option<&str>) -> TokenRequest {
+        TokenRequest {
+            remote_addr: None,
+            api_key: api_key.map(String::from),
+            body: vec![],
+        }
+    }
+
+    #[test]
+    fn static_api_keys() {
+        let user_data = [7u8; NETCODE_USER_DATA_BYTES];
+        let mut api_keys = StaticApiKeys::default();
+        api_keys.insert("first", None);
+        api_keys.insert("second", Some(user_data));
+
+        assert_eq!(api_keys.authenticate(&request(None)), Err(AuthenticationError::MissingCredentials));
+        assert_eq!(
+            api_keys.authenticate(&request(Some("third"))),
+            Err(AuthenticationError::InvalidCredentials)
+        );
+        assert_eq!(api_keys.authenticate(&request(Some("first"))).unwrap().user_data, None);
+        assert_eq!(api_keys.authenticate(&request(Some("second"))).unwrap().user_data, Some(user_data));
+
+        assert!(api_keys.remove("first"));
+        assert!(!api_keys.remove("first"));
+        assert_eq!(
+            api_keys.authenticate(&request(Some("first"))),
+            Err(AuthenticationError::InvalidCredentials)
+        );
+    }
+}
@@ -0,0 +1,35 @@
+//! Small HTTP service issuing netcode connect tokens, so the backend does not need to wrap
+//! [ConnectToken::generate_with_cipher_suite][renetcode::ConnectToken::generate_with_cipher_suite] itself.
+//!
+//! Clients request a token with `POST /token`, the caller is validated by an [Authenticator]
+//! that also chooses the client id and the user data of the token. When accepted, the response body
+//! is the token encoded with [ConnectToken::to_base64][renetcode::ConnectToken::to_base64].
+//!
+//! The service speaks plain HTTP, the api keys and the connect tokens are sent in clear text.
+//! Run it behind a TLS terminating proxy in production.
+//!
+//! ```no_run
+//! # use renet_token_service::{StaticApiKeys, TokenService, TokenServiceConfig};
+//! # use renetcode::CipherSuite;
+//! let config = TokenServiceConfig {
+//!     protocol_id: 7,
+//!     private_key: *b"an example very very secret key.",
+//!     key_id: 0,
+//!     server_addresses: vec!["127.0.0.1:5000".parse().unwrap()],
+//!     expire_seconds: 300,
+//!     timeout_seconds: 15,
+//!     cipher_suite: CipherSuite::ChaCha20Poly1305,
+//! };
+//! let mut api_keys = StaticApiKeys::default();
+//! api_keys.insert("my secret api key", None);
+//!
+//! let handle = TokenService::new(config, api_keys).listen("127.0.0.1:8080").unwrap();
+//! handle.join();
+//! ```
+mod auth;
+mod service;
+
+pub use auth::{AuthenticationError, Authenticator, ClientGrant, StaticApiKeys, TokenRequest};
+pub use service::{
+    TokenService, TokenServiceConfig, TokenServiceError, TokenServiceHandle, DEFAULT_WORKER_THREADS, MAX_REQUEST_BODY_BYTES,
+};
@@ -0,0 +1,85 @@
+//! Token service configured with environment variables:
+//! - `RENET_TOKEN_LISTEN`: address to listen on, default `127.0.0.1:8080`
+//! - `RENET_PROTOCOL_ID`: protocol id of the game servers
+//! - `RENET_PRIVATE_KEY`: the 32 bytes private key of the game servers, encoded as URL-safe base64
+//! - `RENET_KEY_ID`: key id of the private key, default `0`
+//! - `RENET_SERVER_ADDRESSES`: comma separated game server addresses
+//! - `RENET_API_KEYS`: comma separated api keys accepted as bearer token
+//! - `RENET_EXPIRE_SECONDS`: seconds until the tokens expire, default `300`
+//! - `RENET_TIMEOUT_SECONDS`: connection timeout of the tokens, default `15`
+//! - `RENET_CIPHER_SUITE`: `chacha20poly1305` or `aes256gcm`, must match the game servers, default `chacha20poly1305`
+use std::{env, net::SocketAddr, process::exit, str::FromStr};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use renet_token_service::{StaticApiKeys, TokenService, TokenServiceConfig};
+use renetcode::{CipherSuite, NETCODE_KEY_BYTES};
+
+fn main() {
+    let config = match read_config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Invalid configuration: {e}");
+            exit(1);
+        }
+    };
+
+    let mut api_keys = StaticApiKeys::default();
+    for api_key in env_var("RENET_API_KEYS").unwrap_or_default().split(',').filter(|k| !k.is_empty()) {
+        api_keys.insert(api_key, None);
+    }
+
+    let listen = env_var("RENET_TOKEN_LISTEN").unwrap_or_else(|| "127.0.0.1:8080".to_string());
+    match TokenService::new(config, api_keys).listen(listen.as_str()) {
+        Ok(handle) => {
+            println!("Issuing connect tokens on http://{}/token", handle.local_addr());
+            handle.join();
+        }
+        Err(e) => {
+            eprintln!("Failed to listen on {listen}: {e}");
+            exit(1);
+        }
+    }
+}
+
+fn env_var(name: &str) -> Option<String> {
+    env::var(name).ok()
+}
+
+fn parse_var<T: FromStr>(name: &str, default: Option<T>) -> Result<T, String> {
+    match env_var(name) {
+        Some(value) => value.parse().map_err(|_| format!("{name} has an invalid value")),
+        None => default.ok_or_else(|| format!("{name} is required")),
+    }
+}
+
+fn read_config() -> Result<TokenServiceConfig, String> {
+    let private_key = env_var("RENET_PRIVATE_KEY").ok_or("RENET_PRIVATE_KEY is required")?;
+    let private_key: [u8; NETCODE_KEY_BYTES] = URL_SAFE_NO_PAD
+        .decode(private_key.trim_end_matches('='))
+        .ok()
+        .and_then(|key| key.try_into().ok())
+        .ok_or("RENET_PRIVATE_KEY must be 32 bytes encoded as URL-safe base64")?;
+
+    let server_addresses = env_var("RENET_SERVER_ADDRESSES")
+        .ok_or("RENET_SERVER_ADDRESSES is required")?
+        .split(',')
+        .map(|addr| addr.trim().parse::<SocketAddr>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("RENET_SERVER_ADDRESSES has an invalid address: {e}"))?;
+
+    let cipher_suite = match env_var("RENET_CIPHER_SUITE").as_deref() {
+        None | Some("chacha20poly1305") => CipherSuite::ChaCha20Poly1305,
+        Some("aes256gcm") => CipherSuite::Aes256Gcm,
+        Some(_) => return Err("RENET_CIPHER_SUITE must be chacha20poly1305 or aes256gcm".to_string()),
+    };
+
+    Ok(TokenServiceConfig {
+        protocol_id: parse_var("RENET_PROTOCOL_ID", None)?,
+        private_key,
+        key_id: parse_var("RENET_KEY_ID", Some(0))?,
+        server_addresses,
+        expire_seconds: parse_var("RENET_EXPIRE_SECONDS", Some(300))?,
+        timeout_seconds: parse_var("RENET_TIMEOUT_SECONDS", Some(15))?,
+        cipher_suite,
+    })
+}
@@ -0,0 +1,297 @@
+use std::{
+    error::Error,
+    fmt,
+    io::{self, Read},
+    net::{SocketAddr, ToSocketAddrs},
+    sync::Arc,
+    thread::JoinHandle,
+    time::{Duration, SystemTime},
+};
+
+use log::{error, warn};
+use renetcode::{CipherSuite, ConnectToken, TokenGenerationError, NETCODE_KEY_BYTES};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::auth::{AuthenticationError, Authenticator, TokenRequest};
+
+/// The maximum number of bytes accepted in the body of a token request.
+pub const MAX_REQUEST_BODY_BYTES: usize = 4096;
+
+/// The number of threads handling requests started by [TokenService::listen].
+pub const DEFAULT_WORKER_THREADS: usize = 4;
+
+/// Configuration of the issued connect tokens, the protocol id and private key must match the game servers.
+#[derive(Debug, Clone)]
+pub struct TokenServiceConfig {
+    pub protocol_id: u64,
+    pub private_key: [u8; NETCODE_KEY_BYTES],
+    /// Key id stamped in the tokens, see [ConnectToken::generate_with_key_id].
+    pub key_id: u32,
+    /// Game server addresses written in the tokens, the client tries them in order.
+    pub server_addresses: Vec<SocketAddr>,
+    /// Seconds until the tokens expire.
+    pub expire_seconds: u64,
+    /// Connection timeout written in the tokens, negative values disable the timeout.
+    pub timeout_seconds: i32,
+    /// Cipher suite of the connections, must match the game servers.
+    pub cipher_suite: CipherSuite,
+}
+
+#[derive(Debug)]
+pub enum TokenServiceError {
+    Authentication(AuthenticationError),
+    TokenGeneration(TokenGenerationError),
+}
+
+impl Error for TokenServiceError {}
+
+impl fmt::Display for TokenServiceError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TokenServiceError::Authentication(ref err) => write!(fmt, "authentication failed: {err}"),
+            TokenServiceError::TokenGeneration(ref err) => write!(fmt, "failed to generate token: {err}"),
+        }
+    }
+}
+
+impl From<AuthenticationError> for TokenServiceError {
+    fn from(inner: AuthenticationError) -> Self {
+        TokenServiceError::Authentication(inner)
+    }
+}
+
+impl From<TokenGenerationError> for TokenServiceError {
+    fn from(inner: TokenGenerationError) -> Self {
+        TokenServiceError::TokenGeneration(inner)
+    }
+}
+
+/// Issues connect tokens for the callers accepted by the authenticator.
+pub struct TokenService<A: Authenticator> {
+    config: TokenServiceConfig,
+    authenticator: A,
+}
+
+impl<A: Authenticator> TokenService<A> {
+    pub fn new(config: TokenServiceConfig, authenticator: A) -> Self {
+        Self { config, authenticator }
+    }
+
+    pub fn config(&self) -> &TokenServiceConfig {
+        &self.config
+    }
+
+    /// Authenticates the request and generates its connect token, the current time is the duration since the UNIX epoch.
+    pub fn issue(&self, request: &TokenRequest, current_time: Duration) -> Result<ConnectToken, TokenServiceError> {
+        let grant = self.authenticator.authenticate(request)?;
+        let token = ConnectToken::generate_with_cipher_suite(
+            current_time,
+            self.config.protocol_id,
+            self.config.expire_seconds,
+            grant.client_id,
+            self.config.timeout_seconds,
+            self.config.server_addresses.clone(),
+            grant.user_data.as_ref(),
+            self.config.key_id,
+            self.config.cipher_suite,
+            &self.config.private_key,
+        )?;
+
+        Ok(token)
+    }
+
+    /// Starts serving `POST /token` requests on the given address in background threads,
+    /// with [DEFAULT_WORKER_THREADS] threads handling the requests.
+    pub fn listen(self, addr: impl ToSocketAddrs) -> io::Result<TokenServiceHandle> {
+        self.listen_with_workers(addr, DEFAULT_WORKER_THREADS)
+    }
+
+    /// Starts serving `POST /token` requests on the given address, with the given number of threads handling the requests.
+    /// The request body is read by the worker thread, so a client sending its body slowly only blocks one worker.
+    pub fn listen_with_workers(self, addr: impl ToSocketAddrs, workers: usize) -> io::Result<TokenServiceHandle> {
+        let server = Server::http(addr).map_err(io::Error::other)?;
+        let server = Arc::new(server);
+        let local_addr = server
+            .server_addr()
+            .to_ip()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "token service must listen on an ip address"))?;
+
+        let service = Arc::new(self);
+        let threads = (0..workers.max(1))
+            .map(|_| {
+                let server = server.clone();
+                let service = service.clone();
+                std::thread::spawn(move || {
+                    for request in server.incoming_requests() {
+                        service.handle_request(request);
+                    }
+                })
+            })
+            .collect();
+
+        Ok(TokenServiceHandle {
+            server,
+            local_addr,
+            threads,
+        })
+    }
+
+    fn handle_request(&self, mut request: Request) {
+        let response = if request.url() != "/token" {
+            Response::from_string("not found").with_status_code(404)
+        } else if *request.method() != Method::Post {
+            Response::from_string("method not allowed")
+                .with_status_code(405)
+                .with_header(header("Allow", "POST"))
+        } else {
+            match read_token_request(&mut request) {
+                Err(e) => {
+                    warn!("Failed to read token request: {e}");
+                    Response::from_string(e.to_string()).with_status_code(400)
+                }
+                Ok(token_request) => {
+                    let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
+                    match self.issue(&token_request, current_time) {
+                        Ok(token) => Response::from_string(token.to_base64()).with_header(header("Content-Type", "text/plain")),
+                        Err(TokenServiceError::Authentication(e)) => Response::from_string(e.to_string())
+                            .with_status_code(401)
+                            .with_header(header("WWW-Authenticate", "Bearer")),
+                        Err(e) => {
+                            error!("Failed to issue connect token: {e}");
+                            Response::from_string("internal error").with_status_code(500)
+                        }
+                    }
+                }
+            }
+        };
+
+        if let Err(e) = request.respond(response) {
+            warn!("Failed to respond token request: {e}");
+        }
+    }
+}
+
+/// Handle of a running token service, see [TokenService::listen].
+pub struct TokenServiceHandle {
+    server: Arc<Server>,
+    local_addr: SocketAddr,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl TokenServiceHandle {
+    /// Returns the address the service is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stops accepting requests and waits for the service threads to finish.
+    pub fn shutdown(self) {
+        // Each unblock only wakes one of the waiting threads
+        for _ in &self.threads {
+            self.server.unblock();
+        }
+        self.join();
+    }
+
+    /// Blocks until the service threads finish.
+    pub fn join(self) {
+        for thread in self.threads {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn header(field: &str, value: &str) -> Header {
+    Header::from_bytes(field.as_bytes(), value.as_bytes()).expect("static header should be valid")
+}
+
+fn read_token_request(request: &mut Request) -> io::Result<TokenRequest> {
+    let api_key = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Authorization"))
+        .and_then(|h| h.value.as_str().strip_prefix("Bearer "))
+        .map(|key| key.trim().to_string());
+
+    let mut body = Vec::new();
+    request.as_reader().take(MAX_REQUEST_BODY_BYTES as u64 + 1).read_to_end(&mut body)?;
+    if body.len() > MAX_REQUEST_BODY_BYTES {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "request body too large"));
+    }
+
+    Ok(TokenRequest {
+        remote_addr: request.remote_addr().copied(),
+        api_key,
+        body,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ClientGrant, StaticApiKeys};
+
+    fn config() -> TokenServiceConfig {
+        TokenServiceConfig {
+            protocol_id: 7,
+            private_key: *b"an example very very secret key.",
+            key_id: 3,
+            server_addresses: vec!["127.0.0.1:5000".parse().unwrap()],
+            expire_seconds: 30,
+            timeout_seconds: 5,
+            cipher_suite: CipherSuite::Aes256Gcm,
+        }
+    }
+
+    #[test]
+    fn issue_token() {
+        let authenticator = |request: &TokenRequest| match request.body.as_slice() {
+            b"player 9" => Ok(ClientGrant {
+                client_id: 9,
+                user_data: None,
+            }),
+            _ => Err(AuthenticationError::InvalidCredentials),
+        };
+        let service = TokenService::new(config(), authenticator);
+        let mut request = TokenRequest {
+            remote_addr: None,
+            api_key: None,
+            body: b"player 9".to_vec(),
+        };
+
+        let token = service.issue(&request, Duration::from_secs(100)).unwrap();
+        assert_eq!(token.client_id(), 9);
+        assert_eq!(token.protocol_id(), 7);
+        assert_eq!(token.key_id(), 3);
+        assert_eq!(token.create_timestamp(), 100);
+        assert_eq!(token.expire_timestamp(), 130);
+        assert_eq!(token.timeout_seconds(), 5);
+        assert_eq!(token.cipher_suite(), CipherSuite::Aes256Gcm);
+        assert_eq!(token.server_addresses(), config().server_addresses);
+
+        request.body = b"player 10".to_vec();
+        assert!(matches!(
+            service.issue(&request, Duration::ZERO),
+            Err(TokenServiceError::Authentication(AuthenticationError::InvalidCredentials))
+        ));
+    }
+
+    #[test]
+    fn no_server_addresses() {
+        let mut config = config();
+        config.server_addresses.clear();
+        let mut api_keys = StaticApiKeys::default();
+        api_keys.insert("key", None);
+        let service = TokenService::new(config, api_keys);
+        let request = TokenRequest {
+            remote_addr: None,
+            api_key: Some("key".to_string()),
+            body: vec![],
+        };
+
+        assert!(matches!(
+            service.issue(&request, Duration::ZERO),
+            Err(TokenServiceError::TokenGeneration(TokenGenerationError::NoServerAddressAvailable))
+        ));
+    }
+}
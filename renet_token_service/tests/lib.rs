@@ -0,0 +1,144 @@
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream, UdpSocket},
+    time::{Duration, SystemTime},
+};
+
+use renet::{
+    transport::{
+        CipherSuite, ClientAuthentication, ConnectToken, NetcodeClientTransport, NetcodeServerTransport, ServerAuthentication,
+        ServerConfig, NETCODE_USER_DATA_BYTES,
+    },
+    ClientId, ConnectionConfig, RenetClient, RenetServer,
+};
+use renet_token_service::{StaticApiKeys, TokenService, TokenServiceConfig};
+
+const PROTOCOL_ID: u64 = 7;
+const PRIVATE_KEY: &[u8; 32] = b"an example very very secret key.";
+
+pub fn init_log() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
+
+fn http_request(addr: SocketAddr, request: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    let status = response.split(' ').nth(1).unwrap().parse().unwrap();
+    let body = response.split("\r\n\r\n").nth(1).unwrap_or_default().to_string();
+    (status, body)
+}
+
+fn token_request(api_key: Option<&str>) -> String {
+    let authorization = api_key.map(|key| format!("Authorization: Bearer {key}\r\n")).unwrap_or_default();
+    format!("POST /token HTTP/1.1\r\nHost: localhost\r\n{authorization}Content-Length: 0\r\nConnection: close\r\n\r\n")
+}
+
+#[test]
+fn test_token_service_http() {
+    init_log();
+    let config = TokenServiceConfig {
+        protocol_id: PROTOCOL_ID,
+        private_key: *PRIVATE_KEY,
+        key_id: 0,
+        server_addresses: vec!["127.0.0.1:5000".parse().unwrap()],
+        expire_seconds: 300,
+        timeout_seconds: 15,
+        cipher_suite: CipherSuite::ChaCha20Poly1305,
+    };
+    let mut api_keys = StaticApiKeys::default();
+    api_keys.insert("valid", None);
+    let handle = TokenService::new(config, api_keys).listen("127.0.0.1:0").unwrap();
+    let addr = handle.local_addr();
+
+    assert_eq!(http_request(addr, &token_request(None)).0, 401);
+    assert_eq!(http_request(addr, &token_request(Some("invalid"))).0, 401);
+    assert_eq!(
+        http_request(addr, "GET /token HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").0,
+        405
+    );
+    assert_eq!(
+        http_request(
+            addr,
+            "POST /other HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        )
+        .0,
+        404
+    );
+
+    // A request waiting for its body does not block the other requests, bodies above 1024 bytes are read by the workers
+    let mut slow_stream = TcpStream::connect(addr).unwrap();
+    slow_stream
+        .write_all(b"POST /token HTTP/1.1\r\nHost: localhost\r\nContent-Length: 2048\r\nConnection: close\r\n\r\n")
+        .unwrap();
+
+    let (status, body) = http_request(addr, &token_request(Some("valid")));
+    assert_eq!(status, 200);
+    let token = ConnectToken::from_base64(&body).unwrap();
+    assert_eq!(token.protocol_id(), PROTOCOL_ID);
+    assert_eq!(token.server_addresses(), vec!["127.0.0.1:5000".parse::<SocketAddr>().unwrap()]);
+
+    drop(slow_stream);
+    handle.shutdown();
+}
+
+#[test]
+fn test_token_service_connect() {
+    init_log();
+    let server_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let server_addr = server_socket.local_addr().unwrap();
+    let user_data = [3u8; NETCODE_USER_DATA_BYTES];
+
+    let config = TokenServiceConfig {
+        protocol_id: PROTOCOL_ID,
+        private_key: *PRIVATE_KEY,
+        key_id: 0,
+        server_addresses: vec![server_addr],
+        expire_seconds: 300,
+        timeout_seconds: 15,
+        cipher_suite: CipherSuite::ChaCha20Poly1305,
+    };
+    let mut api_keys = StaticApiKeys::default();
+    api_keys.insert("valid", Some(user_data));
+    let handle = TokenService::new(config, api_keys).listen("127.0.0.1:0").unwrap();
+
+    let (status, body) = http_request(handle.local_addr(), &token_request(Some("valid")));
+    assert_eq!(status, 200);
+    let connect_token = ConnectToken::from_base64(&body).unwrap();
+    let client_id = ClientId::from_raw(connect_token.client_id());
+    handle.shutdown();
+
+    let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
+    let server_config = ServerConfig::new(
+        current_time,
+        1,
+        PROTOCOL_ID,
+        vec![server_addr],
+        ServerAuthentication::Secure { private_key: *PRIVATE_KEY },
+    );
+    let mut server_transport = NetcodeServerTransport::new(server_config, server_socket).unwrap();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+
+    let client_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let authentication = ClientAuthentication::Secure { connect_token };
+    let mut client_transport = NetcodeClientTransport::new(current_time, authentication, client_socket).unwrap();
+    let mut client = RenetClient::new(ConnectionConfig::default());
+
+    let delta = Duration::from_millis(10);
+    for _ in 0..200 {
+        client_transport.update(delta, &mut client).unwrap();
+        client_transport.send_packets(&mut client).unwrap();
+        server_transport.update(delta, &mut server).unwrap();
+        server_transport.send_packets(&mut server);
+        if client_transport.is_connected() && server.is_connected(client_id) {
+            break;
+        }
+        std::thread::sleep(delta);
+    }
+
+    assert!(client_transport.is_connected());
+    assert!(server.is_connected(client_id));
+    assert_eq!(server_transport.user_data(client_id), Some(user_data));
+}
@@ -205,7 +205,7 @@ impl<const N: usize> RenetServerVisualizer<N> {
     /// # let mut visualizer = RenetServerVisualizer::<5>::new(Default::default());
     /// while let Some(event) = renet_server.get_event() {
     ///     match event {
-    ///         ServerEvent::ClientConnected { client_id } => {
+    ///         ServerEvent::ClientConnected { client_id, .. } => {
     ///             visualizer.add_client(client_id);
     ///             // ...
     ///         }
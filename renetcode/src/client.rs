@@ -1,8 +1,13 @@
 use std::{fmt, net::SocketAddr, time::Duration};
 
 use crate::{
-    packet::Packet, replay_protection::ReplayProtection, token::ConnectToken, ClientID, NetcodeError, NETCODE_CHALLENGE_TOKEN_BYTES,
-    NETCODE_KEY_BYTES, NETCODE_MAX_PACKET_BYTES, NETCODE_MAX_PAYLOAD_BYTES, NETCODE_SEND_RATE, NETCODE_USER_DATA_BYTES,
+    crypto::{CipherSuite, PacketCipher},
+    packet::Packet,
+    replay_protection::ReplayProtection,
+    token::ConnectToken,
+    ClientID, NetcodeError, NETCODE_CHALLENGE_TOKEN_BYTES, NETCODE_CONNECT_TOKEN_EXPIRE_SECONDS, NETCODE_KEY_BYTES,
+    NETCODE_MAX_PACKET_BYTES, NETCODE_MAX_PAYLOAD_BYTES, NETCODE_NUM_DISCONNECT_PACKETS, NETCODE_SEND_RATE, NETCODE_TIMEOUT_SECONDS,
+    NETCODE_USER_DATA_BYTES,
 };
 
 /// The reason why a client is in error state
@@ -22,11 +27,13 @@ enum ClientState {
     Disconnected(DisconnectReason),
     SendingConnectionRequest,
     SendingConnectionResponse,
+    // Waiting for a free slot in the server, with the position in the queue
+    InQueue(u32),
     Connected,
 }
 
 /// Configuration to establish an secure ou unsecure connection with the server.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[allow(clippy::large_enum_variant)]
 pub enum ClientAuthentication {
     /// Establishes a safe connection with the server using the [crate::ConnectToken].
@@ -44,6 +51,35 @@ pub enum ClientAuthentication {
     },
 }
 
+/// Tunables for the client connection.
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    /// Interval between connection request, response and keep-alive packets sent to the server.
+    pub send_rate: Duration,
+    /// Seconds until the connect token generated for [ClientAuthentication::Unsecure] expires.
+    pub unsecure_expire_seconds: u64,
+    /// Connection timeout in seconds for [ClientAuthentication::Unsecure], negative values disable the timeout.
+    /// Secure clients use the timeout from the [ConnectToken].
+    pub unsecure_timeout_seconds: i32,
+    /// Number of times the transport sends the disconnect packet, see [NetcodeClient::disconnect_packets].
+    pub disconnect_packets: usize,
+    /// Cipher suite for [ClientAuthentication::Unsecure], must match the server [ServerConfig::cipher_suite][crate::ServerConfig::cipher_suite].
+    /// Secure clients use the cipher suite from the [ConnectToken].
+    pub unsecure_cipher_suite: CipherSuite,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            send_rate: NETCODE_SEND_RATE,
+            unsecure_expire_seconds: NETCODE_CONNECT_TOKEN_EXPIRE_SECONDS,
+            unsecure_timeout_seconds: NETCODE_TIMEOUT_SECONDS,
+            disconnect_packets: NETCODE_NUM_DISCONNECT_PACKETS,
+            unsecure_cipher_suite: CipherSuite::default(),
+        }
+    }
+}
+
 /// A client that can generate encrypted packets that be sent to the connected server, or consume
 /// encrypted packets from the server.
 /// The client is agnostic from the transport layer, only consuming and generating bytes
@@ -60,11 +96,15 @@ pub struct NetcodeClient {
     server_addr: SocketAddr,
     server_addr_index: usize,
     connect_token: ConnectToken,
+    // Ciphers created from the connect token keys, reused for every packet
+    send_cipher: PacketCipher,
+    receive_cipher: PacketCipher,
     challenge_token_sequence: u64,
     challenge_token_data: [u8; NETCODE_CHALLENGE_TOKEN_BYTES],
     max_clients: u32,
     client_index: u32,
     send_rate: Duration,
+    disconnect_packets: usize,
     replay_protection: ReplayProtection,
     out: [u8; NETCODE_MAX_PACKET_BYTES],
 }
@@ -87,26 +127,34 @@ impl fmt::Display for DisconnectReason {
 
 impl NetcodeClient {
     pub fn new(current_time: Duration, authentication: ClientAuthentication) -> Result<Self, NetcodeError> {
+        Self::with_config(current_time, authentication, ClientConfig::default())
+    }
+
+    pub fn with_config(current_time: Duration, authentication: ClientAuthentication, config: ClientConfig) -> Result<Self, NetcodeError> {
         let connect_token: ConnectToken = match authentication {
             ClientAuthentication::Unsecure {
                 server_addr,
                 protocol_id,
                 client_id,
                 user_data,
-            } => ConnectToken::generate(
+            } => ConnectToken::generate_with_cipher_suite(
                 current_time,
                 protocol_id,
-                300,
+                config.unsecure_expire_seconds,
                 client_id,
-                15,
+                config.unsecure_timeout_seconds,
                 vec![server_addr],
                 user_data.as_ref(),
+                0,
+                config.unsecure_cipher_suite,
                 &[0; NETCODE_KEY_BYTES],
             )?,
             ClientAuthentication::Secure { connect_token } => connect_token,
         };
 
         let server_addr = connect_token.server_addresses[0].expect("cannot create or deserialize a ConnectToken without a server address");
+        let send_cipher = PacketCipher::new(&connect_token.client_to_server_key, connect_token.cipher_suite);
+        let receive_cipher = PacketCipher::new(&connect_token.server_to_client_key, connect_token.cipher_suite);
 
         Ok(Self {
             sequence: 0,
@@ -121,9 +169,12 @@ impl NetcodeClient {
             current_time,
             max_clients: 0,
             client_index: 0,
-            send_rate: NETCODE_SEND_RATE,
+            send_rate: config.send_rate,
+            disconnect_packets: config.disconnect_packets.max(1),
             challenge_token_data: [0u8; NETCODE_CHALLENGE_TOKEN_BYTES],
             connect_token,
+            send_cipher,
+            receive_cipher,
             replay_protection: ReplayProtection::new(),
             out: [0u8; NETCODE_MAX_PACKET_BYTES],
         })
@@ -132,10 +183,19 @@ impl NetcodeClient {
     pub fn is_connecting(&self) -> bool {
         matches!(
             self.state,
-            ClientState::SendingConnectionRequest | ClientState::SendingConnectionResponse
+            ClientState::SendingConnectionRequest | ClientState::SendingConnectionResponse | ClientState::InQueue(_)
         )
     }
 
+    /// Returns the position in the server waiting queue, if the server is full and placed the client in the queue.
+    /// The client connects automatically when a slot is available.
+    pub fn queue_position(&self) -> Option<u32> {
+        match self.state {
+            ClientState::InQueue(position) => Some(position),
+            _ => None,
+        }
+    }
+
     pub fn is_connected(&self) -> bool {
         self.state == ClientState::Connected
     }
@@ -171,15 +231,44 @@ impl NetcodeClient {
         self.server_addr
     }
 
+    /// Returns the index in the connect token of the current server address.
+    pub fn server_addr_index(&self) -> usize {
+        self.server_addr_index
+    }
+
+    /// Starts the connection from the server address at the index in the connect token instead of the first one,
+    /// the following addresses are still tried in order when it times out.
+    /// Can only be called before the first packet is sent to the server.
+    pub fn set_server_addr_index(&mut self, index: usize) -> Result<(), NetcodeError> {
+        if self.state != ClientState::SendingConnectionRequest || self.last_packet_send_time.is_some() {
+            return Err(NetcodeError::ClientNotConnected);
+        }
+
+        match self.connect_token.server_addresses.get(index) {
+            Some(Some(server_addr)) => {
+                self.server_addr = *server_addr;
+                self.server_addr_index = index;
+                Ok(())
+            }
+            _ => Err(NetcodeError::NoMoreServers),
+        }
+    }
+
+    /// Returns how many times the disconnect packet should be sent to the server.
+    /// The copies are identical, the server ignores them after the first one arrives.
+    pub fn disconnect_packets(&self) -> usize {
+        self.disconnect_packets
+    }
+
     /// Disconnect the client from the server.
-    /// Returns a disconnect packet that should be sent to the server.
+    /// Returns a disconnect packet that should be sent to the server [NetcodeClient::disconnect_packets] times.
     pub fn disconnect(&mut self) -> Result<(SocketAddr, &mut [u8]), NetcodeError> {
         self.state = ClientState::Disconnected(DisconnectReason::DisconnectedByClient);
         let packet = Packet::Disconnect;
         let len = packet.encode(
             &mut self.out,
             self.connect_token.protocol_id,
-            Some((self.sequence, &self.connect_token.client_to_server_key)),
+            Some((self.sequence, &self.send_cipher)),
         )?;
 
         Ok((self.server_addr, &mut self.out[..len]))
@@ -192,7 +281,7 @@ impl NetcodeClient {
         let packet = match Packet::decode(
             buffer,
             self.connect_token.protocol_id,
-            Some(&self.connect_token.server_to_client_key),
+            Some(&self.receive_cipher),
             Some(&mut self.replay_protection),
         ) {
             Ok((_, packet)) => packet,
@@ -204,7 +293,10 @@ impl NetcodeClient {
         log::trace!("Received packet from server: {:?}", packet.packet_type());
 
         match (packet, &self.state) {
-            (Packet::ConnectionDenied, ClientState::SendingConnectionRequest | ClientState::SendingConnectionResponse) => {
+            (
+                Packet::ConnectionDenied,
+                ClientState::SendingConnectionRequest | ClientState::SendingConnectionResponse | ClientState::InQueue(_),
+            ) => {
                 self.state = ClientState::Disconnected(DisconnectReason::ConnectionDenied);
                 self.last_packet_received_time = self.current_time;
             }
@@ -224,7 +316,11 @@ impl NetcodeClient {
             (Packet::KeepAlive { .. }, ClientState::Connected) => {
                 self.last_packet_received_time = self.current_time;
             }
-            (Packet::KeepAlive { client_index, max_clients }, ClientState::SendingConnectionResponse) => {
+            (Packet::QueuePosition { position }, ClientState::SendingConnectionResponse | ClientState::InQueue(_)) => {
+                self.last_packet_received_time = self.current_time;
+                self.state = ClientState::InQueue(position);
+            }
+            (Packet::KeepAlive { client_index, max_clients }, ClientState::SendingConnectionResponse | ClientState::InQueue(_)) => {
                 self.last_packet_received_time = self.current_time;
                 self.max_clients = max_clients;
                 self.client_index = client_index;
@@ -258,7 +354,7 @@ impl NetcodeClient {
         let len = packet.encode(
             &mut self.out,
             self.connect_token.protocol_id,
-            Some((self.sequence, &self.connect_token.client_to_server_key)),
+            Some((self.sequence, &self.send_cipher)),
         )?;
         self.sequence += 1;
         self.last_packet_send_time = Some(self.current_time);
@@ -319,6 +415,15 @@ impl NetcodeClient {
                 }
                 Ok(())
             }
+            ClientState::InQueue(_) => {
+                // The connect token was already validated by the server, so it can not expire while waiting in the queue
+                if connection_timed_out {
+                    self.state = ClientState::Disconnected(DisconnectReason::ConnectionResponseTimedOut);
+                    return Err(NetcodeError::Disconnected(DisconnectReason::ConnectionResponseTimedOut));
+                }
+
+                Ok(())
+            }
             ClientState::Connected => {
                 if connection_timed_out {
                     self.state = ClientState::Disconnected(DisconnectReason::ConnectionTimedOut);
@@ -340,13 +445,16 @@ impl NetcodeClient {
 
         if matches!(
             self.state,
-            ClientState::Connected | ClientState::SendingConnectionRequest | ClientState::SendingConnectionResponse
+            ClientState::Connected
+                | ClientState::SendingConnectionRequest
+                | ClientState::SendingConnectionResponse
+                | ClientState::InQueue(_)
         ) {
             self.last_packet_send_time = Some(self.current_time);
         }
         let packet = match self.state {
             ClientState::SendingConnectionRequest => Packet::connection_request_from_token(&self.connect_token),
-            ClientState::SendingConnectionResponse => Packet::Response {
+            ClientState::SendingConnectionResponse | ClientState::InQueue(_) => Packet::Response {
                 token_sequence: self.challenge_token_sequence,
                 token_data: self.challenge_token_data,
             },
@@ -360,7 +468,7 @@ impl NetcodeClient {
         let result = packet.encode(
             &mut self.out,
             self.connect_token.protocol_id,
-            Some((self.sequence, &self.connect_token.client_to_server_key)),
+            Some((self.sequence, &self.send_cipher)),
         );
         match result {
             Err(_) => None,
@@ -399,8 +507,8 @@ mod tests {
             private_key,
         )
         .unwrap();
-        let server_key = connect_token.server_to_client_key;
-        let client_key = connect_token.client_to_server_key;
+        let server_cipher = PacketCipher::new(&connect_token.server_to_client_key, CipherSuite::default());
+        let client_cipher = PacketCipher::new(&connect_token.client_to_server_key, CipherSuite::default());
         let authentication = ClientAuthentication::Secure { connect_token };
         let mut client = NetcodeClient::new(Duration::ZERO, authentication).unwrap();
         let (packet_buffer, _) = client.update(Duration::ZERO).unwrap();
@@ -411,37 +519,95 @@ mod tests {
 
         let challenge_sequence = 7;
         let user_data = generate_random_bytes();
-        let challenge_key = generate_random_bytes();
-        let challenge_packet = Packet::generate_challenge(client_id, &user_data, challenge_sequence, &challenge_key).unwrap();
-        let len = challenge_packet.encode(&mut buffer, protocol_id, Some((0, &server_key))).unwrap();
+        let challenge_cipher = PacketCipher::new(&generate_random_bytes(), CipherSuite::ChaCha20Poly1305);
+        let challenge_packet = Packet::generate_challenge(client_id, &user_data, challenge_sequence, &challenge_cipher).unwrap();
+        let len = challenge_packet
+            .encode(&mut buffer, protocol_id, Some((0, &server_cipher)))
+            .unwrap();
         client.process_packet(&mut buffer[..len]);
         assert_eq!(ClientState::SendingConnectionResponse, client.state);
 
         let (packet_buffer, _) = client.update(Duration::ZERO).unwrap();
-        let (_, packet) = Packet::decode(packet_buffer, protocol_id, Some(&client_key), None).unwrap();
+        let (_, packet) = Packet::decode(packet_buffer, protocol_id, Some(&client_cipher), None).unwrap();
         assert!(matches!(packet, Packet::Response { .. }));
 
         let max_clients = 4;
         let client_index = 2;
         let keep_alive_packet = Packet::KeepAlive { max_clients, client_index };
-        let len = keep_alive_packet.encode(&mut buffer, protocol_id, Some((1, &server_key))).unwrap();
+        let len = keep_alive_packet
+            .encode(&mut buffer, protocol_id, Some((1, &server_cipher)))
+            .unwrap();
         client.process_packet(&mut buffer[..len]);
 
         assert_eq!(client.state, ClientState::Connected);
 
         let payload = vec![7u8; 500];
         let payload_packet = Packet::Payload(&payload[..]);
-        let len = payload_packet.encode(&mut buffer, protocol_id, Some((2, &server_key))).unwrap();
+        let len = payload_packet.encode(&mut buffer, protocol_id, Some((2, &server_cipher))).unwrap();
 
         let payload_client = client.process_packet(&mut buffer[..len]).unwrap();
         assert_eq!(payload, payload_client);
 
         let to_send_payload = vec![5u8; 1000];
         let (_, packet) = client.generate_payload_packet(&to_send_payload).unwrap();
-        let (_, result) = Packet::decode(packet, protocol_id, Some(&client_key), None).unwrap();
+        let (_, result) = Packet::decode(packet, protocol_id, Some(&client_cipher), None).unwrap();
         match result {
             Packet::Payload(payload) => assert_eq!(to_send_payload, payload),
             _ => unreachable!(),
         }
     }
+
+    #[test]
+    fn client_config() {
+        let config = ClientConfig {
+            send_rate: Duration::from_millis(500),
+            unsecure_expire_seconds: 60,
+            unsecure_timeout_seconds: -1,
+            disconnect_packets: 0,
+            unsecure_cipher_suite: CipherSuite::Aes256Gcm,
+        };
+        let authentication = ClientAuthentication::Unsecure {
+            protocol_id: 0,
+            client_id: 1,
+            server_addr: "127.0.0.1:5000".parse().unwrap(),
+            user_data: None,
+        };
+        let mut client = NetcodeClient::with_config(Duration::ZERO, authentication, config).unwrap();
+        assert_eq!(client.connect_token.timeout_seconds, -1);
+        assert_eq!(client.connect_token.cipher_suite, CipherSuite::Aes256Gcm);
+        // Always sends at least one disconnect packet
+        assert_eq!(client.disconnect_packets(), 1);
+
+        assert!(client.update(Duration::ZERO).is_some());
+        assert!(client.update(Duration::from_millis(250)).is_none());
+        assert!(client.update(Duration::from_millis(250)).is_some());
+
+        // Negative timeout never times out, only the connect token expiration applies
+        client.update(Duration::from_secs(30));
+        assert!(client.is_connecting());
+        client.update(Duration::from_secs(30));
+        assert_eq!(client.disconnect_reason(), Some(DisconnectReason::ConnectTokenExpired));
+    }
+
+    #[test]
+    fn client_server_addr_index() {
+        let server_addresses: Vec<SocketAddr> = vec!["127.0.0.1:8080".parse().unwrap(), "127.0.0.2:3000".parse().unwrap()];
+        let private_key = b"an example very very secret key.";
+        let connect_token = ConnectToken::generate(Duration::ZERO, 2, 30, 4, 5, server_addresses.clone(), None, private_key).unwrap();
+        let mut client = NetcodeClient::new(Duration::ZERO, ClientAuthentication::Secure { connect_token }).unwrap();
+
+        assert!(matches!(client.set_server_addr_index(2), Err(NetcodeError::NoMoreServers)));
+        assert!(matches!(client.set_server_addr_index(32), Err(NetcodeError::NoMoreServers)));
+        client.set_server_addr_index(1).unwrap();
+        assert_eq!(client.server_addr_index(), 1);
+        assert_eq!(client.server_addr(), server_addresses[1]);
+
+        let (_, addr) = client.update(Duration::ZERO).unwrap();
+        assert_eq!(addr, server_addresses[1]);
+        assert!(matches!(client.set_server_addr_index(0), Err(NetcodeError::ClientNotConnected)));
+
+        // No more servers after the last address times out
+        client.update(Duration::from_secs(6));
+        assert_eq!(client.disconnect_reason(), Some(DisconnectReason::ConnectionRequestTimedOut));
+    }
 }
@@ -0,0 +1,204 @@
+//! Conformance tests against fixed vectors in the netcode 1.02 standard format.
+//! The vectors in `test_vectors` are generated independently of renetcode by `test_vectors/generate.py`,
+//! following the byte layouts written by the reference implementation.
+
+use std::net::SocketAddr;
+
+use crate::{
+    crypto::{CipherSuite, PacketCipher},
+    packet::{ChallengeToken, Packet},
+    replay_protection::ReplayProtection,
+    token::{ConnectToken, PrivateConnectToken},
+    NETCODE_CHALLENGE_TOKEN_BYTES, NETCODE_CONNECT_TOKEN_PRIVATE_BYTES, NETCODE_KEY_BYTES, NETCODE_MAX_PACKET_BYTES,
+    NETCODE_USER_DATA_BYTES, NETCODE_VERSION_INFO,
+};
+
+const PROTOCOL_ID: u64 = 0x1122334455667788;
+const CREATE_TIMESTAMP: u64 = 1_000_000;
+const EXPIRE_TIMESTAMP: u64 = 1_000_300;
+const TIMEOUT_SECONDS: i32 = 15;
+const CLIENT_ID: u64 = 0x0102030405060708;
+const CHALLENGE_SEQUENCE: u64 = 0x1234;
+
+fn key(start: u8) -> [u8; NETCODE_KEY_BYTES] {
+    std::array::from_fn(|i| start + i as u8)
+}
+
+fn private_key() -> [u8; NETCODE_KEY_BYTES] {
+    key(0x00)
+}
+
+fn client_to_server_key() -> [u8; NETCODE_KEY_BYTES] {
+    key(0xA0)
+}
+
+fn server_to_client_key() -> [u8; NETCODE_KEY_BYTES] {
+    key(0xC0)
+}
+
+fn challenge_key() -> [u8; NETCODE_KEY_BYTES] {
+    key(0x60)
+}
+
+fn xnonce() -> [u8; 24] {
+    std::array::from_fn(|i| 0x40 + i as u8)
+}
+
+fn user_data() -> [u8; NETCODE_USER_DATA_BYTES] {
+    std::array::from_fn(|i| i as u8)
+}
+
+fn server_addresses() -> [Option<SocketAddr>; 32] {
+    let mut server_addresses = [None; 32];
+    server_addresses[0] = Some("127.0.0.1:40000".parse().unwrap());
+    server_addresses[1] = Some("[2001:db8::1]:50000".parse().unwrap());
+    server_addresses
+}
+
+fn private_connect_token() -> PrivateConnectToken {
+    PrivateConnectToken {
+        client_id: CLIENT_ID,
+        timeout_seconds: TIMEOUT_SECONDS,
+        server_addresses: server_addresses(),
+        client_to_server_key: client_to_server_key(),
+        server_to_client_key: server_to_client_key(),
+        user_data: user_data(),
+        cipher_suite: CipherSuite::ChaCha20Poly1305,
+    }
+}
+
+fn connect_token() -> ConnectToken {
+    ConnectToken {
+        client_id: 0,
+        version_info: *NETCODE_VERSION_INFO,
+        protocol_id: PROTOCOL_ID,
+        create_timestamp: CREATE_TIMESTAMP,
+        expire_timestamp: EXPIRE_TIMESTAMP,
+        xnonce: xnonce(),
+        server_addresses: server_addresses(),
+        client_to_server_key: client_to_server_key(),
+        server_to_client_key: server_to_client_key(),
+        private_data: *include_bytes!("../test_vectors/private_connect_token.bin"),
+        timeout_seconds: TIMEOUT_SECONDS,
+        cipher_suite: CipherSuite::ChaCha20Poly1305,
+    }
+}
+
+fn challenge_token_data() -> [u8; NETCODE_CHALLENGE_TOKEN_BYTES] {
+    *include_bytes!("../test_vectors/challenge_token.bin")
+}
+
+fn assert_packet(packet: Packet, sequence: u64, expected: &[u8]) {
+    let cipher = PacketCipher::new(&client_to_server_key(), CipherSuite::ChaCha20Poly1305);
+    let mut buffer = [0u8; NETCODE_MAX_PACKET_BYTES];
+    let len = packet.encode(&mut buffer, PROTOCOL_ID, Some((sequence, &cipher))).unwrap();
+    assert_eq!(
+        &buffer[..len],
+        expected,
+        "encoded {:?} does not match the vector",
+        packet.packet_type()
+    );
+
+    let mut buffer = expected.to_vec();
+    let mut replay_protection = ReplayProtection::new();
+    let (decoded_sequence, decoded) = Packet::decode(&mut buffer, PROTOCOL_ID, Some(&cipher), Some(&mut replay_protection)).unwrap();
+    assert_eq!(decoded_sequence, sequence);
+    assert_eq!(decoded, packet);
+}
+
+#[test]
+fn private_connect_token_vector() {
+    let expected = include_bytes!("../test_vectors/private_connect_token.bin");
+    let mut buffer = [0u8; NETCODE_CONNECT_TOKEN_PRIVATE_BYTES];
+    private_connect_token()
+        .encode(&mut buffer, PROTOCOL_ID, EXPIRE_TIMESTAMP, &xnonce(), &private_key())
+        .unwrap();
+    assert_eq!(&buffer, expected);
+
+    let decoded = PrivateConnectToken::decode(expected, PROTOCOL_ID, EXPIRE_TIMESTAMP, &xnonce(), &private_key()).unwrap();
+    assert_eq!(decoded, private_connect_token());
+}
+
+#[test]
+fn connect_token_vector() {
+    let expected = include_bytes!("../test_vectors/connect_token.bin");
+    let mut buffer = vec![];
+    connect_token().write_standard(&mut buffer).unwrap();
+    assert_eq!(buffer, expected);
+
+    let decoded = ConnectToken::read_standard(&mut expected.as_slice()).unwrap();
+    assert_eq!(decoded, connect_token());
+}
+
+#[test]
+fn connect_token_invalid_address_count() {
+    let mut token = include_bytes!("../test_vectors/connect_token.bin").to_vec();
+    // Offset of the number of server addresses: version, protocol id, timestamps, nonce, private data and timeout
+    let offset = 13 + 8 + 8 + 8 + 24 + NETCODE_CONNECT_TOKEN_PRIVATE_BYTES + 4;
+    for num_server_addresses in [0u32, 33] {
+        token[offset..offset + 4].copy_from_slice(&num_server_addresses.to_le_bytes());
+        assert!(ConnectToken::read_standard(&mut token.as_slice()).is_err());
+    }
+}
+
+#[test]
+fn challenge_token_vector() {
+    let challenge_cipher = PacketCipher::new(&challenge_key(), CipherSuite::ChaCha20Poly1305);
+    let Packet::Challenge {
+        token_data,
+        token_sequence,
+    } = Packet::generate_challenge(CLIENT_ID, &user_data(), CHALLENGE_SEQUENCE, &challenge_cipher).unwrap()
+    else {
+        unreachable!()
+    };
+    assert_eq!(token_sequence, CHALLENGE_SEQUENCE);
+    assert_eq!(token_data, challenge_token_data());
+
+    let decoded = ChallengeToken::decode(challenge_token_data(), CHALLENGE_SEQUENCE, &challenge_cipher).unwrap();
+    assert_eq!(decoded, ChallengeToken::new(CLIENT_ID, &user_data()));
+}
+
+#[test]
+fn connection_request_vector() {
+    let expected = include_bytes!("../test_vectors/connection_request.bin");
+    let packet = Packet::connection_request_from_token(&connect_token());
+    let mut buffer = [0u8; NETCODE_MAX_PACKET_BYTES];
+    let len = packet.encode(&mut buffer, PROTOCOL_ID, None).unwrap();
+    assert_eq!(&buffer[..len], expected);
+
+    let mut buffer = expected.to_vec();
+    let (_, decoded) = Packet::decode(&mut buffer, PROTOCOL_ID, None, None).unwrap();
+    assert_eq!(decoded, packet);
+}
+
+#[test]
+fn packet_vectors() {
+    assert_packet(Packet::ConnectionDenied, 0, include_bytes!("../test_vectors/connection_denied.bin"));
+    assert_packet(
+        Packet::Challenge {
+            token_sequence: CHALLENGE_SEQUENCE,
+            token_data: challenge_token_data(),
+        },
+        0x0102,
+        include_bytes!("../test_vectors/challenge.bin"),
+    );
+    assert_packet(
+        Packet::Response {
+            token_sequence: CHALLENGE_SEQUENCE,
+            token_data: challenge_token_data(),
+        },
+        0x030405,
+        include_bytes!("../test_vectors/response.bin"),
+    );
+    assert_packet(
+        Packet::KeepAlive {
+            client_index: 3,
+            max_clients: 64,
+        },
+        0x0102030405060708,
+        include_bytes!("../test_vectors/keep_alive.bin"),
+    );
+    let payload: Vec<u8> = (0..100).collect();
+    assert_packet(Packet::Payload(&payload), 1000, include_bytes!("../test_vectors/payload.bin"));
+    assert_packet(Packet::Disconnect, 255, include_bytes!("../test_vectors/disconnect.bin"));
+}
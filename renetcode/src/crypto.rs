@@ -1,19 +1,45 @@
+use std::io;
+
+use aes_gcm::Aes256Gcm;
 use chacha20poly1305::aead::{rand_core::RngCore, OsRng};
 use chacha20poly1305::{AeadInPlace, ChaCha20Poly1305, Error as CryptoError, Key, KeyInit, Nonce, Tag, XChaCha20Poly1305, XNonce};
 
 use crate::NETCODE_MAC_BYTES;
 
-pub fn dencrypted_in_place(buffer: &mut [u8], sequence: u64, private_key: &[u8; 32], aad: &[u8]) -> Result<(), CryptoError> {
-    let mut nonce = [0; 12];
-    nonce[4..12].copy_from_slice(&sequence.to_le_bytes());
-    let nonce = Nonce::from(nonce);
-    let (buffer, tag) = buffer.split_at_mut(buffer.len() - NETCODE_MAC_BYTES);
-    let tag = Tag::from_slice(tag);
+/// The AEAD cipher used to encrypt the packets of a connection, carried in the connect token.
+/// The connect token private data is always encrypted with XChaCha20-Poly1305.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CipherSuite {
+    /// ChaCha20-Poly1305, the cipher of the netcode standard.
+    #[default]
+    ChaCha20Poly1305,
+    /// AES-256-GCM, faster on CPUs with AES instructions (AES-NI).
+    /// Not part of the netcode standard, only renetcode clients and servers can use it.
+    Aes256Gcm,
+}
 
-    let key = Key::from_slice(private_key);
-    let cipher = ChaCha20Poly1305::new(key);
+impl CipherSuite {
+    /// Identifier written in the connect token, 0 is the standard cipher so tokens from other implementations are still valid.
+    pub(crate) fn id(self) -> u8 {
+        match self {
+            CipherSuite::ChaCha20Poly1305 => 0,
+            CipherSuite::Aes256Gcm => 1,
+        }
+    }
+
+    pub(crate) fn from_id(id: u8) -> Result<Self, io::Error> {
+        match id {
+            0 => Ok(CipherSuite::ChaCha20Poly1305),
+            1 => Ok(CipherSuite::Aes256Gcm),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "invalid cipher suite")),
+        }
+    }
+}
 
-    cipher.decrypt_in_place_detached(&nonce, aad, buffer, tag)
+fn sequence_nonce(sequence: u64) -> Nonce {
+    let mut nonce = [0; 12];
+    nonce[4..12].copy_from_slice(&sequence.to_le_bytes());
+    Nonce::from(nonce)
 }
 
 pub fn dencrypted_in_place_xnonce(buffer: &mut [u8], xnonce: &[u8; 24], private_key: &[u8; 32], aad: &[u8]) -> Result<(), CryptoError> {
@@ -27,18 +53,60 @@ pub fn dencrypted_in_place_xnonce(buffer: &mut [u8], xnonce: &[u8; 24], private_
     cipher.decrypt_in_place_detached(xnonce, aad, buffer, tag)
 }
 
-pub fn encrypt_in_place(buffer: &mut [u8], sequence: u64, key: &[u8; 32], aad: &[u8]) -> Result<(), CryptoError> {
-    let mut nonce = [0; 12];
-    nonce[4..12].copy_from_slice(&sequence.to_le_bytes());
-    let nonce = Nonce::from(nonce);
-    let (buffer, buffer_tag) = buffer.split_at_mut(buffer.len() - NETCODE_MAC_BYTES);
+/// Cipher of a connection, created once from its key and reused for every packet.
+#[derive(Clone)]
+pub enum PacketCipher {
+    ChaCha20Poly1305(ChaCha20Poly1305),
+    // Boxed since the expanded AES key is much bigger than the ChaCha20 key
+    Aes256Gcm(Box<Aes256Gcm>),
+}
 
-    let key = Key::from_slice(key);
-    let cipher = ChaCha20Poly1305::new(key);
-    let tag = cipher.encrypt_in_place_detached(&nonce, aad, buffer)?;
-    buffer_tag.copy_from_slice(&tag);
+impl PacketCipher {
+    pub fn new(key: &[u8; 32], cipher_suite: CipherSuite) -> Self {
+        let key = Key::from_slice(key);
+        match cipher_suite {
+            CipherSuite::ChaCha20Poly1305 => PacketCipher::ChaCha20Poly1305(ChaCha20Poly1305::new(key)),
+            CipherSuite::Aes256Gcm => PacketCipher::Aes256Gcm(Box::new(Aes256Gcm::new(key))),
+        }
+    }
 
-    Ok(())
+    pub fn cipher_suite(&self) -> CipherSuite {
+        match self {
+            PacketCipher::ChaCha20Poly1305(_) => CipherSuite::ChaCha20Poly1305,
+            PacketCipher::Aes256Gcm(_) => CipherSuite::Aes256Gcm,
+        }
+    }
+
+    pub fn decrypt_in_place(&self, buffer: &mut [u8], sequence: u64, aad: &[u8]) -> Result<(), CryptoError> {
+        let nonce = sequence_nonce(sequence);
+        let (buffer, tag) = buffer.split_at_mut(buffer.len() - NETCODE_MAC_BYTES);
+        let tag = Tag::from_slice(tag);
+
+        match self {
+            PacketCipher::ChaCha20Poly1305(cipher) => cipher.decrypt_in_place_detached(&nonce, aad, buffer, tag),
+            PacketCipher::Aes256Gcm(cipher) => cipher.decrypt_in_place_detached(&nonce, aad, buffer, tag),
+        }
+    }
+
+    pub fn encrypt_in_place(&self, buffer: &mut [u8], sequence: u64, aad: &[u8]) -> Result<(), CryptoError> {
+        let nonce = sequence_nonce(sequence);
+        let (buffer, buffer_tag) = buffer.split_at_mut(buffer.len() - NETCODE_MAC_BYTES);
+
+        let tag = match self {
+            PacketCipher::ChaCha20Poly1305(cipher) => cipher.encrypt_in_place_detached(&nonce, aad, buffer)?,
+            PacketCipher::Aes256Gcm(cipher) => cipher.encrypt_in_place_detached(&nonce, aad, buffer)?,
+        };
+        buffer_tag.copy_from_slice(&tag);
+
+        Ok(())
+    }
+}
+
+// The ciphers don't implement Debug, so the key is never printed
+impl std::fmt::Debug for PacketCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("PacketCipher").field(&self.cipher_suite()).finish()
+    }
 }
 
 pub fn encrypt_in_place_xnonce(buffer: &mut [u8], xnonce: &[u8; 24], key: &[u8; 32], aad: &[u8]) -> Result<(), CryptoError> {
@@ -73,12 +141,42 @@ mod tests {
         let sequence = 2;
         let aad = b"test";
 
+        for cipher_suite in [CipherSuite::ChaCha20Poly1305, CipherSuite::Aes256Gcm] {
+            let mut data = b"some packet data".to_vec();
+            let data_len = data.len();
+            data.extend_from_slice(&[0u8; NETCODE_MAC_BYTES]);
+
+            let cipher = PacketCipher::new(key, cipher_suite);
+            cipher.encrypt_in_place(&mut data, sequence, aad).unwrap();
+            cipher.decrypt_in_place(&mut data, sequence, aad).unwrap();
+            assert_eq!(&data[..data_len], b"some packet data");
+        }
+    }
+
+    #[test]
+    fn test_cipher_suites_are_not_interchangeable() {
+        let key = b"an example very very secret key."; // 32-bytes
         let mut data = b"some packet data".to_vec();
-        let data_len = data.len();
         data.extend_from_slice(&[0u8; NETCODE_MAC_BYTES]);
 
-        encrypt_in_place(&mut data, sequence, key, aad).unwrap();
-        dencrypted_in_place(&mut data, sequence, key, aad).unwrap();
-        assert_eq!(&data[..data_len], b"some packet data");
+        PacketCipher::new(key, CipherSuite::Aes256Gcm)
+            .encrypt_in_place(&mut data, 0, b"")
+            .unwrap();
+        assert!(PacketCipher::new(key, CipherSuite::ChaCha20Poly1305)
+            .decrypt_in_place(&mut data, 0, b"")
+            .is_err());
+    }
+
+    #[test]
+    fn test_aes_256_gcm_vector() {
+        // Test case 13 from "The Galois/Counter Mode of Operation (GCM)", empty plaintext with a zero key and nonce
+        let mut data = [0u8; NETCODE_MAC_BYTES];
+        PacketCipher::new(&[0; 32], CipherSuite::Aes256Gcm)
+            .encrypt_in_place(&mut data, 0, b"")
+            .unwrap();
+        assert_eq!(
+            data,
+            [0x53, 0x0f, 0x8a, 0xfb, 0xc7, 0x45, 0x36, 0xb9, 0xa9, 0x63, 0xb4, 0xf1, 0xc4, 0xcb, 0x73, 0x8b]
+        );
     }
 }
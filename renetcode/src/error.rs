@@ -14,6 +14,8 @@ pub enum NetcodeError {
     InvalidProtocolID,
     /// The connect token has an invalid version.
     InvalidVersion,
+    /// The connect token uses a different cipher suite than the server.
+    InvalidCipherSuite,
     /// Packet size is too small to be a netcode packet.
     PacketTooSmall,
     /// Payload is above the maximum limit
@@ -34,6 +36,12 @@ pub enum NetcodeError {
     ClientNotFound,
     /// Client is not connected.
     ClientNotConnected,
+    /// A client with the same id is already connected.
+    ClientAlreadyConnected,
+    /// The server reached the maximum number of clients.
+    ServerFull,
+    /// Packets are not generated for loopback clients, payloads are exchanged in-process.
+    LoopbackClient,
     /// IO error.
     IoError(io::Error),
     /// An error occured while generating the connect token.
@@ -49,6 +57,7 @@ impl fmt::Display for NetcodeError {
             InvalidPacketType => write!(fmt, "invalid packet type"),
             InvalidProtocolID => write!(fmt, "invalid protocol id"),
             InvalidVersion => write!(fmt, "invalid version info"),
+            InvalidCipherSuite => write!(fmt, "connect token uses a different cipher suite than the server"),
             PacketTooSmall => write!(fmt, "packet is too small"),
             PayloadAboveLimit => write!(fmt, "payload is above the {} bytes limit", NETCODE_MAX_PAYLOAD_BYTES),
             Expired => write!(fmt, "connection expired"),
@@ -59,6 +68,9 @@ impl fmt::Display for NetcodeError {
             NotInHostList => write!(fmt, "token does not contain the server address"),
             ClientNotFound => write!(fmt, "client was not found"),
             ClientNotConnected => write!(fmt, "client is disconnected or connecting"),
+            ClientAlreadyConnected => write!(fmt, "client is already connected"),
+            ServerFull => write!(fmt, "server has reached the maximum number of clients"),
+            LoopbackClient => write!(fmt, "client is a loopback client"),
             IoError(ref err) => write!(fmt, "{}", err),
             TokenGenerationError(ref err) => write!(fmt, "{}", err),
         }
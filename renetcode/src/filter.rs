@@ -0,0 +1,467 @@
+use std::{
+    collections::HashMap,
+    error, fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+    time::Duration,
+};
+
+use crate::ClientID;
+
+/// A range of ip addresses in the CIDR notation, for example `10.0.0.0/8` or `2001:db8::/32`.
+/// A single address can be converted into a range containing only itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IpRange {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+/// The ip range has a prefix length bigger than the address size or could not be parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidIpRange;
+
+impl fmt::Display for InvalidIpRange {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "invalid ip range")
+    }
+}
+
+impl error::Error for InvalidIpRange {}
+
+impl IpRange {
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Result<Self, InvalidIpRange> {
+        let max_prefix_len = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        if prefix_len > max_prefix_len {
+            return Err(InvalidIpRange);
+        }
+
+        // Ipv4-mapped ranges are stored as ipv4 ranges, since addresses are normalized before lookups
+        let (addr, prefix_len) = match addr {
+            IpAddr::V6(v6) if prefix_len >= 96 && v6.to_ipv4_mapped().is_some() => (addr.to_canonical(), prefix_len - 96),
+            _ => (addr, prefix_len),
+        };
+
+        // Store the network address so equal ranges compare equal
+        let addr = match addr {
+            IpAddr::V4(addr) => IpAddr::V4(Ipv4Addr::from(u32::from(addr) & ipv4_mask(prefix_len))),
+            IpAddr::V6(addr) => IpAddr::V6(Ipv6Addr::from(u128::from(addr) & ipv6_mask(prefix_len))),
+        };
+
+        Ok(Self { addr, prefix_len })
+    }
+
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    fn is_single_addr(&self) -> bool {
+        match self.addr {
+            IpAddr::V4(_) => self.prefix_len == 32,
+            IpAddr::V6(_) => self.prefix_len == 128,
+        }
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(addr), IpAddr::V4(ip)) => {
+                let mask = ipv4_mask(self.prefix_len);
+                u32::from(addr) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(addr), IpAddr::V6(ip)) => {
+                let mask = ipv6_mask(self.prefix_len);
+                u128::from(addr) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+fn ipv4_mask(prefix_len: u8) -> u32 {
+    u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0)
+}
+
+fn ipv6_mask(prefix_len: u8) -> u128 {
+    u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0)
+}
+
+impl From<IpAddr> for IpRange {
+    fn from(addr: IpAddr) -> Self {
+        let prefix_len = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+
+        Self::new(addr, prefix_len).expect("prefix length should be valid for the address")
+    }
+}
+
+impl FromStr for IpRange {
+    type Err = InvalidIpRange;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('/') {
+            None => Ok(IpAddr::from_str(s).map_err(|_| InvalidIpRange)?.into()),
+            Some((addr, prefix_len)) => {
+                let addr = IpAddr::from_str(addr).map_err(|_| InvalidIpRange)?;
+                let prefix_len = u8::from_str(prefix_len).map_err(|_| InvalidIpRange)?;
+                Self::new(addr, prefix_len)
+            }
+        }
+    }
+}
+
+/// Configuration for the per address rate limiting in the server.
+/// Each limit is a token bucket that refills at the given rate up to the burst size.
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    /// Connection requests per second allowed from the same ip address.
+    /// Clients resend the connection request every 250ms while connecting.
+    pub connection_requests_per_second: f64,
+    /// Maximum connection requests that can be received at once from the same ip address.
+    pub connection_requests_burst: u32,
+    /// Packets per second that fail to decode or decrypt allowed from the same ip address.
+    /// When exceeded, packets from the address are dropped before being decrypted.
+    pub invalid_packets_per_second: f64,
+    /// Maximum invalid packets that can be received at once from the same ip address.
+    pub invalid_packets_burst: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            connection_requests_per_second: 10.0,
+            connection_requests_burst: 20,
+            invalid_packets_per_second: 10.0,
+            invalid_packets_burst: 20,
+        }
+    }
+}
+
+/// Counters of packets dropped by the server before being processed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PacketFilterStats {
+    /// Packets dropped because the address or the client id is banned.
+    pub banned_packets: u64,
+    /// Connection requests dropped by the rate limit.
+    pub rate_limited_connection_requests: u64,
+    /// Packets dropped because the address sent too many invalid packets.
+    pub rate_limited_invalid_packets: u64,
+    /// Packets from unconnected addresses that failed to decode or decrypt.
+    pub invalid_packets: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    tokens: f64,
+    last_update: Duration,
+}
+
+#[derive(Debug, Default)]
+struct RateLimiter {
+    buckets: HashMap<IpAddr, TokenBucket>,
+}
+
+impl RateLimiter {
+    /// Ipv6 addresses are limited per /64 network, since a single host usually controls the whole subnet.
+    fn bucket_key(ip: IpAddr) -> IpAddr {
+        match ip.to_canonical() {
+            IpAddr::V6(ip) => IpAddr::V6(Ipv6Addr::from(u128::from(ip) & ipv6_mask(64))),
+            ip => ip,
+        }
+    }
+
+    fn refill(bucket: &mut TokenBucket, rate: f64, burst: u32, current_time: Duration) {
+        let elapsed = current_time.saturating_sub(bucket.last_update).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(burst as f64);
+        bucket.last_update = current_time;
+    }
+
+    fn has_tokens(&mut self, ip: IpAddr, rate: f64, burst: u32, current_time: Duration) -> bool {
+        match self.buckets.get_mut(&Self::bucket_key(ip)) {
+            None => burst > 0,
+            Some(bucket) => {
+                Self::refill(bucket, rate, burst, current_time);
+                bucket.tokens >= 1.0
+            }
+        }
+    }
+
+    /// Consumes a token from the address bucket, returns false if there was none available.
+    fn consume(&mut self, ip: IpAddr, rate: f64, burst: u32, current_time: Duration) -> bool {
+        let bucket = self.buckets.entry(Self::bucket_key(ip)).or_insert(TokenBucket {
+            tokens: burst as f64,
+            last_update: current_time,
+        });
+        Self::refill(bucket, rate, burst, current_time);
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+
+        bucket.tokens -= 1.0;
+        true
+    }
+
+    /// Removes buckets that are full, they behave the same as a new bucket.
+    fn remove_full_buckets(&mut self, rate: f64, burst: u32, current_time: Duration) {
+        self.buckets.retain(|_, bucket| {
+            Self::refill(bucket, rate, burst, current_time);
+            bucket.tokens < burst as f64
+        });
+    }
+}
+
+fn is_active(expire_time: Option<Duration>, current_time: Duration) -> bool {
+    match expire_time {
+        Some(expire_time) => current_time < expire_time,
+        None => true,
+    }
+}
+
+/// Ban lists and rate limits applied to packets from addresses that are not connected.
+#[derive(Debug, Default)]
+pub(crate) struct PacketFilter {
+    banned_client_ids: HashMap<ClientID, Option<Duration>>,
+    banned_ips: HashMap<IpAddr, Option<Duration>>,
+    banned_ranges: HashMap<IpRange, Option<Duration>>,
+    rate_limit: Option<RateLimitConfig>,
+    connection_requests: RateLimiter,
+    invalid_packets: RateLimiter,
+    stats: PacketFilterStats,
+}
+
+impl PacketFilter {
+    pub fn stats(&self) -> PacketFilterStats {
+        self.stats
+    }
+
+    pub fn set_rate_limit(&mut self, rate_limit: Option<RateLimitConfig>) {
+        self.rate_limit = rate_limit;
+        self.connection_requests = RateLimiter::default();
+        self.invalid_packets = RateLimiter::default();
+    }
+
+    pub fn ban_client_id(&mut self, client_id: ClientID, expire_time: Option<Duration>) {
+        self.banned_client_ids.insert(client_id, expire_time);
+    }
+
+    pub fn unban_client_id(&mut self, client_id: ClientID) -> bool {
+        self.banned_client_ids.remove(&client_id).is_some()
+    }
+
+    pub fn ban_ip_range(&mut self, range: IpRange, expire_time: Option<Duration>) {
+        if range.is_single_addr() {
+            self.banned_ips.insert(range.addr, expire_time);
+        } else {
+            self.banned_ranges.insert(range, expire_time);
+        }
+    }
+
+    pub fn unban_ip_range(&mut self, range: IpRange) -> bool {
+        if range.is_single_addr() {
+            self.banned_ips.remove(&range.addr).is_some()
+        } else {
+            self.banned_ranges.remove(&range).is_some()
+        }
+    }
+
+    pub fn is_client_id_banned(&self, client_id: ClientID, current_time: Duration) -> bool {
+        matches!(self.banned_client_ids.get(&client_id), Some(&expire_time) if is_active(expire_time, current_time))
+    }
+
+    pub fn is_ip_banned(&self, ip: IpAddr, current_time: Duration) -> bool {
+        let ip = ip.to_canonical();
+        if matches!(self.banned_ips.get(&ip), Some(&expire_time) if is_active(expire_time, current_time)) {
+            return true;
+        }
+
+        self.banned_ranges
+            .iter()
+            .any(|(range, &expire_time)| is_active(expire_time, current_time) && range.contains(ip))
+    }
+
+    /// Returns false if the packet should be dropped because the address is banned,
+    /// or it has sent too many invalid packets.
+    pub fn allow_packet(&mut self, ip: IpAddr, current_time: Duration) -> bool {
+        if self.is_ip_banned(ip, current_time) {
+            self.stats.banned_packets += 1;
+            return false;
+        }
+
+        if let Some(config) = &self.rate_limit {
+            if !self
+                .invalid_packets
+                .has_tokens(ip, config.invalid_packets_per_second, config.invalid_packets_burst, current_time)
+            {
+                self.stats.rate_limited_invalid_packets += 1;
+                return false;
+            }
+        }
+
+        true
+    }
+
+    pub fn allow_connection_request(&mut self, ip: IpAddr, current_time: Duration) -> bool {
+        if let Some(config) = &self.rate_limit {
+            if !self.connection_requests.consume(
+                ip,
+                config.connection_requests_per_second,
+                config.connection_requests_burst,
+                current_time,
+            ) {
+                self.stats.rate_limited_connection_requests += 1;
+                return false;
+            }
+        }
+
+        true
+    }
+
+    pub fn client_id_banned(&mut self) {
+        self.stats.banned_packets += 1;
+    }
+
+    pub fn invalid_packet(&mut self, ip: IpAddr, current_time: Duration) {
+        self.stats.invalid_packets += 1;
+        if let Some(config) = &self.rate_limit {
+            self.invalid_packets
+                .consume(ip, config.invalid_packets_per_second, config.invalid_packets_burst, current_time);
+        }
+    }
+
+    /// Removes expired bans and rate limit entries that are no longer limiting.
+    pub fn update(&mut self, current_time: Duration) {
+        self.banned_client_ids
+            .retain(|_, &mut expire_time| is_active(expire_time, current_time));
+        self.banned_ips.retain(|_, &mut expire_time| is_active(expire_time, current_time));
+        self.banned_ranges
+            .retain(|_, &mut expire_time| is_active(expire_time, current_time));
+
+        if let Some(config) = &self.rate_limit {
+            self.connection_requests.remove_full_buckets(
+                config.connection_requests_per_second,
+                config.connection_requests_burst,
+                current_time,
+            );
+            self.invalid_packets
+                .remove_full_buckets(config.invalid_packets_per_second, config.invalid_packets_burst, current_time);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ip_range() {
+        let range: IpRange = "10.1.0.0/16".parse().unwrap();
+        assert!(range.contains("10.1.200.3".parse().unwrap()));
+        assert!(!range.contains("10.2.0.1".parse().unwrap()));
+        assert!(!range.contains("::1".parse().unwrap()));
+        assert_eq!(range, "10.1.2.3/16".parse().unwrap());
+
+        let range: IpRange = "2001:db8::/32".parse().unwrap();
+        assert!(range.contains("2001:db8:1::1".parse().unwrap()));
+        assert!(!range.contains("2001:db9::1".parse().unwrap()));
+
+        let everything: IpRange = "0.0.0.0/0".parse().unwrap();
+        assert!(everything.contains("1.2.3.4".parse().unwrap()));
+
+        assert_eq!("10.0.0.0/33".parse::<IpRange>(), Err(InvalidIpRange));
+        assert_eq!("10.0.0.0/a".parse::<IpRange>(), Err(InvalidIpRange));
+    }
+
+    #[test]
+    fn ban_expiration() {
+        let mut filter = PacketFilter::default();
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        filter.ban_ip_range("10.0.0.0/24".parse().unwrap(), Some(Duration::from_secs(10)));
+        filter.ban_client_id(4, None);
+
+        assert!(!filter.allow_packet(ip, Duration::from_secs(5)));
+        assert!(filter.is_client_id_banned(4, Duration::from_secs(5)));
+
+        filter.update(Duration::from_secs(10));
+        assert!(filter.allow_packet(ip, Duration::from_secs(10)));
+        assert!(filter.is_client_id_banned(4, Duration::from_secs(10)));
+        assert_eq!(filter.stats().banned_packets, 1);
+    }
+
+    #[test]
+    fn rate_limit() {
+        let mut filter = PacketFilter::default();
+        filter.set_rate_limit(Some(RateLimitConfig {
+            connection_requests_per_second: 1.0,
+            connection_requests_burst: 2,
+            invalid_packets_per_second: 1.0,
+            invalid_packets_burst: 1,
+        }));
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let other_ip: IpAddr = "10.0.0.2".parse().unwrap();
+
+        assert!(filter.allow_connection_request(ip, Duration::ZERO));
+        assert!(filter.allow_connection_request(ip, Duration::ZERO));
+        assert!(!filter.allow_connection_request(ip, Duration::ZERO));
+        assert!(filter.allow_connection_request(other_ip, Duration::ZERO));
+        assert!(filter.allow_connection_request(ip, Duration::from_secs(1)));
+
+        assert!(filter.allow_packet(ip, Duration::ZERO));
+        filter.invalid_packet(ip, Duration::ZERO);
+        assert!(!filter.allow_packet(ip, Duration::ZERO));
+        assert!(filter.allow_packet(ip, Duration::from_secs(1)));
+
+        let stats = filter.stats();
+        assert_eq!(stats.rate_limited_connection_requests, 1);
+        assert_eq!(stats.rate_limited_invalid_packets, 1);
+        assert_eq!(stats.invalid_packets, 1);
+
+        filter.update(Duration::from_secs(10));
+        assert!(filter.connection_requests.buckets.is_empty());
+        assert!(filter.invalid_packets.buckets.is_empty());
+    }
+
+    #[test]
+    fn ipv6_rate_limit_per_subnet() {
+        let mut filter = PacketFilter::default();
+        filter.set_rate_limit(Some(RateLimitConfig {
+            connection_requests_per_second: 1.0,
+            connection_requests_burst: 1,
+            invalid_packets_per_second: 1.0,
+            invalid_packets_burst: 1,
+        }));
+
+        assert!(filter.allow_connection_request("2001:db8:0:1::1".parse().unwrap(), Duration::ZERO));
+        assert!(!filter.allow_connection_request("2001:db8:0:1::2".parse().unwrap(), Duration::ZERO));
+        assert!(filter.allow_connection_request("2001:db8:0:2::1".parse().unwrap(), Duration::ZERO));
+
+        // Ipv4-mapped addresses share the bucket of the ipv4 address
+        filter.invalid_packet("10.0.0.1".parse().unwrap(), Duration::ZERO);
+        assert!(!filter.allow_packet("::ffff:10.0.0.1".parse().unwrap(), Duration::ZERO));
+    }
+
+    #[test]
+    fn ipv4_mapped_ban() {
+        let mut filter = PacketFilter::default();
+        filter.ban_ip_range("10.0.0.0/24".parse().unwrap(), None);
+        assert!(!filter.allow_packet("::ffff:10.0.0.1".parse().unwrap(), Duration::ZERO));
+
+        filter.ban_ip_range("::ffff:10.1.0.0/112".parse().unwrap(), None);
+        assert!(!filter.allow_packet("10.1.2.3".parse().unwrap(), Duration::ZERO));
+        assert!(filter.unban_ip_range("10.1.0.0/16".parse().unwrap()));
+
+        let mapped_addr: IpAddr = "::ffff:10.2.0.1".parse().unwrap();
+        assert_eq!(IpRange::from(mapped_addr), "10.2.0.1".parse().unwrap());
+        filter.ban_ip_range("::ffff:10.2.0.1".parse().unwrap(), None);
+        assert!(!filter.allow_packet("10.2.0.1".parse().unwrap(), Duration::ZERO));
+        assert!(!filter.allow_packet(mapped_addr, Duration::ZERO));
+        assert!(filter.allow_packet("10.2.0.2".parse().unwrap(), Duration::ZERO));
+        assert!(filter.unban_ip_range(IpRange::from(IpAddr::from([10, 2, 0, 1]))));
+    }
+}
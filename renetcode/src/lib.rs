@@ -17,32 +17,42 @@
 //! [standard]: https://github.com/networkprotocol/netcode/blob/master/STANDARD.md
 //! [netcode]: https://github.com/networkprotocol/netcode
 mod client;
+#[cfg(test)]
+mod conformance;
 mod crypto;
 mod error;
+mod filter;
 mod packet;
 mod replay_protection;
 mod serialize;
 mod server;
 mod token;
+#[cfg(feature = "serde")]
+mod user_data;
 
-pub use client::{ClientAuthentication, DisconnectReason, NetcodeClient};
-pub use crypto::generate_random_bytes;
+pub use client::{ClientAuthentication, ClientConfig, DisconnectReason, NetcodeClient};
+pub use crypto::{generate_random_bytes, CipherSuite};
 pub use error::NetcodeError;
-pub use server::{NetcodeServer, ServerAuthentication, ServerConfig, ServerResult};
+pub use filter::{InvalidIpRange, IpRange, PacketFilterStats, RateLimitConfig};
+pub use server::{ConnectionApproval, ConnectionRequest, NetcodeServer, ServerAuthentication, ServerConfig, ServerResult};
 pub use token::{ConnectToken, TokenGenerationError};
+#[cfg(feature = "serde")]
+pub use user_data::{decode_user_data, encode_user_data, UserDataError};
 
 use std::time::Duration;
 
 type ClientID = u64;
 
 const NETCODE_VERSION_INFO: &[u8; 13] = b"NETCODE 1.02\0";
-const NETCODE_MAX_CLIENTS: usize = 1024;
-const NETCODE_MAX_PENDING_CLIENTS: usize = NETCODE_MAX_CLIENTS * 4;
+/// The default maximum number of clients in the middle of the connection handshake, see [ServerConfig::max_pending_clients].
+pub const NETCODE_MAX_PENDING_CLIENTS: usize = 4096;
 
 const NETCODE_ADDRESS_NONE: u8 = 0;
 const NETCODE_ADDRESS_IPV4: u8 = 1;
 const NETCODE_ADDRESS_IPV6: u8 = 2;
 
+/// The number of bytes of a connect token in the netcode standard format, see [ConnectToken::write_standard].
+pub const NETCODE_CONNECT_TOKEN_BYTES: usize = 2048;
 const NETCODE_CONNECT_TOKEN_PRIVATE_BYTES: usize = 1024;
 /// The maximum number of bytes that a netcode packet can contain.
 pub const NETCODE_MAX_PACKET_BYTES: usize = 1400;
@@ -59,6 +69,14 @@ const NETCODE_CONNECT_TOKEN_XNONCE_BYTES: usize = 24;
 
 const NETCODE_ADDITIONAL_DATA_SIZE: usize = 13 + 8 + 8;
 
-const NETCODE_TIMEOUT_SECONDS: i32 = 15;
+/// The default connection timeout used by unsecure clients, see [ClientConfig::unsecure_timeout_seconds].
+pub const NETCODE_TIMEOUT_SECONDS: i32 = 15;
+/// The default connect token expiration used by unsecure clients, see [ClientConfig::unsecure_expire_seconds].
+pub const NETCODE_CONNECT_TOKEN_EXPIRE_SECONDS: u64 = 300;
 
-const NETCODE_SEND_RATE: Duration = Duration::from_millis(250);
+/// The default interval between protocol packets, see [ServerConfig::send_rate] and [ClientConfig::send_rate].
+pub const NETCODE_SEND_RATE: Duration = Duration::from_millis(250);
+
+/// The default number of times a disconnect packet is sent, so the peer is notified even if some packets are lost.
+/// See [ClientConfig::disconnect_packets] and [NetcodeServer::set_disconnect_packets].
+pub const NETCODE_NUM_DISCONNECT_PACKETS: usize = 10;
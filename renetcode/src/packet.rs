@@ -1,11 +1,11 @@
 use std::io::{self, Cursor, Write};
 
-use crate::crypto::{dencrypted_in_place, encrypt_in_place};
+use crate::crypto::PacketCipher;
 use crate::replay_protection::ReplayProtection;
 use crate::token::ConnectToken;
 use crate::{
     serialize::*, NetcodeError, NETCODE_CHALLENGE_TOKEN_BYTES, NETCODE_CONNECT_TOKEN_PRIVATE_BYTES, NETCODE_CONNECT_TOKEN_XNONCE_BYTES,
-    NETCODE_KEY_BYTES, NETCODE_MAC_BYTES,
+    NETCODE_MAC_BYTES,
 };
 use crate::{NETCODE_USER_DATA_BYTES, NETCODE_VERSION_INFO};
 
@@ -19,6 +19,8 @@ pub enum PacketType {
     KeepAlive = 4,
     Payload = 5,
     Disconnect = 6,
+    // Not part of the netcode standard, only sent when the server has the waiting queue enabled
+    QueuePosition = 7,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -46,6 +48,9 @@ pub enum Packet<'a> {
     },
     Payload(&'a [u8]),
     Disconnect,
+    QueuePosition {
+        position: u32,
+    },
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -66,6 +71,7 @@ impl PacketType {
             4 => KeepAlive,
             5 => Payload,
             6 => Disconnect,
+            7 => QueuePosition,
             _ => return Err(NetcodeError::InvalidPacketType),
         };
         Ok(packet_type)
@@ -88,6 +94,7 @@ impl<'a> Packet<'a> {
             Packet::KeepAlive { .. } => PacketType::KeepAlive,
             Packet::Payload { .. } => PacketType::Payload,
             Packet::Disconnect => PacketType::Disconnect,
+            Packet::QueuePosition { .. } => PacketType::QueuePosition,
         }
     }
 
@@ -109,12 +116,12 @@ impl<'a> Packet<'a> {
         client_id: u64,
         user_data: &[u8; NETCODE_USER_DATA_BYTES],
         challenge_sequence: u64,
-        challenge_key: &[u8; NETCODE_KEY_BYTES],
+        challenge_cipher: &PacketCipher,
     ) -> Result<Self, NetcodeError> {
         let token = ChallengeToken::new(client_id, user_data);
         let mut buffer = [0u8; NETCODE_CHALLENGE_TOKEN_BYTES];
         token.write(&mut Cursor::new(&mut buffer[..]))?;
-        encrypt_in_place(&mut buffer, challenge_sequence, challenge_key, b"")?;
+        challenge_cipher.encrypt_in_place(&mut buffer, challenge_sequence, b"")?;
 
         Ok(Packet::Challenge {
             token_sequence: challenge_sequence,
@@ -155,6 +162,9 @@ impl<'a> Packet<'a> {
             Packet::Payload(p) => {
                 writer.write_all(p)?;
             }
+            Packet::QueuePosition { position } => {
+                writer.write_all(&position.to_le_bytes())?;
+            }
             Packet::ConnectionDenied | Packet::Disconnect => {}
         }
 
@@ -208,21 +218,26 @@ impl<'a> Packet<'a> {
 
                 Ok(Packet::KeepAlive { client_index, max_clients })
             }
+            PacketType::QueuePosition => {
+                let position = read_u32(src)?;
+
+                Ok(Packet::QueuePosition { position })
+            }
             PacketType::ConnectionDenied => Ok(Packet::ConnectionDenied),
             PacketType::Disconnect => Ok(Packet::Disconnect),
             PacketType::Payload => unreachable!(),
         }
     }
 
-    pub fn encode(&self, buffer: &mut [u8], protocol_id: u64, crypto_info: Option<(u64, &[u8; 32])>) -> Result<usize, NetcodeError> {
+    pub fn encode(&self, buffer: &mut [u8], protocol_id: u64, crypto_info: Option<(u64, &PacketCipher)>) -> Result<usize, NetcodeError> {
         if matches!(self, Packet::ConnectionRequest { .. }) {
+            // Connection requests have no sequence, the prefix byte is only the packet type
             let mut writer = io::Cursor::new(buffer);
-            let prefix_byte = encode_prefix(self.id(), 0);
-            writer.write_all(&prefix_byte.to_le_bytes())?;
+            writer.write_all(&[self.id()])?;
 
             self.write(&mut writer)?;
             Ok(writer.position() as usize)
-        } else if let Some((sequence, private_key)) = crypto_info {
+        } else if let Some((sequence, cipher)) = crypto_info {
             let (start, end, aad) = {
                 let mut writer = io::Cursor::new(&mut *buffer);
                 let prefix_byte = {
@@ -245,17 +260,22 @@ impl<'a> Packet<'a> {
                 )));
             }
 
-            encrypt_in_place(&mut buffer[start..end + NETCODE_MAC_BYTES], sequence, private_key, &aad)?;
+            cipher.encrypt_in_place(&mut buffer[start..end + NETCODE_MAC_BYTES], sequence, &aad)?;
             Ok(end + NETCODE_MAC_BYTES)
         } else {
             Err(NetcodeError::UnavailablePrivateKey)
         }
     }
 
+    /// Returns true if the prefix byte of the buffer is from a disconnect packet, the packet is not decrypted.
+    pub fn is_disconnect(buffer: &[u8]) -> bool {
+        matches!(buffer.first(), Some(&prefix_byte) if decode_prefix(prefix_byte).0 == PacketType::Disconnect as u8)
+    }
+
     pub fn decode(
         mut buffer: &'a mut [u8],
         protocol_id: u64,
-        private_key: Option<&[u8; 32]>,
+        crypto_info: Option<&PacketCipher>,
         replay_protection: Option<&mut ReplayProtection>,
     ) -> Result<(u64, Self), NetcodeError> {
         if buffer.len() < 2 + NETCODE_MAC_BYTES {
@@ -268,7 +288,7 @@ impl<'a> Packet<'a> {
 
         if matches!(packet_type, PacketType::ConnectionRequest) {
             Ok((0, Packet::read(PacketType::ConnectionRequest, &buffer[1..])?))
-        } else if let Some(private_key) = private_key {
+        } else if let Some(cipher) = crypto_info {
             let (sequence, aad, read_pos) = {
                 let src = &mut io::Cursor::new(&mut buffer);
                 src.set_position(1);
@@ -283,7 +303,7 @@ impl<'a> Packet<'a> {
                 }
             }
 
-            dencrypted_in_place(&mut buffer[read_pos..], sequence, private_key, &aad)?;
+            cipher.decrypt_in_place(&mut buffer[read_pos..], sequence, &aad)?;
 
             if let Some(replay_protection) = replay_protection {
                 if packet_type.apply_replay_protection() {
@@ -324,11 +344,11 @@ impl ChallengeToken {
     pub fn decode(
         token_data: [u8; NETCODE_CHALLENGE_TOKEN_BYTES],
         token_sequence: u64,
-        challenge_key: &[u8; NETCODE_KEY_BYTES],
+        challenge_cipher: &PacketCipher,
     ) -> Result<ChallengeToken, NetcodeError> {
         let mut decoded = [0u8; NETCODE_CHALLENGE_TOKEN_BYTES];
         decoded.copy_from_slice(&token_data);
-        dencrypted_in_place(&mut decoded, token_sequence, challenge_key, b"")?;
+        challenge_cipher.decrypt_in_place(&mut decoded, token_sequence, b"")?;
 
         Ok(ChallengeToken::read(&mut Cursor::new(&mut decoded))?)
     }
@@ -353,7 +373,7 @@ fn encode_prefix(value: u8, sequence: u64) -> u8 {
 
 fn sequence_bytes_required(sequence: u64) -> usize {
     let mut mask: u64 = 0xFF00_0000_0000_0000;
-    for i in 0..8 {
+    for i in 0..7 {
         if (sequence & mask) != 0x00 {
             return 8 - i;
         }
@@ -361,7 +381,9 @@ fn sequence_bytes_required(sequence: u64) -> usize {
         mask >>= 8;
     }
 
-    0
+    // The sequence is always written with at least one byte, otherwise packets without data
+    // would be smaller than the minimum packet size.
+    1
 }
 
 fn write_sequence(out: &mut impl io::Write, seq: u64) -> Result<usize, io::Error> {
@@ -378,7 +400,10 @@ fn read_sequence(source: &mut impl io::Read, len: usize) -> Result<u64, io::Erro
 
 #[cfg(test)]
 mod tests {
-    use crate::{crypto::generate_random_bytes, NETCODE_MAX_PACKET_BYTES, NETCODE_MAX_PAYLOAD_BYTES};
+    use crate::{
+        crypto::{generate_random_bytes, CipherSuite},
+        NETCODE_MAX_PACKET_BYTES, NETCODE_MAX_PAYLOAD_BYTES,
+    };
 
     use super::*;
 
@@ -426,6 +451,17 @@ mod tests {
         assert_eq!(deserialized, connection_keep_alive);
     }
 
+    #[test]
+    fn queue_position_serialization() {
+        let queue_position = Packet::QueuePosition { position: 42 };
+
+        let mut buffer = Vec::new();
+        queue_position.write(&mut buffer).unwrap();
+        let deserialized = Packet::read(PacketType::QueuePosition, buffer.as_slice()).unwrap();
+
+        assert_eq!(deserialized, queue_position);
+    }
+
     #[test]
     fn prefix_sequence() {
         let packet_type = Packet::Disconnect.id();
@@ -447,12 +483,12 @@ mod tests {
     #[test]
     fn encrypt_decrypt_disconnect_packet() {
         let mut buffer = [0u8; NETCODE_MAX_PACKET_BYTES];
-        let key = b"an example very very secret key."; // 32-bytes
+        let cipher = PacketCipher::new(b"an example very very secret key.", CipherSuite::default());
         let packet = Packet::Disconnect;
         let protocol_id = 12;
         let sequence = 1;
-        let len = packet.encode(&mut buffer, protocol_id, Some((sequence, key))).unwrap();
-        let (d_sequence, d_packet) = Packet::decode(&mut buffer[..len], protocol_id, Some(key), None).unwrap();
+        let len = packet.encode(&mut buffer, protocol_id, Some((sequence, &cipher))).unwrap();
+        let (d_sequence, d_packet) = Packet::decode(&mut buffer[..len], protocol_id, Some(&cipher), None).unwrap();
         assert_eq!(sequence, d_sequence);
         assert_eq!(packet, d_packet);
     }
@@ -460,12 +496,12 @@ mod tests {
     #[test]
     fn encrypt_decrypt_denied_packet() {
         let mut buffer = [0u8; NETCODE_MAX_PACKET_BYTES];
-        let key = b"an example very very secret key."; // 32-bytes
+        let cipher = PacketCipher::new(b"an example very very secret key.", CipherSuite::default());
         let packet = Packet::ConnectionDenied;
         let protocol_id = 12;
         let sequence = 2;
-        let len = packet.encode(&mut buffer, protocol_id, Some((sequence, key))).unwrap();
-        let (d_sequence, d_packet) = Packet::decode(&mut buffer[..len], protocol_id, Some(key), None).unwrap();
+        let len = packet.encode(&mut buffer, protocol_id, Some((sequence, &cipher))).unwrap();
+        let (d_sequence, d_packet) = Packet::decode(&mut buffer[..len], protocol_id, Some(&cipher), None).unwrap();
         assert_eq!(sequence, d_sequence);
         assert_eq!(packet, d_packet);
     }
@@ -474,12 +510,12 @@ mod tests {
     fn encrypt_decrypt_payload_packet() {
         let mut buffer = [0u8; NETCODE_MAX_PACKET_BYTES];
         let payload = vec![7u8; NETCODE_MAX_PAYLOAD_BYTES];
-        let key = b"an example very very secret key."; // 32-bytes
+        let cipher = PacketCipher::new(b"an example very very secret key.", CipherSuite::default());
         let packet = Packet::Payload(&payload);
         let protocol_id = 12;
         let sequence = 2;
-        let len = packet.encode(&mut buffer, protocol_id, Some((sequence, key))).unwrap();
-        let (d_sequence, d_packet) = Packet::decode(&mut buffer[..len], protocol_id, Some(key), None).unwrap();
+        let len = packet.encode(&mut buffer, protocol_id, Some((sequence, &cipher))).unwrap();
+        let (d_sequence, d_packet) = Packet::decode(&mut buffer[..len], protocol_id, Some(&cipher), None).unwrap();
         assert_eq!(sequence, d_sequence);
         match d_packet {
             Packet::Payload(ref p) => assert_eq!(&payload, p),
@@ -492,17 +528,17 @@ mod tests {
     fn encrypt_decrypt_challenge_token() {
         let client_id = 0;
         let user_data = generate_random_bytes();
-        let challenge_key = generate_random_bytes();
+        let challenge_cipher = PacketCipher::new(&generate_random_bytes(), CipherSuite::ChaCha20Poly1305);
         let challenge_sequence = 1;
         let token = ChallengeToken::new(client_id, &user_data);
-        let packet = Packet::generate_challenge(client_id, &user_data, challenge_sequence, &challenge_key).unwrap();
+        let packet = Packet::generate_challenge(client_id, &user_data, challenge_sequence, &challenge_cipher).unwrap();
 
         match packet {
             Packet::Challenge {
                 token_data,
                 token_sequence,
             } => {
-                let decoded = ChallengeToken::decode(token_data, token_sequence, &challenge_key).unwrap();
+                let decoded = ChallengeToken::decode(token_data, token_sequence, &challenge_cipher).unwrap();
                 assert_eq!(decoded, token);
             }
             _ => unreachable!(),
@@ -1,19 +1,39 @@
-use std::{collections::HashMap, net::SocketAddr, time::Duration};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, VecDeque},
+    fmt,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
 
 use crate::{
-    crypto::generate_random_bytes,
+    crypto::{generate_random_bytes, CipherSuite, PacketCipher},
+    filter::{IpRange, PacketFilter, PacketFilterStats, RateLimitConfig},
     packet::{ChallengeToken, Packet},
     replay_protection::ReplayProtection,
-    token::PrivateConnectToken,
+    token::{key_id_from_xnonce, PrivateConnectToken},
     ClientID, NetcodeError, NETCODE_CONNECT_TOKEN_PRIVATE_BYTES, NETCODE_CONNECT_TOKEN_XNONCE_BYTES, NETCODE_KEY_BYTES, NETCODE_MAC_BYTES,
-    NETCODE_MAX_CLIENTS, NETCODE_MAX_PACKET_BYTES, NETCODE_MAX_PAYLOAD_BYTES, NETCODE_MAX_PENDING_CLIENTS, NETCODE_SEND_RATE,
+    NETCODE_MAX_PACKET_BYTES, NETCODE_MAX_PAYLOAD_BYTES, NETCODE_MAX_PENDING_CLIENTS, NETCODE_NUM_DISCONNECT_PACKETS, NETCODE_SEND_RATE,
     NETCODE_USER_DATA_BYTES, NETCODE_VERSION_INFO,
 };
 
+// Placeholder address for loopback clients, they are never indexed by address
+const LOOPBACK_ADDR: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
+
+// How long the disconnect packets of a disconnected address are expected, clients send them all at once
+const RECENTLY_DISCONNECTED_DURATION: Duration = Duration::from_secs(5);
+
+// Queued clients without a timeout in their connect token are removed after this long without packets
+const QUEUED_CLIENT_TIMEOUT: Duration = Duration::from_secs(15);
+
+// Minimum number of used connect tokens remembered, so small servers can't have their entries replaced quickly
+const MIN_CONNECT_TOKEN_ENTRIES: usize = 2048;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum ConnectionState {
     Disconnected,
     PendingResponse,
+    Queued,
     Connected,
 }
 
@@ -22,8 +42,9 @@ struct Connection {
     confirmed: bool,
     client_id: ClientID,
     state: ConnectionState,
-    send_key: [u8; NETCODE_KEY_BYTES],
-    receive_key: [u8; NETCODE_KEY_BYTES],
+    // Ciphers created from the connect token keys, reused for every packet
+    send_cipher: PacketCipher,
+    receive_cipher: PacketCipher,
     user_data: [u8; NETCODE_USER_DATA_BYTES],
     addr: SocketAddr,
     last_packet_received_time: Duration,
@@ -32,32 +53,122 @@ struct Connection {
     sequence: u64,
     expire_timestamp: u64,
     replay_protection: ReplayProtection,
+    queue_position: u32,
+    client_index: usize,
+    loopback: bool,
 }
 
 #[derive(Debug, Copy, Clone)]
 struct ConnectTokenEntry {
-    time: Duration,
     address: SocketAddr,
     mac: [u8; NETCODE_MAC_BYTES],
 }
 
+/// Connect tokens already used by clients, a token can only be used from one address.
+/// When the table is full the oldest entries are replaced.
+#[derive(Debug)]
+struct ConnectTokenEntries {
+    capacity: usize,
+    entries: HashMap<[u8; NETCODE_MAC_BYTES], SocketAddr>,
+    // Macs in order of insertion
+    order: VecDeque<[u8; NETCODE_MAC_BYTES]>,
+}
+
+impl ConnectTokenEntries {
+    fn capacity_for(max_clients: usize) -> usize {
+        max_clients.saturating_mul(2).max(MIN_CONNECT_TOKEN_ENTRIES)
+    }
+
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+    }
+
+    fn find_or_add(&mut self, new_entry: ConnectTokenEntry) -> bool {
+        if let Some(address) = self.entries.get(&new_entry.mac) {
+            return *address == new_entry.address;
+        }
+
+        while self.order.len() >= self.capacity.max(1) {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(new_entry.mac, new_entry.address);
+        self.order.push_back(new_entry.mac);
+
+        true
+    }
+}
+
+/// Information from a connection request, given to the connection approval callback.
+#[derive(Debug)]
+pub struct ConnectionRequest<'a> {
+    pub client_id: ClientID,
+    pub addr: SocketAddr,
+    /// User data from the connect token.
+    pub user_data: &'a [u8; NETCODE_USER_DATA_BYTES],
+}
+
+/// Result of the connection approval callback.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionApproval {
+    Approved,
+    /// The client receives a connection denied packet, the reason is only logged in the server.
+    Denied {
+        reason: String,
+    },
+}
+
+type ConnectionApprovalFn = Box<dyn FnMut(&ConnectionRequest) -> ConnectionApproval + Send + Sync>;
+
+struct ConnectionApprover(ConnectionApprovalFn);
+
+impl fmt::Debug for ConnectionApprover {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ConnectionApprover")
+    }
+}
+
 /// A server that can generate packets from connect clients, that are encrypted, or process
 /// incoming encrypted packets from clients. The server is agnostic from the transport layer, only
 /// consuming and generating bytes that can be transported in any way desired.
 #[derive(Debug)]
 pub struct NetcodeServer {
-    clients: Box<[Option<Connection>]>,
+    clients: HashMap<ClientID, Connection>,
+    // Connected client ids by address, to find the client of each received packet
+    clients_by_addr: HashMap<SocketAddr, ClientID>,
+    // Client indices released by disconnected clients, the lowest one is reused first
+    free_client_indices: BinaryHeap<Reverse<usize>>,
     pending_clients: HashMap<SocketAddr, Connection>,
-    connect_token_entries: Box<[Option<ConnectTokenEntry>; NETCODE_MAX_CLIENTS * 2]>,
+    connect_token_entries: ConnectTokenEntries,
     protocol_id: u64,
-    connect_key: [u8; NETCODE_KEY_BYTES],
+    private_keys: HashMap<u32, [u8; NETCODE_KEY_BYTES]>,
     max_clients: usize,
+    max_queued_clients: usize,
+    max_pending_clients: usize,
+    send_rate: Duration,
+    cipher_suite: CipherSuite,
+    disconnect_packets: usize,
+    // Addresses of the pending clients waiting for a free slot, in order of arrival
+    queue: VecDeque<SocketAddr>,
     challenge_sequence: u64,
-    challenge_key: [u8; NETCODE_KEY_BYTES],
+    challenge_cipher: PacketCipher,
     public_addresses: Vec<SocketAddr>,
     current_time: Duration,
     global_sequence: u64,
     secure: bool,
+    connection_approver: Option<ConnectionApprover>,
+    packet_filter: PacketFilter,
+    // Expire time of the addresses that disconnected recently, their remaining disconnect packets are not invalid packets
+    recently_disconnected: HashMap<SocketAddr, Duration>,
     out: [u8; NETCODE_MAX_PACKET_BYTES],
 }
 
@@ -78,6 +189,8 @@ pub enum ServerResult<'a, 's> {
         payload: &'s mut [u8],
     },
     /// The client connection has been terminated.
+    /// The payload is a disconnect packet, that should be sent [NetcodeServer::disconnect_packets] times.
+    /// Loopback clients have no payload and an unspecified address.
     ClientDisconnected {
         client_id: ClientID,
         addr: SocketAddr,
@@ -89,18 +202,26 @@ pub enum ServerResult<'a, 's> {
 pub enum ServerAuthentication {
     /// Establishes a safe connection using a private key for encryption. The private key cannot be
     /// shared with the client. Connections are stablished using [crate::token::ConnectToken].
+    /// The private key has the key id 0.
     ///
     /// See also [ClientAuthentication::Secure][crate::ClientAuthentication::Secure]
     Secure { private_key: [u8; NETCODE_KEY_BYTES] },
+    /// Establishes a safe connection accepting tokens encrypted with any of the private keys,
+    /// identified by the key id used when generating the token with [crate::token::ConnectToken::generate_with_key_id].
+    /// Keys can be added or removed later, see [NetcodeServer::add_private_key].
+    SecureWithKeys { private_keys: Vec<(u32, [u8; NETCODE_KEY_BYTES])> },
     /// Establishes unsafe connections with clients, useful for testing and prototyping.
     ///
     /// See also [ClientAuthentication::Unsecure][crate::ClientAuthentication::Unsecure]
     Unsecure,
 }
 
+/// Configuration of the [NetcodeServer], create it with [ServerConfig::new] and change the optional fields afterwards.
+#[non_exhaustive]
 pub struct ServerConfig {
     pub current_time: Duration,
-    /// Maximum numbers of clients that can be connected at a time
+    /// Maximum numbers of clients that can be connected at a time.
+    /// The server keeps track of the last `max_clients * 2` connect tokens used, to deny tokens reused from other addresses.
     pub max_clients: usize,
     /// Unique identifier for this particular game/application.
     /// You can use a hash function with the current version of the game to generate this value
@@ -110,54 +231,92 @@ pub struct ServerConfig {
     pub public_addresses: Vec<SocketAddr>,
     /// Authentication configuration for the server
     pub authentication: ServerAuthentication,
+    /// Interval between keep-alive packets sent to connected clients when no other packet was sent.
+    /// The default is [NETCODE_SEND_RATE][crate::NETCODE_SEND_RATE].
+    pub send_rate: Duration,
+    /// Maximum number of clients in the middle of the connection handshake, more connection requests are ignored.
+    /// The default is [NETCODE_MAX_PENDING_CLIENTS][crate::NETCODE_MAX_PENDING_CLIENTS].
+    pub max_pending_clients: usize,
+    /// Cipher suite used to encrypt the packets, connect tokens generated with another cipher suite are rejected.
+    /// The default is [CipherSuite::ChaCha20Poly1305], the cipher of the netcode standard.
+    pub cipher_suite: CipherSuite,
 }
 
-impl NetcodeServer {
-    pub fn new(config: ServerConfig) -> Self {
-        if config.max_clients > NETCODE_MAX_CLIENTS {
-            // TODO: do we really need to set a max?
-            //       only using for token entries
-            panic!("The max clients allowed is {}", NETCODE_MAX_CLIENTS);
+impl ServerConfig {
+    /// Creates a configuration with the default values for the optional fields.
+    pub fn new(
+        current_time: Duration,
+        max_clients: usize,
+        protocol_id: u64,
+        public_addresses: Vec<SocketAddr>,
+        authentication: ServerAuthentication,
+    ) -> Self {
+        Self {
+            current_time,
+            max_clients,
+            protocol_id,
+            public_addresses,
+            authentication,
+            send_rate: NETCODE_SEND_RATE,
+            max_pending_clients: NETCODE_MAX_PENDING_CLIENTS,
+            cipher_suite: CipherSuite::default(),
         }
-        let challenge_key = generate_random_bytes();
-        let clients = vec![None; config.max_clients].into_boxed_slice();
+    }
+}
 
-        let connect_key = match config.authentication {
-            ServerAuthentication::Unsecure => [0; NETCODE_KEY_BYTES],
-            ServerAuthentication::Secure { private_key } => private_key,
-        };
+impl NetcodeServer {
+    pub fn new(config: ServerConfig) -> Self {
+        // Challenge tokens are only read by the server that created them, they always use the standard cipher
+        let challenge_cipher = PacketCipher::new(&generate_random_bytes(), CipherSuite::ChaCha20Poly1305);
 
         let secure = match config.authentication {
             ServerAuthentication::Unsecure => false,
-            ServerAuthentication::Secure { .. } => true,
+            ServerAuthentication::Secure { .. } | ServerAuthentication::SecureWithKeys { .. } => true,
+        };
+
+        let private_keys = match config.authentication {
+            ServerAuthentication::Unsecure => HashMap::from([(0, [0; NETCODE_KEY_BYTES])]),
+            ServerAuthentication::Secure { private_key } => HashMap::from([(0, private_key)]),
+            ServerAuthentication::SecureWithKeys { private_keys } => private_keys.into_iter().collect(),
         };
 
         Self {
-            clients,
-            connect_token_entries: Box::new([None; NETCODE_MAX_CLIENTS * 2]),
+            clients: HashMap::new(),
+            clients_by_addr: HashMap::new(),
+            free_client_indices: BinaryHeap::new(),
+            connect_token_entries: ConnectTokenEntries::new(ConnectTokenEntries::capacity_for(config.max_clients)),
             pending_clients: HashMap::new(),
             protocol_id: config.protocol_id,
-            connect_key,
+            private_keys,
             max_clients: config.max_clients,
+            max_queued_clients: 0,
+            max_pending_clients: config.max_pending_clients,
+            send_rate: config.send_rate,
+            cipher_suite: config.cipher_suite,
+            disconnect_packets: NETCODE_NUM_DISCONNECT_PACKETS,
+            queue: VecDeque::new(),
             challenge_sequence: 0,
             global_sequence: 0,
-            challenge_key,
+            challenge_cipher,
             public_addresses: config.public_addresses,
             current_time: config.current_time,
             secure,
+            connection_approver: None,
+            packet_filter: PacketFilter::default(),
+            recently_disconnected: HashMap::new(),
             out: [0u8; NETCODE_MAX_PACKET_BYTES],
         }
     }
 
     #[doc(hidden)]
     pub fn __test() -> Self {
-        let config = ServerConfig {
-            current_time: Duration::ZERO,
-            max_clients: 32,
-            protocol_id: 0,
-            public_addresses: vec!["127.0.0.1:0".parse().unwrap()],
-            authentication: ServerAuthentication::Unsecure,
-        };
+        let config = ServerConfig::new(
+            Duration::ZERO,
+            32,
+            0,
+            vec!["127.0.0.1:0".parse().unwrap()],
+            ServerAuthentication::Unsecure,
+        );
         Self::new(config)
     }
 
@@ -169,43 +328,144 @@ impl NetcodeServer {
         self.current_time
     }
 
+    /// Returns the cipher suite used to encrypt the packets, see [ServerConfig::cipher_suite].
+    pub fn cipher_suite(&self) -> CipherSuite {
+        self.cipher_suite
+    }
+
+    /// Sets a callback to approve or deny new connection requests, it's called before the client
+    /// is added as a pending connection, so denied clients never use a slot in the server.
+    /// The callback can be called again for the same client if it resends the connection request.
+    ///
+    /// # Example
+    /// ```
+    /// # use renetcode::{ConnectionApproval, ConnectionRequest};
+    /// # let mut server = renetcode::NetcodeServer::__test();
+    /// const GAME_VERSION: u8 = 3;
+    /// server.set_connection_approval(|request: &ConnectionRequest| {
+    ///     if request.user_data[0] != GAME_VERSION {
+    ///         return ConnectionApproval::Denied { reason: "outdated game version".to_string() };
+    ///     }
+    ///     ConnectionApproval::Approved
+    /// });
+    /// ```
+    pub fn set_connection_approval<F>(&mut self, approval: F)
+    where
+        F: FnMut(&ConnectionRequest) -> ConnectionApproval + Send + Sync + 'static,
+    {
+        self.connection_approver = Some(ConnectionApprover(Box::new(approval)));
+    }
+
+    /// Removes the connection approval callback, all valid connection requests are accepted.
+    pub fn clear_connection_approval(&mut self) {
+        self.connection_approver = None;
+    }
+
+    /// Adds a private key accepted when decrypting connect tokens, replacing any key with the same id.
+    /// Tokens must be generated with the same key id using [crate::token::ConnectToken::generate_with_key_id].
+    ///
+    /// To rotate keys, add the new key to all servers, switch the token generation to the new key id,
+    /// and remove the old key after the tokens generated with it have expired.
+    pub fn add_private_key(&mut self, key_id: u32, private_key: [u8; NETCODE_KEY_BYTES]) {
+        self.private_keys.insert(key_id, private_key);
+    }
+
+    /// Removes a private key, new connection requests with tokens encrypted with it are rejected.
+    /// Returns false if there was no key with this id.
+    pub fn remove_private_key(&mut self, key_id: u32) -> bool {
+        self.private_keys.remove(&key_id).is_some()
+    }
+
+    /// Returns the ids of the private keys accepted by the server.
+    pub fn private_key_ids(&self) -> Vec<u32> {
+        self.private_keys.keys().copied().collect()
+    }
+
+    /// Bans a client id, connection requests with this client id are denied.
+    /// The ban is permanent if no duration is given.
+    /// A connected client is not disconnected, use [NetcodeServer::disconnect] for that.
+    pub fn ban_client_id(&mut self, client_id: ClientID, duration: Option<Duration>) {
+        let expire_time = duration.map(|duration| self.current_time + duration);
+        self.packet_filter.ban_client_id(client_id, expire_time);
+    }
+
+    /// Removes the ban from a client id, returns false if it was not banned.
+    pub fn unban_client_id(&mut self, client_id: ClientID) -> bool {
+        self.packet_filter.unban_client_id(client_id)
+    }
+
+    /// Returns whether a client id is banned.
+    pub fn is_client_id_banned(&self, client_id: ClientID) -> bool {
+        self.packet_filter.is_client_id_banned(client_id, self.current_time)
+    }
+
+    /// Bans an ip address or a range of addresses, packets from unconnected addresses in the range
+    /// are dropped before being decrypted. The ban is permanent if no duration is given.
+    /// Connected clients are not disconnected, use [NetcodeServer::disconnect] for that.
+    ///
+    /// # Example
+    /// ```
+    /// # use std::time::Duration;
+    /// # use renetcode::IpRange;
+    /// # let mut server = renetcode::NetcodeServer::__test();
+    /// let range: IpRange = "10.0.0.0/8".parse().unwrap();
+    /// server.ban_ip_range(range, Some(Duration::from_secs(3600)));
+    /// let ip: std::net::IpAddr = "192.168.0.3".parse().unwrap();
+    /// server.ban_ip_range(ip, None);
+    /// ```
+    pub fn ban_ip_range(&mut self, range: impl Into<IpRange>, duration: Option<Duration>) {
+        let expire_time = duration.map(|duration| self.current_time + duration);
+        self.packet_filter.ban_ip_range(range.into(), expire_time);
+    }
+
+    /// Removes the ban from an ip address or range, returns false if it was not banned.
+    /// The range must be the same used when banning.
+    pub fn unban_ip_range(&mut self, range: impl Into<IpRange>) -> bool {
+        self.packet_filter.unban_ip_range(range.into())
+    }
+
+    /// Returns whether an ip address is banned, directly or by a range.
+    pub fn is_ip_banned(&self, ip: IpAddr) -> bool {
+        self.packet_filter.is_ip_banned(ip, self.current_time)
+    }
+
+    /// Enables rate limiting of connection requests and invalid packets per ip address, or disables it with None.
+    /// Connected clients are not rate limited.
+    pub fn set_rate_limit(&mut self, rate_limit: Option<RateLimitConfig>) {
+        self.packet_filter.set_rate_limit(rate_limit);
+    }
+
+    /// Returns counters of packets dropped by the ban lists and rate limits.
+    pub fn packet_filter_stats(&self) -> PacketFilterStats {
+        self.packet_filter.stats()
+    }
+
     fn find_or_add_connect_token_entry(&mut self, new_entry: ConnectTokenEntry) -> bool {
-        let mut min = Duration::MAX;
-        let mut oldest_entry = 0;
-        let mut empty_entry = false;
-        let mut matching_entry = None;
-        for (i, entry) in self.connect_token_entries.iter().enumerate() {
-            match entry {
-                Some(e) => {
-                    if e.mac == new_entry.mac {
-                        matching_entry = Some(e);
-                    }
-                    if !empty_entry && e.time < min {
-                        oldest_entry = i;
-                        min = e.time;
-                    }
-                }
-                None => {
-                    if !empty_entry {
-                        empty_entry = true;
-                        oldest_entry = i;
-                    }
-                }
-            }
-        }
+        self.connect_token_entries.find_or_add(new_entry)
+    }
 
-        if let Some(entry) = matching_entry {
-            return entry.address == new_entry.address;
+    fn next_client_index(&mut self) -> usize {
+        match self.free_client_indices.pop() {
+            Some(Reverse(client_index)) => client_index,
+            // Without released indices, all indices below the number of clients are in use
+            None => self.clients.len(),
         }
+    }
 
-        self.connect_token_entries[oldest_entry] = Some(new_entry);
-
-        true
+    fn remove_client(&mut self, client_id: ClientID) -> Option<Connection> {
+        let client = self.clients.remove(&client_id)?;
+        if !client.loopback {
+            self.clients_by_addr.remove(&client.addr);
+            self.recently_disconnected
+                .insert(client.addr, self.current_time + RECENTLY_DISCONNECTED_DURATION);
+        }
+        self.free_client_indices.push(Reverse(client.client_index));
+        Some(client)
     }
 
     /// Returns the user data from the connected client.
     pub fn user_data(&self, client_id: ClientID) -> Option<[u8; NETCODE_USER_DATA_BYTES]> {
-        if let Some(client) = find_client_by_id(&self.clients, client_id) {
+        if let Some(client) = self.clients.get(&client_id) {
             return Some(client.user_data);
         }
 
@@ -215,7 +475,7 @@ impl NetcodeServer {
     /// Returns the duration since the connected client last received a packet.
     /// Usefull to detect users that are timing out.
     pub fn time_since_last_received_packet(&self, client_id: ClientID) -> Option<Duration> {
-        if let Some(client) = find_client_by_id(&self.clients, client_id) {
+        if let Some(client) = self.clients.get(&client_id) {
             let time = self.current_time - client.last_packet_received_time;
             return Some(time);
         }
@@ -223,13 +483,73 @@ impl NetcodeServer {
         None
     }
 
-    /// Returns the client address if connected.
+    /// Returns the client address if connected, loopback clients have no address.
     pub fn client_addr(&self, client_id: ClientID) -> Option<SocketAddr> {
-        if let Some(client) = find_client_by_id(&self.clients, client_id) {
-            return Some(client.addr);
+        match self.clients.get(&client_id) {
+            Some(client) if !client.loopback => Some(client.addr),
+            _ => None,
         }
+    }
 
-        None
+    /// Connects a loopback client, a client running in the same process as the server, like the host in a listen server.
+    /// Loopback clients use a client index and count towards the maximum number of clients like any other client,
+    /// but no packets are exchanged with them: they never time out, and payloads are passed in-process with
+    /// [NetcodeServer::process_loopback_payload] and [NetcodeServer::generate_payload_packet] returns [NetcodeError::LoopbackClient].
+    ///
+    /// Returns the client index of the loopback client.
+    pub fn connect_loopback_client(
+        &mut self,
+        client_id: ClientID,
+        user_data: Option<&[u8; NETCODE_USER_DATA_BYTES]>,
+    ) -> Result<usize, NetcodeError> {
+        if self.clients.contains_key(&client_id) {
+            return Err(NetcodeError::ClientAlreadyConnected);
+        }
+        if self.clients.len() >= self.max_clients {
+            return Err(NetcodeError::ServerFull);
+        }
+
+        let client_index = self.next_client_index();
+        let connection = Connection {
+            confirmed: true,
+            client_id,
+            state: ConnectionState::Connected,
+            // Loopback clients never encode packets
+            send_cipher: PacketCipher::new(&[0; NETCODE_KEY_BYTES], self.cipher_suite),
+            receive_cipher: PacketCipher::new(&[0; NETCODE_KEY_BYTES], self.cipher_suite),
+            user_data: user_data.copied().unwrap_or([0; NETCODE_USER_DATA_BYTES]),
+            addr: LOOPBACK_ADDR,
+            last_packet_received_time: self.current_time,
+            last_packet_send_time: self.current_time,
+            timeout_seconds: -1,
+            sequence: 0,
+            expire_timestamp: 0,
+            replay_protection: ReplayProtection::new(),
+            queue_position: 0,
+            client_index,
+            loopback: true,
+        };
+        self.clients.insert(client_id, connection);
+        log::debug!("Loopback Client {} connected with client index {}", client_id, client_index);
+
+        Ok(client_index)
+    }
+
+    /// Returns whether the client is a connected loopback client.
+    pub fn is_loopback_client(&self, client_id: ClientID) -> bool {
+        matches!(self.clients.get(&client_id), Some(client) if client.loopback)
+    }
+
+    /// Process a payload sent in-process by a loopback client.
+    /// Returns [ServerResult::Payload] if the client is a connected loopback client.
+    pub fn process_loopback_payload<'a>(&mut self, client_id: ClientID, payload: &'a [u8]) -> ServerResult<'a, '_> {
+        match self.clients.get_mut(&client_id) {
+            Some(client) if client.loopback => {
+                client.last_packet_received_time = self.current_time;
+                ServerResult::Payload { client_id, payload }
+            }
+            _ => ServerResult::None,
+        }
     }
 
     fn handle_connection_request<'a>(
@@ -253,7 +573,25 @@ impl NetcodeServer {
             return Err(NetcodeError::Expired);
         }
 
-        let connect_token = PrivateConnectToken::decode(&data, self.protocol_id, expire_timestamp, &xnonce, &self.connect_key)?;
+        // Tokens generated without a key id, by other netcode implementations, are decrypted with the key 0
+        let key_id = key_id_from_xnonce(&xnonce);
+        let decoded = match self.private_keys.get(&key_id).or_else(|| self.private_keys.get(&0)) {
+            Some(private_key) => {
+                PrivateConnectToken::decode(&data, self.protocol_id, expire_timestamp, &xnonce, private_key).map_err(Into::into)
+            }
+            None => Err(NetcodeError::UnavailablePrivateKey),
+        };
+        let connect_token = match decoded {
+            Ok(connect_token) => connect_token,
+            Err(e) => {
+                self.packet_filter.invalid_packet(addr.ip(), self.current_time);
+                return Err(e);
+            }
+        };
+
+        if connect_token.cipher_suite != self.cipher_suite {
+            return Err(NetcodeError::InvalidCipherSuite);
+        }
 
         // Skip host list check when unsecure
         if self.secure {
@@ -268,8 +606,8 @@ impl NetcodeServer {
             }
         }
 
-        let addr_already_connected = find_client_mut_by_addr(&mut self.clients, addr).is_some();
-        let id_already_connected = find_client_mut_by_id(&mut self.clients, connect_token.client_id).is_some();
+        let addr_already_connected = self.clients_by_addr.contains_key(&addr);
+        let id_already_connected = self.clients.contains_key(&connect_token.client_id);
         if id_already_connected || addr_already_connected {
             log::debug!(
                 "Connection request denied: client {} already connected (address: {}).",
@@ -279,40 +617,64 @@ impl NetcodeServer {
             return Ok(ServerResult::None);
         }
 
-        if !self.pending_clients.contains_key(&addr) && self.pending_clients.len() >= NETCODE_MAX_PENDING_CLIENTS {
+        if self.packet_filter.is_client_id_banned(connect_token.client_id, self.current_time) {
+            log::debug!(
+                "Connection request denied: client {} is banned (address: {}).",
+                connect_token.client_id,
+                addr
+            );
+            self.packet_filter.client_id_banned();
+            self.pending_clients.remove(&addr);
+            return self.connection_denied(addr, &PacketCipher::new(&connect_token.server_to_client_key, self.cipher_suite));
+        }
+
+        let is_new_request = !self.pending_clients.contains_key(&addr);
+        if let (true, Some(approver)) = (is_new_request, self.connection_approver.as_mut()) {
+            let request = ConnectionRequest {
+                client_id: connect_token.client_id,
+                addr,
+                user_data: &connect_token.user_data,
+            };
+            if let ConnectionApproval::Denied { reason } = (approver.0)(&request) {
+                log::debug!(
+                    "Connection request denied for client {} ({}): {}",
+                    connect_token.client_id,
+                    addr,
+                    reason
+                );
+                return self.connection_denied(addr, &PacketCipher::new(&connect_token.server_to_client_key, self.cipher_suite));
+            }
+        }
+
+        // Clients waiting in the queue are limited separately
+        let num_pending_clients = self.pending_clients.len().saturating_sub(self.queue.len());
+        if is_new_request && num_pending_clients >= self.max_pending_clients {
             log::warn!(
                 "Connection request denied: reached max amount allowed of pending clients ({}).",
-                NETCODE_MAX_PENDING_CLIENTS
+                self.max_pending_clients
             );
             return Ok(ServerResult::None);
         }
 
         let mut mac = [0u8; NETCODE_MAC_BYTES];
         mac.copy_from_slice(&data[NETCODE_CONNECT_TOKEN_PRIVATE_BYTES - NETCODE_MAC_BYTES..]);
-        let connect_token_entry = ConnectTokenEntry {
-            address: addr,
-            time: self.current_time,
-            mac,
-        };
+        let connect_token_entry = ConnectTokenEntry { address: addr, mac };
 
         if !self.find_or_add_connect_token_entry(connect_token_entry) {
             log::warn!("Connection request denied: unable to add connect token entry");
             return Ok(ServerResult::None);
         }
 
-        if self.clients.iter().flatten().count() >= self.max_clients {
+        // Pending clients resend the request until they receive the challenge, their cipher is reused
+        let send_cipher = match self.pending_clients.get(&addr) {
+            Some(pending) => pending.send_cipher.clone(),
+            None => PacketCipher::new(&connect_token.server_to_client_key, self.cipher_suite),
+        };
+
+        let queue_full = self.queue.len() >= self.max_queued_clients;
+        if self.clients.len() >= self.max_clients && queue_full {
             self.pending_clients.remove(&addr);
-            let packet = Packet::ConnectionDenied;
-            let len = packet.encode(
-                &mut self.out,
-                self.protocol_id,
-                Some((self.global_sequence, &connect_token.server_to_client_key)),
-            )?;
-            self.global_sequence += 1;
-            return Ok(ServerResult::PacketToSend {
-                addr,
-                payload: &mut self.out[..len],
-            });
+            return self.connection_denied(addr, &send_cipher);
         }
 
         self.challenge_sequence += 1;
@@ -320,14 +682,10 @@ impl NetcodeServer {
             connect_token.client_id,
             &connect_token.user_data,
             self.challenge_sequence,
-            &self.challenge_key,
+            &self.challenge_cipher,
         )?;
 
-        let len = packet.encode(
-            &mut self.out,
-            self.protocol_id,
-            Some((self.global_sequence, &connect_token.server_to_client_key)),
-        )?;
+        let len = packet.encode(&mut self.out, self.protocol_id, Some((self.global_sequence, &send_cipher)))?;
         self.global_sequence += 1;
 
         log::trace!("Connection request from Client {}", connect_token.client_id);
@@ -340,12 +698,15 @@ impl NetcodeServer {
             last_packet_send_time: self.current_time,
             addr,
             state: ConnectionState::PendingResponse,
-            send_key: connect_token.server_to_client_key,
-            receive_key: connect_token.client_to_server_key,
+            send_cipher,
+            receive_cipher: PacketCipher::new(&connect_token.client_to_server_key, self.cipher_suite),
             timeout_seconds: connect_token.timeout_seconds,
             expire_timestamp,
             user_data: connect_token.user_data,
             replay_protection: ReplayProtection::new(),
+            queue_position: 0,
+            client_index: 0,
+            loopback: false,
         });
         pending.last_packet_received_time = self.current_time;
         pending.last_packet_send_time = self.current_time;
@@ -356,6 +717,17 @@ impl NetcodeServer {
         })
     }
 
+    fn connection_denied<'a>(&mut self, addr: SocketAddr, send_cipher: &PacketCipher) -> Result<ServerResult<'a, '_>, NetcodeError> {
+        let packet = Packet::ConnectionDenied;
+        let len = packet.encode(&mut self.out, self.protocol_id, Some((self.global_sequence, send_cipher)))?;
+        self.global_sequence += 1;
+
+        Ok(ServerResult::PacketToSend {
+            addr,
+            payload: &mut self.out[..len],
+        })
+    }
+
     /// Returns an encoded packet payload to be sent to the client
     pub fn generate_payload_packet<'s>(
         &'s mut self,
@@ -366,9 +738,13 @@ impl NetcodeServer {
             return Err(NetcodeError::PayloadAboveLimit);
         }
 
-        if let Some(client) = find_client_mut_by_id(&mut self.clients, client_id) {
+        if let Some(client) = self.clients.get_mut(&client_id) {
+            if client.loopback {
+                return Err(NetcodeError::LoopbackClient);
+            }
+
             let packet = Packet::Payload(payload);
-            let len = packet.encode(&mut self.out, self.protocol_id, Some((client.sequence, &client.send_key)))?;
+            let len = packet.encode(&mut self.out, self.protocol_id, Some((client.sequence, &client.send_cipher)))?;
             client.sequence += 1;
             client.last_packet_send_time = self.current_time;
 
@@ -396,11 +772,15 @@ impl NetcodeServer {
         }
 
         // Handle connected client
-        if let Some((slot, client)) = find_client_mut_by_addr(&mut self.clients, addr) {
+        if let Some(client) = self
+            .clients_by_addr
+            .get(&addr)
+            .and_then(|client_id| self.clients.get_mut(client_id))
+        {
             let (_, packet) = Packet::decode(
                 buffer,
                 self.protocol_id,
-                Some(&client.receive_key),
+                Some(&client.receive_cipher),
                 Some(&mut client.replay_protection),
             )?;
             log::trace!(
@@ -415,7 +795,7 @@ impl NetcodeServer {
                     Packet::Disconnect => {
                         client.state = ConnectionState::Disconnected;
                         let client_id = client.client_id;
-                        self.clients[slot] = None;
+                        self.remove_client(client_id);
                         log::trace!("Client {} requested to disconnect", client_id);
                         return Ok(ServerResult::ClientDisconnected {
                             client_id,
@@ -446,14 +826,26 @@ impl NetcodeServer {
             }
         }
 
+        // Drop packets from banned or abusive addresses before decrypting them
+        if !self.packet_filter.allow_packet(addr.ip(), self.current_time) {
+            return Ok(ServerResult::None);
+        }
+
         // Handle pending client
         if let Some(pending) = self.pending_clients.get_mut(&addr) {
-            let (_, packet) = Packet::decode(
+            let decoded = Packet::decode(
                 buffer,
                 self.protocol_id,
-                Some(&pending.receive_key),
+                Some(&pending.receive_cipher),
                 Some(&mut pending.replay_protection),
-            )?;
+            );
+            let (_, packet) = match decoded {
+                Ok(decoded) => decoded,
+                Err(e) => {
+                    self.packet_filter.invalid_packet(addr.ip(), self.current_time);
+                    return Err(e);
+                }
+            };
             pending.last_packet_received_time = self.current_time;
             log::trace!("Received packet from pending client ({}): {:?}", addr, packet.packet_type());
             match packet {
@@ -464,35 +856,76 @@ impl NetcodeServer {
                     xnonce,
                     version_info,
                 } => {
+                    if !self.packet_filter.allow_connection_request(addr.ip(), self.current_time) {
+                        return Ok(ServerResult::None);
+                    }
                     return self.handle_connection_request(addr, version_info, protocol_id, expire_timestamp, xnonce, data);
                 }
                 Packet::Response {
                     token_data,
                     token_sequence,
                 } => {
-                    let challenge_token = ChallengeToken::decode(token_data, token_sequence, &self.challenge_key)?;
+                    let challenge_token = match ChallengeToken::decode(token_data, token_sequence, &self.challenge_cipher) {
+                        Ok(challenge_token) => challenge_token,
+                        Err(e) => {
+                            self.packet_filter.invalid_packet(addr.ip(), self.current_time);
+                            return Err(e);
+                        }
+                    };
                     let mut pending = self.pending_clients.remove(&addr).unwrap();
-                    if find_client_slot_by_id(&self.clients, challenge_token.client_id).is_some() {
+                    if self.clients.contains_key(&challenge_token.client_id) {
                         log::debug!(
                             "Ignored connection response for Client {}, already connected.",
                             challenge_token.client_id
                         );
                         return Ok(ServerResult::None);
                     }
-                    match self.clients.iter().position(|c| c.is_none()) {
-                        None => {
-                            let packet = Packet::ConnectionDenied;
-                            let len = packet.encode(&mut self.out, self.protocol_id, Some((self.global_sequence, &pending.send_key)))?;
-                            pending.state = ConnectionState::Disconnected;
+
+                    // Queued clients connect in order of arrival, new clients only connect if nobody is waiting
+                    let is_queued = pending.state == ConnectionState::Queued;
+                    let clients_ahead = if is_queued { pending.queue_position as usize } else { self.queue.len() };
+                    let free_slots = self.max_clients.saturating_sub(self.connected_clients());
+                    let client_index = if free_slots > clients_ahead { Some(self.next_client_index()) } else { None };
+
+                    match client_index {
+                        None if self.max_queued_clients > 0 && (is_queued || self.queue.len() < self.max_queued_clients) => {
+                            if !is_queued {
+                                pending.state = ConnectionState::Queued;
+                                pending.queue_position = self.queue.len() as u32;
+                                self.queue.push_back(addr);
+                                log::debug!(
+                                    "Server is full, Client {} waiting in queue at position {}",
+                                    pending.client_id,
+                                    pending.queue_position
+                                );
+                            }
+
+                            let packet = Packet::QueuePosition {
+                                position: pending.queue_position,
+                            };
+                            let len = packet.encode(&mut self.out, self.protocol_id, Some((self.global_sequence, &pending.send_cipher)))?;
                             self.global_sequence += 1;
                             pending.last_packet_send_time = self.current_time;
+                            self.pending_clients.insert(addr, pending);
                             return Ok(ServerResult::PacketToSend {
                                 addr,
                                 payload: &mut self.out[..len],
                             });
                         }
+                        None => {
+                            if is_queued {
+                                self.queue.retain(|queued_addr| *queued_addr != addr);
+                            }
+                            return self.connection_denied(addr, &pending.send_cipher);
+                        }
                         Some(client_index) => {
+                            if is_queued {
+                                if let Some(index) = self.queue.iter().position(|queued_addr| *queued_addr == addr) {
+                                    self.queue.remove(index);
+                                }
+                            }
                             pending.state = ConnectionState::Connected;
+                            pending.client_index = client_index;
                             pending.user_data = challenge_token.user_data;
                             pending.last_packet_send_time = self.current_time;
 
@@ -500,12 +933,20 @@ impl NetcodeServer {
                                 max_clients: self.max_clients as u32,
                                 client_index: client_index as u32,
                             };
-                            let len = packet.encode(&mut self.out, self.protocol_id, Some((pending.sequence, &pending.send_key)))?;
+                            let len = match packet.encode(&mut self.out, self.protocol_id, Some((pending.sequence, &pending.send_cipher))) {
+                                Ok(len) => len,
+                                Err(e) => {
+                                    // The client was not added, release its index
+                                    self.free_client_indices.push(Reverse(client_index));
+                                    return Err(e);
+                                }
+                            };
                             pending.sequence += 1;
 
                             let client_id: ClientID = pending.client_id;
                             let user_data: [u8; NETCODE_USER_DATA_BYTES] = pending.user_data;
-                            self.clients[client_index] = Some(pending);
+                            self.clients_by_addr.insert(addr, client_id);
+                            self.clients.insert(client_id, pending);
 
                             return Ok(ServerResult::ClientConnected {
                                 client_id,
@@ -516,12 +957,34 @@ impl NetcodeServer {
                         }
                     }
                 }
+                Packet::Disconnect => {
+                    // Clients can leave the waiting queue
+                    if self.pending_clients[&addr].state == ConnectionState::Queued {
+                        log::debug!("Client {} left the waiting queue", self.pending_clients[&addr].client_id);
+                        self.pending_clients.remove(&addr);
+                        self.queue.retain(|queued_addr| *queued_addr != addr);
+                        self.recently_disconnected
+                            .insert(addr, self.current_time + RECENTLY_DISCONNECTED_DURATION);
+                    }
+                    return Ok(ServerResult::None);
+                }
                 _ => return Ok(ServerResult::None),
             }
         }
 
         // Handle new client
-        let (_, packet) = Packet::decode(buffer, self.protocol_id, None, None)?;
+        let is_disconnect = Packet::is_disconnect(buffer);
+        let (_, packet) = match Packet::decode(buffer, self.protocol_id, None, None) {
+            Ok(decoded) => decoded,
+            // The redundant disconnect packets from clients that just disconnected are not abuse
+            Err(NetcodeError::UnavailablePrivateKey) if is_disconnect && self.recently_disconnected.contains_key(&addr) => {
+                return Ok(ServerResult::None)
+            }
+            Err(e) => {
+                self.packet_filter.invalid_packet(addr.ip(), self.current_time);
+                return Err(e);
+            }
+        };
         match packet {
             Packet::ConnectionRequest {
                 data,
@@ -529,22 +992,26 @@ impl NetcodeServer {
                 expire_timestamp,
                 xnonce,
                 version_info,
-            } => self.handle_connection_request(addr, version_info, protocol_id, expire_timestamp, xnonce, data),
+            } => {
+                if !self.packet_filter.allow_connection_request(addr.ip(), self.current_time) {
+                    return Ok(ServerResult::None);
+                }
+                self.handle_connection_request(addr, version_info, protocol_id, expire_timestamp, xnonce, data)
+            }
             _ => unreachable!("Decoding packet without key can only return ConnectionRequest packets"),
         }
     }
 
+    /// Returns the client indices of the connected clients, in ascending order.
     pub fn clients_slot(&self) -> Vec<usize> {
-        self.clients
-            .iter()
-            .enumerate()
-            .filter_map(|(index, slot)| if slot.is_some() { Some(index) } else { None })
-            .collect()
+        let mut slots: Vec<usize> = self.clients.values().map(|client| client.client_index).collect();
+        slots.sort_unstable();
+        slots
     }
 
     /// Returns the ids from the connected clients (iterator).
     pub fn clients_id_iter(&self) -> impl Iterator<Item = ClientID> + '_ {
-        self.clients.iter().filter_map(|slot| slot.as_ref().map(|client| client.client_id))
+        self.clients.keys().copied()
     }
 
     /// Returns the ids from the connected clients.
@@ -563,25 +1030,84 @@ impl NetcodeServer {
     /// does not disconnect clients. So [`NetcodeServer::connected_clients()`] can return a higher value than [`NetcodeServer::max_clients()`].
     pub fn set_max_clients(&mut self, max_clients: usize) {
         self.max_clients = max_clients;
+        self.connect_token_entries
+            .set_capacity(ConnectTokenEntries::capacity_for(max_clients));
+    }
+
+    /// Sets the maximum number of clients that can wait in the queue when the server is full, 0 disables the queue.
+    /// Queued clients receive their position periodically and connect in order of arrival when a slot is available,
+    /// when the queue is also full new clients are denied.
+    ///
+    /// The queue uses a packet that is not part of the netcode standard, only enable it with renetcode clients.
+    pub fn set_max_queued_clients(&mut self, max_queued_clients: usize) {
+        self.max_queued_clients = max_queued_clients;
+    }
+
+    /// Returns the maximum number of clients that can wait in the queue.
+    pub fn max_queued_clients(&self) -> usize {
+        self.max_queued_clients
+    }
+
+    /// Returns the current number of clients waiting in the queue.
+    pub fn queued_clients(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Sets how many times the disconnect packet from [ServerResult::ClientDisconnected] should be sent to the client,
+    /// so the client is notified even if some packets are lost. The minimum is 1.
+    /// The default is [NETCODE_NUM_DISCONNECT_PACKETS][crate::NETCODE_NUM_DISCONNECT_PACKETS].
+    pub fn set_disconnect_packets(&mut self, disconnect_packets: usize) {
+        self.disconnect_packets = disconnect_packets.max(1);
+    }
+
+    /// Returns how many times the disconnect packet should be sent to the client.
+    /// The copies are identical, the client ignores them after the first one arrives.
+    pub fn disconnect_packets(&self) -> usize {
+        self.disconnect_packets
     }
 
     /// Returns current number of clients connected.
     pub fn connected_clients(&self) -> usize {
-        self.clients.iter().filter(|slot| slot.is_some()).count()
+        self.clients.len()
     }
 
-    /// Advance the server current time, and remove any pending connections that have expired.
+    /// Advance the server current time, and remove any pending connections and bans that have expired.
     pub fn update(&mut self, duration: Duration) {
         self.current_time += duration;
+        self.packet_filter.update(self.current_time);
+        let current_time = self.current_time;
+        self.recently_disconnected.retain(|_, expire_time| *expire_time > current_time);
 
         for client in self.pending_clients.values_mut() {
-            if self.current_time.as_secs() > client.expire_timestamp {
+            if client.state == ConnectionState::Queued {
+                // The connect token was already validated, queued clients are only removed when they time out
+                let timeout = match client.timeout_seconds {
+                    timeout_seconds if timeout_seconds > 0 => Duration::from_secs(timeout_seconds as u64),
+                    _ => QUEUED_CLIENT_TIMEOUT,
+                };
+                if client.last_packet_received_time + timeout < self.current_time {
+                    log::debug!("Queued Client {} disconnected, connection timed out.", client.client_id);
+                    client.state = ConnectionState::Disconnected;
+                }
+            } else if self.current_time.as_secs() > client.expire_timestamp {
                 log::debug!("Pending Client {} disconnected, connection token expired.", client.client_id);
                 client.state = ConnectionState::Disconnected;
             }
         }
 
         self.pending_clients.retain(|_, c| c.state != ConnectionState::Disconnected);
+
+        // Update the positions of the clients waiting in the queue
+        let pending_clients = &mut self.pending_clients;
+        let mut position = 0;
+        self.queue.retain(|addr| match pending_clients.get_mut(addr) {
+            Some(client) if client.state == ConnectionState::Queued => {
+                client.queue_position = position;
+                position += 1;
+                true
+            }
+            _ => false,
+        });
     }
 
     /// Updates the client, returns a ServerResult.
@@ -599,12 +1125,12 @@ impl NetcodeServer {
     /// # fn send_to(p: &[u8], addr: std::net::SocketAddr) {}
     /// ```
     pub fn update_client(&mut self, client_id: ClientID) -> ServerResult<'_, '_> {
-        let slot = match find_client_slot_by_id(&self.clients, client_id) {
-            None => return ServerResult::None,
-            Some(slot) => slot,
-        };
+        if let Some(client) = self.clients.get_mut(&client_id) {
+            // Loopback clients have no connection to keep alive
+            if client.loopback {
+                return ServerResult::None;
+            }
 
-        if let Some(client) = &mut self.clients[slot] {
             let connection_timed_out = client.timeout_seconds > 0
                 && (client.last_packet_received_time + Duration::from_secs(client.timeout_seconds as u64) < self.current_time);
             if connection_timed_out {
@@ -614,12 +1140,10 @@ impl NetcodeServer {
 
             if client.state == ConnectionState::Disconnected {
                 let packet = Packet::Disconnect;
-                let sequence = client.sequence;
-                let send_key = client.send_key;
+                let client = self.remove_client(client_id).unwrap();
                 let addr = client.addr;
-                self.clients[slot] = None;
 
-                let len = match packet.encode(&mut self.out, self.protocol_id, Some((sequence, &send_key))) {
+                let len = match packet.encode(&mut self.out, self.protocol_id, Some((client.sequence, &client.send_cipher))) {
                     Err(e) => {
                         log::error!("Failed to encode disconnect packet: {}", e);
                         return ServerResult::ClientDisconnected {
@@ -638,13 +1162,13 @@ impl NetcodeServer {
                 };
             }
 
-            if client.last_packet_send_time + NETCODE_SEND_RATE <= self.current_time {
+            if client.last_packet_send_time + self.send_rate <= self.current_time {
                 let packet = Packet::KeepAlive {
-                    client_index: slot as u32,
+                    client_index: client.client_index as u32,
                     max_clients: self.max_clients as u32,
                 };
 
-                let len = match packet.encode(&mut self.out, self.protocol_id, Some((client.sequence, &client.send_key))) {
+                let len = match packet.encode(&mut self.out, self.protocol_id, Some((client.sequence, &client.send_cipher))) {
                     Err(e) => {
                         log::error!("Failed to encode keep alive packet: {}", e);
                         return ServerResult::None;
@@ -664,19 +1188,28 @@ impl NetcodeServer {
     }
 
     pub fn is_client_connected(&self, client_id: ClientID) -> bool {
-        find_client_slot_by_id(&self.clients, client_id).is_some()
+        self.clients.contains_key(&client_id)
     }
 
     /// Disconnect an client and returns its address and a disconnect packet to be sent to them.
+    /// Loopback clients are disconnected without a packet.
     // TODO: we can return Result<PacketToSend, NetcodeError>
     //       but the library user would need to be aware that he has to run
     //       the same code as Result::ClientDisconnected
     pub fn disconnect(&mut self, client_id: ClientID) -> ServerResult<'_, '_> {
-        if let Some(slot) = find_client_slot_by_id(&self.clients, client_id) {
-            let client = self.clients[slot].take().unwrap();
+        if let Some(client) = self.remove_client(client_id) {
+            if client.loopback {
+                log::debug!("Loopback Client {} disconnected", client_id);
+                return ServerResult::ClientDisconnected {
+                    client_id,
+                    addr: client.addr,
+                    payload: None,
+                };
+            }
+
             let packet = Packet::Disconnect;
 
-            let len = match packet.encode(&mut self.out, self.protocol_id, Some((client.sequence, &client.send_key))) {
+            let len = match packet.encode(&mut self.out, self.protocol_id, Some((client.sequence, &client.send_cipher))) {
                 Err(e) => {
                     log::error!("Failed to encode disconnect packet: {}", e);
                     return ServerResult::ClientDisconnected {
@@ -698,31 +1231,9 @@ impl NetcodeServer {
     }
 }
 
-fn find_client_mut_by_id(clients: &mut [Option<Connection>], client_id: ClientID) -> Option<&mut Connection> {
-    clients.iter_mut().flatten().find(|c| c.client_id == client_id)
-}
-
-fn find_client_by_id(clients: &[Option<Connection>], client_id: ClientID) -> Option<&Connection> {
-    clients.iter().flatten().find(|c| c.client_id == client_id)
-}
-
-fn find_client_slot_by_id(clients: &[Option<Connection>], client_id: ClientID) -> Option<usize> {
-    clients.iter().enumerate().find_map(|(i, c)| match c {
-        Some(c) if c.client_id == client_id => Some(i),
-        _ => None,
-    })
-}
-
-fn find_client_mut_by_addr(clients: &mut [Option<Connection>], addr: SocketAddr) -> Option<(usize, &mut Connection)> {
-    clients.iter_mut().enumerate().find_map(|(i, c)| match c {
-        Some(c) if c.addr == addr => Some((i, c)),
-        _ => None,
-    })
-}
-
 #[cfg(test)]
 mod tests {
-    use crate::{client::NetcodeClient, token::ConnectToken, ClientAuthentication};
+    use crate::{client::NetcodeClient, token::ConnectToken, ClientAuthentication, DisconnectReason};
 
     use super::*;
 
@@ -730,13 +1241,13 @@ mod tests {
     const TEST_PROTOCOL_ID: u64 = 7;
 
     fn new_server() -> NetcodeServer {
-        let config = ServerConfig {
-            current_time: Duration::ZERO,
-            max_clients: 16,
-            protocol_id: TEST_PROTOCOL_ID,
-            public_addresses: vec!["127.0.0.1:5000".parse().unwrap()],
-            authentication: ServerAuthentication::Secure { private_key: *TEST_KEY },
-        };
+        let config = ServerConfig::new(
+            Duration::ZERO,
+            16,
+            TEST_PROTOCOL_ID,
+            vec!["127.0.0.1:5000".parse().unwrap()],
+            ServerAuthentication::Secure { private_key: *TEST_KEY },
+        );
         NetcodeServer::new(config)
     }
 
@@ -837,13 +1348,481 @@ mod tests {
         assert!(!server.is_client_connected(client_id));
     }
 
+    #[test]
+    fn connection_approval() {
+        let mut server = new_server();
+        server.set_connection_approval(|request: &ConnectionRequest| {
+            if request.user_data[0] == 0 {
+                return ConnectionApproval::Denied {
+                    reason: "banned".to_string(),
+                };
+            }
+            ConnectionApproval::Approved
+        });
+
+        let mut connect_client = |client_id: u64, user_data: [u8; NETCODE_USER_DATA_BYTES]| {
+            let connect_token = ConnectToken::generate(
+                Duration::ZERO,
+                TEST_PROTOCOL_ID,
+                3,
+                client_id,
+                5,
+                server.addresses(),
+                Some(&user_data),
+                TEST_KEY,
+            )
+            .unwrap();
+            let client_addr = SocketAddr::from(([127, 0, 0, 1], 3000 + client_id as u16));
+            let mut client = NetcodeClient::new(Duration::ZERO, ClientAuthentication::Secure { connect_token }).unwrap();
+            let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+            match server.process_packet(client_addr, client_packet) {
+                ServerResult::PacketToSend { payload, .. } => {
+                    client.process_packet(payload);
+                }
+                _ => unreachable!(),
+            };
+            client
+        };
+
+        let approved_client = connect_client(1, [1; NETCODE_USER_DATA_BYTES]);
+        assert!(!approved_client.is_disconnected());
+
+        let denied_client = connect_client(2, [0; NETCODE_USER_DATA_BYTES]);
+        assert_eq!(denied_client.disconnect_reason(), Some(DisconnectReason::ConnectionDenied));
+        assert_eq!(server.pending_clients.len(), 1);
+    }
+
+    #[test]
+    fn banned_clients() {
+        let mut server = new_server();
+        let client_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        let new_client = |client_id: u64| {
+            let connect_token = ConnectToken::generate(
+                Duration::ZERO,
+                TEST_PROTOCOL_ID,
+                30,
+                client_id,
+                5,
+                vec!["127.0.0.1:5000".parse().unwrap()],
+                None,
+                TEST_KEY,
+            )
+            .unwrap();
+            NetcodeClient::new(Duration::ZERO, ClientAuthentication::Secure { connect_token }).unwrap()
+        };
+
+        // Clients that are not banned can connect
+        let mut client = new_client(3);
+        let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+        match server.process_packet("127.0.0.1:3001".parse().unwrap(), client_packet) {
+            ServerResult::PacketToSend { payload, .. } => client.process_packet(payload),
+            _ => unreachable!(),
+        };
+        assert!(!client.is_disconnected());
+
+        server.ban_client_id(1, Some(Duration::from_secs(10)));
+        let mut client = new_client(1);
+        let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+        match server.process_packet(client_addr, client_packet) {
+            ServerResult::PacketToSend { payload, .. } => client.process_packet(payload),
+            _ => unreachable!(),
+        };
+        assert_eq!(client.disconnect_reason(), Some(DisconnectReason::ConnectionDenied));
+
+        server.ban_ip_range(client_addr.ip(), None);
+        let mut client = new_client(2);
+        let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+        assert_eq!(server.process_packet(client_addr, client_packet), ServerResult::None);
+        assert_eq!(server.packet_filter_stats().banned_packets, 2);
+
+        assert!(server.unban_ip_range(client_addr.ip()));
+        server.update(Duration::from_secs(10));
+        assert!(!server.is_client_id_banned(1));
+        let mut client = new_client(1);
+        let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+        match server.process_packet(client_addr, client_packet) {
+            ServerResult::PacketToSend { payload, .. } => client.process_packet(payload),
+            _ => unreachable!(),
+        };
+        assert!(!client.is_disconnected());
+    }
+
+    #[test]
+    fn rate_limit_invalid_packets() {
+        let mut server = new_server();
+        server.set_rate_limit(Some(RateLimitConfig {
+            invalid_packets_burst: 2,
+            ..Default::default()
+        }));
+        let client_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+
+        let connect_token = ConnectToken::generate(
+            Duration::ZERO,
+            TEST_PROTOCOL_ID,
+            3,
+            1,
+            5,
+            server.addresses(),
+            None,
+            b"an invalid key for this server. ",
+        )
+        .unwrap();
+        for _ in 0..3 {
+            let mut client = NetcodeClient::new(
+                Duration::ZERO,
+                ClientAuthentication::Secure {
+                    connect_token: connect_token.clone(),
+                },
+            )
+            .unwrap();
+            let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+            assert_eq!(server.process_packet(client_addr, client_packet), ServerResult::None);
+        }
+
+        let stats = server.packet_filter_stats();
+        assert_eq!(stats.invalid_packets, 2);
+        assert_eq!(stats.rate_limited_invalid_packets, 1);
+    }
+
+    #[test]
+    fn waiting_queue() {
+        let mut server = new_server();
+        server.set_max_clients(1);
+        server.set_max_queued_clients(1);
+
+        let new_client = |client_id: u64| {
+            let connect_token = ConnectToken::generate(
+                Duration::ZERO,
+                TEST_PROTOCOL_ID,
+                30,
+                client_id,
+                5,
+                vec!["127.0.0.1:5000".parse().unwrap()],
+                None,
+                TEST_KEY,
+            )
+            .unwrap();
+            NetcodeClient::new(Duration::ZERO, ClientAuthentication::Secure { connect_token }).unwrap()
+        };
+        // Sends the connection request and the challenge response
+        let handshake = |server: &mut NetcodeServer, client: &mut NetcodeClient, addr: SocketAddr| {
+            for _ in 0..2 {
+                let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+                match server.process_packet(addr, client_packet) {
+                    ServerResult::PacketToSend { payload, .. } | ServerResult::ClientConnected { payload, .. } => {
+                        client.process_packet(payload);
+                    }
+                    _ => unreachable!(),
+                }
+                if client.is_disconnected() {
+                    return;
+                }
+            }
+        };
+
+        let addr_1: SocketAddr = "127.0.0.1:3001".parse().unwrap();
+        let mut client_1 = new_client(1);
+        handshake(&mut server, &mut client_1, addr_1);
+        assert!(client_1.is_connected());
+
+        let addr_2: SocketAddr = "127.0.0.1:3002".parse().unwrap();
+        let mut client_2 = new_client(2);
+        handshake(&mut server, &mut client_2, addr_2);
+        assert!(client_2.is_connecting());
+        assert_eq!(client_2.queue_position(), Some(0));
+        assert_eq!(server.queued_clients(), 1);
+
+        // Queue is full
+        let addr_3: SocketAddr = "127.0.0.1:3003".parse().unwrap();
+        let mut client_3 = new_client(3);
+        handshake(&mut server, &mut client_3, addr_3);
+        assert_eq!(client_3.disconnect_reason(), Some(DisconnectReason::ConnectionDenied));
+
+        // Queued client is connected when a slot is available
+        server.disconnect(1);
+        server.update(NETCODE_SEND_RATE);
+        let (client_packet, _) = client_2.update(NETCODE_SEND_RATE).unwrap();
+        match server.process_packet(addr_2, client_packet) {
+            ServerResult::ClientConnected { client_id, payload, .. } => {
+                assert_eq!(client_id, 2);
+                client_2.process_packet(payload);
+            }
+            _ => unreachable!(),
+        }
+        assert!(client_2.is_connected());
+        assert_eq!(server.queued_clients(), 0);
+    }
+
+    #[test]
+    fn queued_client_without_timeout() {
+        let mut server = new_server();
+        server.set_max_clients(1);
+        server.set_max_queued_clients(1);
+        server.connect_loopback_client(1, None).unwrap();
+
+        let connect_token = ConnectToken::generate(
+            Duration::ZERO,
+            TEST_PROTOCOL_ID,
+            300,
+            2,
+            -1,
+            vec!["127.0.0.1:5000".parse().unwrap()],
+            None,
+            TEST_KEY,
+        )
+        .unwrap();
+        let mut client = NetcodeClient::new(Duration::ZERO, ClientAuthentication::Secure { connect_token }).unwrap();
+        let addr: SocketAddr = "127.0.0.1:3002".parse().unwrap();
+        for _ in 0..2 {
+            let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+            match server.process_packet(addr, client_packet) {
+                ServerResult::PacketToSend { payload, .. } => {
+                    client.process_packet(payload);
+                }
+                _ => unreachable!(),
+            }
+        }
+        assert_eq!(client.queue_position(), Some(0));
+        assert_eq!(server.queued_clients(), 1);
+
+        // Queued clients that stop sending packets are removed even without a timeout
+        server.update(QUEUED_CLIENT_TIMEOUT);
+        assert_eq!(server.queued_clients(), 1);
+        server.update(Duration::from_secs(1));
+        assert_eq!(server.queued_clients(), 0);
+    }
+
+    #[test]
+    fn loopback_client() {
+        let mut server = new_server();
+        server.set_max_clients(2);
+
+        assert_eq!(server.connect_loopback_client(10, Some(&[7; NETCODE_USER_DATA_BYTES])).unwrap(), 0);
+        assert!(matches!(
+            server.connect_loopback_client(10, None),
+            Err(NetcodeError::ClientAlreadyConnected)
+        ));
+        assert!(server.is_loopback_client(10));
+        assert!(server.is_client_connected(10));
+        assert_eq!(server.clients_id(), vec![10]);
+        assert_eq!(server.client_addr(10), None);
+        assert_eq!(server.user_data(10), Some([7; NETCODE_USER_DATA_BYTES]));
+
+        // Payloads are exchanged in-process
+        assert!(matches!(
+            server.generate_payload_packet(10, &[1, 2]),
+            Err(NetcodeError::LoopbackClient)
+        ));
+        assert_eq!(
+            server.process_loopback_payload(10, &[3, 4]),
+            ServerResult::Payload {
+                client_id: 10,
+                payload: &[3, 4]
+            }
+        );
+
+        // Loopback clients never time out
+        server.update(Duration::from_secs(60));
+        assert_eq!(server.update_client(10), ServerResult::None);
+        assert!(server.is_client_connected(10));
+
+        // Loopback clients count towards the maximum number of clients
+        assert_eq!(server.connect_loopback_client(11, None).unwrap(), 1);
+        assert!(matches!(server.connect_loopback_client(12, None), Err(NetcodeError::ServerFull)));
+        assert_eq!(server.connected_clients(), 2);
+
+        match server.disconnect(10) {
+            ServerResult::ClientDisconnected { client_id, payload, .. } => {
+                assert_eq!(client_id, 10);
+                assert!(payload.is_none());
+            }
+            _ => unreachable!(),
+        }
+        assert!(!server.is_loopback_client(10));
+        assert_eq!(server.connect_loopback_client(12, None).unwrap(), 0);
+    }
+
+    #[test]
+    fn redundant_disconnect_packets() {
+        let mut server = new_server();
+        assert_eq!(server.disconnect_packets(), NETCODE_NUM_DISCONNECT_PACKETS);
+        server.set_disconnect_packets(0);
+        assert_eq!(server.disconnect_packets(), 1);
+
+        let connect_token = ConnectToken::generate(
+            Duration::ZERO,
+            TEST_PROTOCOL_ID,
+            30,
+            1,
+            5,
+            vec!["127.0.0.1:5000".parse().unwrap()],
+            None,
+            TEST_KEY,
+        )
+        .unwrap();
+        let client_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        let mut client = NetcodeClient::new(Duration::ZERO, ClientAuthentication::Secure { connect_token }).unwrap();
+        for _ in 0..2 {
+            let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+            match server.process_packet(client_addr, client_packet) {
+                ServerResult::PacketToSend { payload, .. } | ServerResult::ClientConnected { payload, .. } => {
+                    client.process_packet(payload);
+                }
+                _ => unreachable!(),
+            }
+        }
+        assert!(client.is_connected());
+
+        let (_, disconnect_packet) = client.disconnect().unwrap();
+        let disconnect_packet = disconnect_packet.to_vec();
+        for _ in 0..client.disconnect_packets() {
+            match server.process_packet(client_addr, &mut disconnect_packet.clone()) {
+                ServerResult::ClientDisconnected { client_id, .. } => assert_eq!(client_id, 1),
+                ServerResult::None => assert!(!server.is_client_connected(1)),
+                _ => unreachable!(),
+            }
+        }
+        assert_eq!(server.connected_clients(), 0);
+        // The copies received after the disconnection are not counted as invalid packets
+        assert_eq!(server.packet_filter_stats().invalid_packets, 0);
+
+        // Only disconnect packets from the disconnected address are expected
+        let other_addr: SocketAddr = "127.0.0.1:3001".parse().unwrap();
+        assert_eq!(
+            server.process_packet(other_addr, &mut disconnect_packet.clone()),
+            ServerResult::None
+        );
+        assert_eq!(server.packet_filter_stats().invalid_packets, 1);
+
+        server.update(RECENTLY_DISCONNECTED_DURATION);
+        assert_eq!(
+            server.process_packet(client_addr, &mut disconnect_packet.clone()),
+            ServerResult::None
+        );
+        assert_eq!(server.packet_filter_stats().invalid_packets, 2);
+    }
+
+    #[test]
+    fn private_key_rotation() {
+        const NEW_KEY: &[u8; NETCODE_KEY_BYTES] = b"another example very secret key.";
+        let config = ServerConfig::new(
+            Duration::ZERO,
+            16,
+            TEST_PROTOCOL_ID,
+            vec!["127.0.0.1:5000".parse().unwrap()],
+            ServerAuthentication::SecureWithKeys {
+                private_keys: vec![(1, *TEST_KEY)],
+            },
+        );
+        let mut server = NetcodeServer::new(config);
+        server.add_private_key(2, *NEW_KEY);
+
+        let mut try_connect = |client_id: u64, key_id: u32, private_key: &[u8; NETCODE_KEY_BYTES]| {
+            let connect_token = ConnectToken::generate_with_key_id(
+                Duration::ZERO,
+                TEST_PROTOCOL_ID,
+                30,
+                client_id,
+                5,
+                server.addresses(),
+                None,
+                key_id,
+                private_key,
+            )
+            .unwrap();
+            assert_eq!(connect_token.key_id(), key_id);
+            let client_addr = SocketAddr::from(([127, 0, 0, 1], 3000 + client_id as u16));
+            let mut client = NetcodeClient::new(Duration::ZERO, ClientAuthentication::Secure { connect_token }).unwrap();
+            let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+            matches!(server.process_packet(client_addr, client_packet), ServerResult::PacketToSend { .. })
+        };
+
+        assert!(try_connect(1, 1, TEST_KEY));
+        assert!(try_connect(2, 2, NEW_KEY));
+        // Wrong key for the key id
+        assert!(!try_connect(3, 1, NEW_KEY));
+
+        assert!(server.remove_private_key(1));
+        assert_eq!(server.private_key_ids(), vec![2]);
+        let connect_token =
+            ConnectToken::generate_with_key_id(Duration::ZERO, TEST_PROTOCOL_ID, 30, 4, 5, server.addresses(), None, 1, TEST_KEY).unwrap();
+        let mut client = NetcodeClient::new(Duration::ZERO, ClientAuthentication::Secure { connect_token }).unwrap();
+        let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+        assert_eq!(
+            server.process_packet("127.0.0.1:4000".parse().unwrap(), client_packet),
+            ServerResult::None
+        );
+    }
+
+    #[test]
+    fn cipher_suites() {
+        let mut config = ServerConfig::new(
+            Duration::ZERO,
+            16,
+            TEST_PROTOCOL_ID,
+            vec!["127.0.0.1:5000".parse().unwrap()],
+            ServerAuthentication::Secure { private_key: *TEST_KEY },
+        );
+        config.cipher_suite = CipherSuite::Aes256Gcm;
+        let mut server = NetcodeServer::new(config);
+        assert_eq!(server.cipher_suite(), CipherSuite::Aes256Gcm);
+
+        let new_client = |client_id: u64, cipher_suite: CipherSuite| {
+            let connect_token = ConnectToken::generate_with_cipher_suite(
+                Duration::ZERO,
+                TEST_PROTOCOL_ID,
+                30,
+                client_id,
+                5,
+                server.addresses(),
+                None,
+                0,
+                cipher_suite,
+                TEST_KEY,
+            )
+            .unwrap();
+            NetcodeClient::new(Duration::ZERO, ClientAuthentication::Secure { connect_token }).unwrap()
+        };
+        let mut chacha_client = new_client(1, CipherSuite::ChaCha20Poly1305);
+        let mut aes_client = new_client(2, CipherSuite::Aes256Gcm);
+
+        // Tokens with another cipher suite are rejected
+        let (client_packet, _) = chacha_client.update(Duration::ZERO).unwrap();
+        assert_eq!(
+            server.process_packet("127.0.0.1:3001".parse().unwrap(), client_packet),
+            ServerResult::None
+        );
+
+        let client_addr: SocketAddr = "127.0.0.1:3002".parse().unwrap();
+        for _ in 0..2 {
+            let (client_packet, _) = aes_client.update(Duration::ZERO).unwrap();
+            match server.process_packet(client_addr, client_packet) {
+                ServerResult::PacketToSend { payload, .. } | ServerResult::ClientConnected { payload, .. } => {
+                    aes_client.process_packet(payload);
+                }
+                _ => unreachable!(),
+            }
+        }
+        assert!(aes_client.is_connected());
+
+        let (_, packet) = server.generate_payload_packet(2, &[7u8; 300]).unwrap();
+        assert_eq!(aes_client.process_packet(packet).unwrap(), [7u8; 300]);
+        let (_, packet) = aes_client.generate_payload_packet(&[3u8; 300]).unwrap();
+        match server.process_packet(client_addr, packet) {
+            ServerResult::Payload { client_id, payload } => {
+                assert_eq!(client_id, 2);
+                assert_eq!(payload, [3u8; 300]);
+            }
+            _ => unreachable!(),
+        }
+    }
+
     #[test]
     fn connect_token_already_used() {
         let mut server = new_server();
 
         let client_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
         let mut connect_token = ConnectTokenEntry {
-            time: Duration::ZERO,
             address: client_addr,
             mac: generate_random_bytes(),
         };
@@ -856,4 +1835,99 @@ mod tests {
         // Don't allow same token with different address
         assert!(!server.find_or_add_connect_token_entry(connect_token));
     }
+
+    #[test]
+    fn connect_token_entries_capacity() {
+        let mut server = new_server();
+        // Small servers still remember the minimum number of entries
+        server.set_max_clients(1);
+        assert_eq!(server.connect_token_entries.capacity, MIN_CONNECT_TOKEN_ENTRIES);
+        server.set_max_clients(MIN_CONNECT_TOKEN_ENTRIES);
+        assert_eq!(server.connect_token_entries.capacity, MIN_CONNECT_TOKEN_ENTRIES * 2);
+        server.set_max_clients(1);
+
+        let client_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        let other_addr: SocketAddr = "127.0.0.1:3001".parse().unwrap();
+        let entries: Vec<ConnectTokenEntry> = (0..MIN_CONNECT_TOKEN_ENTRIES + 1)
+            .map(|_| ConnectTokenEntry {
+                address: client_addr,
+                mac: generate_random_bytes(),
+            })
+            .collect();
+        for entry in entries.iter() {
+            assert!(server.find_or_add_connect_token_entry(*entry));
+        }
+
+        // The oldest entry was replaced
+        assert!(server.find_or_add_connect_token_entry(ConnectTokenEntry {
+            address: other_addr,
+            ..entries[0]
+        }));
+        assert!(!server.find_or_add_connect_token_entry(ConnectTokenEntry {
+            address: other_addr,
+            ..entries[MIN_CONNECT_TOKEN_ENTRIES]
+        }));
+    }
+
+    #[test]
+    fn many_clients() {
+        const MAX_CLIENTS: usize = 1100;
+        let mut server = new_server();
+        server.set_max_clients(MAX_CLIENTS);
+
+        let connect_client = |server: &mut NetcodeServer, client_id: u64| {
+            let connect_token = ConnectToken::generate(
+                Duration::ZERO,
+                TEST_PROTOCOL_ID,
+                30,
+                client_id,
+                5,
+                vec!["127.0.0.1:5000".parse().unwrap()],
+                None,
+                TEST_KEY,
+            )
+            .unwrap();
+            let addr = SocketAddr::from(([127, 0, (client_id >> 8) as u8, client_id as u8], 3000));
+            let mut client = NetcodeClient::new(Duration::ZERO, ClientAuthentication::Secure { connect_token }).unwrap();
+            for _ in 0..2 {
+                let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+                match server.process_packet(addr, client_packet) {
+                    ServerResult::PacketToSend { payload, .. } | ServerResult::ClientConnected { payload, .. } => {
+                        client.process_packet(payload);
+                    }
+                    _ => unreachable!(),
+                }
+                if client.is_disconnected() {
+                    break;
+                }
+            }
+            client
+        };
+
+        for client_id in 0..MAX_CLIENTS as u64 {
+            assert!(connect_client(&mut server, client_id).is_connected());
+        }
+        assert_eq!(server.connected_clients(), MAX_CLIENTS);
+        assert_eq!(server.clients_slot(), (0..MAX_CLIENTS).collect::<Vec<usize>>());
+
+        // Server is full
+        let client = connect_client(&mut server, MAX_CLIENTS as u64);
+        assert_eq!(client.disconnect_reason(), Some(DisconnectReason::ConnectionDenied));
+
+        // The client index is reused by new clients
+        server.disconnect(1050);
+        assert!(!server.is_client_connected(1050));
+        let mut client = connect_client(&mut server, MAX_CLIENTS as u64 + 1);
+        assert!(client.is_connected());
+        let (_, packet) = client.generate_payload_packet(&[1, 2, 3]).unwrap();
+        let addr = server.client_addr(MAX_CLIENTS as u64 + 1).unwrap();
+        match server.process_packet(addr, packet) {
+            ServerResult::Payload { client_id, payload } => {
+                assert_eq!(client_id, MAX_CLIENTS as u64 + 1);
+                assert_eq!(payload, [1, 2, 3]);
+            }
+            _ => unreachable!(),
+        }
+        assert_eq!(server.clients_slot(), (0..MAX_CLIENTS).collect::<Vec<usize>>());
+    }
 }
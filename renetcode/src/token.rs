@@ -7,12 +7,13 @@ use std::{
 };
 
 use crate::{
-    crypto::{dencrypted_in_place_xnonce, encrypt_in_place_xnonce, generate_random_bytes},
+    crypto::{dencrypted_in_place_xnonce, encrypt_in_place_xnonce, generate_random_bytes, CipherSuite},
     serialize::*,
     NetcodeError, NETCODE_ADDITIONAL_DATA_SIZE, NETCODE_ADDRESS_IPV4, NETCODE_ADDRESS_IPV6, NETCODE_ADDRESS_NONE,
-    NETCODE_CONNECT_TOKEN_PRIVATE_BYTES, NETCODE_CONNECT_TOKEN_XNONCE_BYTES, NETCODE_KEY_BYTES, NETCODE_TIMEOUT_SECONDS,
+    NETCODE_CONNECT_TOKEN_BYTES, NETCODE_CONNECT_TOKEN_PRIVATE_BYTES, NETCODE_CONNECT_TOKEN_XNONCE_BYTES, NETCODE_KEY_BYTES,
     NETCODE_USER_DATA_BYTES, NETCODE_VERSION_INFO,
 };
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use chacha20poly1305::aead::Error as CryptoError;
 
 /// A public connect token that the client receives to start connecting to the server.
@@ -34,6 +35,7 @@ pub struct ConnectToken {
     pub(crate) server_to_client_key: [u8; NETCODE_KEY_BYTES],
     pub(crate) private_data: [u8; NETCODE_CONNECT_TOKEN_PRIVATE_BYTES],
     pub(crate) timeout_seconds: i32,
+    pub(crate) cipher_suite: CipherSuite,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -44,6 +46,7 @@ pub(crate) struct PrivateConnectToken {
     pub client_to_server_key: [u8; NETCODE_KEY_BYTES],
     pub server_to_client_key: [u8; NETCODE_KEY_BYTES],
     pub user_data: [u8; NETCODE_USER_DATA_BYTES], // user defined data specific to this protocol id
+    pub cipher_suite: CipherSuite,                // written in the padding, so standard tokens use the standard cipher
 }
 
 #[derive(Debug)]
@@ -82,9 +85,20 @@ impl fmt::Display for TokenGenerationError {
     }
 }
 
+// The key id is stored in the first bytes of the xnonce, this way the server knows which key to use
+// before decrypting the token without changing the token format. The remaining bytes are still random.
+const NETCODE_KEY_ID_BYTES: usize = 4;
+
+pub(crate) fn key_id_from_xnonce(xnonce: &[u8; NETCODE_CONNECT_TOKEN_XNONCE_BYTES]) -> u32 {
+    let mut key_id = [0u8; NETCODE_KEY_ID_BYTES];
+    key_id.copy_from_slice(&xnonce[..NETCODE_KEY_ID_BYTES]);
+    u32::from_le_bytes(key_id)
+}
+
 impl ConnectToken {
     /// Generate a token to be sent to an client. The user data is available to the server after an
     /// successfull conection. The private key and the protocol id must be the same used in server.
+    /// The token is stamped with the key id 0, use [ConnectToken::generate_with_key_id] when the server has multiple keys.
     #[allow(clippy::too_many_arguments)]
     pub fn generate(
         current_time: Duration,
@@ -95,12 +109,69 @@ impl ConnectToken {
         server_addresses: Vec<SocketAddr>,
         user_data: Option<&[u8; NETCODE_USER_DATA_BYTES]>,
         private_key: &[u8; NETCODE_KEY_BYTES],
+    ) -> Result<Self, TokenGenerationError> {
+        Self::generate_with_key_id(
+            current_time,
+            protocol_id,
+            expire_seconds,
+            client_id,
+            timeout_seconds,
+            server_addresses,
+            user_data,
+            0,
+            private_key,
+        )
+    }
+
+    /// Generate a token encrypted with one of the private keys accepted by the server, identified by the key id.
+    /// This allows rotating keys without invalidating tokens in flight, see [NetcodeServer::add_private_key][crate::NetcodeServer::add_private_key].
+    #[allow(clippy::too_many_arguments)]
+    pub fn generate_with_key_id(
+        current_time: Duration,
+        protocol_id: u64,
+        expire_seconds: u64,
+        client_id: u64,
+        timeout_seconds: i32,
+        server_addresses: Vec<SocketAddr>,
+        user_data: Option<&[u8; NETCODE_USER_DATA_BYTES]>,
+        key_id: u32,
+        private_key: &[u8; NETCODE_KEY_BYTES],
+    ) -> Result<Self, TokenGenerationError> {
+        Self::generate_with_cipher_suite(
+            current_time,
+            protocol_id,
+            expire_seconds,
+            client_id,
+            timeout_seconds,
+            server_addresses,
+            user_data,
+            key_id,
+            CipherSuite::default(),
+            private_key,
+        )
+    }
+
+    /// Generate a token for a connection encrypted with the given cipher suite,
+    /// the server only accepts tokens with the cipher suite in its [ServerConfig::cipher_suite][crate::ServerConfig::cipher_suite].
+    #[allow(clippy::too_many_arguments)]
+    pub fn generate_with_cipher_suite(
+        current_time: Duration,
+        protocol_id: u64,
+        expire_seconds: u64,
+        client_id: u64,
+        timeout_seconds: i32,
+        server_addresses: Vec<SocketAddr>,
+        user_data: Option<&[u8; NETCODE_USER_DATA_BYTES]>,
+        key_id: u32,
+        cipher_suite: CipherSuite,
+        private_key: &[u8; NETCODE_KEY_BYTES],
     ) -> Result<Self, TokenGenerationError> {
         let expire_timestamp = current_time.as_secs() + expire_seconds;
 
-        let private_connect_token = PrivateConnectToken::generate(client_id, timeout_seconds, server_addresses, user_data)?;
+        let private_connect_token = PrivateConnectToken::generate(client_id, timeout_seconds, server_addresses, user_data, cipher_suite)?;
         let mut private_data = [0u8; NETCODE_CONNECT_TOKEN_PRIVATE_BYTES];
-        let xnonce = generate_random_bytes();
+        let mut xnonce: [u8; NETCODE_CONNECT_TOKEN_XNONCE_BYTES] = generate_random_bytes();
+        xnonce[..NETCODE_KEY_ID_BYTES].copy_from_slice(&key_id.to_le_bytes());
         private_connect_token.encode(&mut private_data, protocol_id, expire_timestamp, &xnonce, private_key)?;
 
         Ok(Self {
@@ -114,12 +185,107 @@ impl ConnectToken {
             server_addresses: private_connect_token.server_addresses,
             client_to_server_key: private_connect_token.client_to_server_key,
             server_to_client_key: private_connect_token.server_to_client_key,
-            timeout_seconds: NETCODE_TIMEOUT_SECONDS,
+            timeout_seconds,
+            cipher_suite,
         })
     }
 
+    /// Returns the id of the private key used to encrypt the token.
+    pub fn key_id(&self) -> u32 {
+        key_id_from_xnonce(&self.xnonce)
+    }
+
+    /// Returns the client id, tokens read with [ConnectToken::read_standard] have the client id 0.
+    pub fn client_id(&self) -> u64 {
+        self.client_id
+    }
+
+    /// Returns the protocol id of the game/application, servers with another protocol id reject the token.
+    pub fn protocol_id(&self) -> u64 {
+        self.protocol_id
+    }
+
+    /// Returns when the token was created, in seconds since the UNIX epoch.
+    pub fn create_timestamp(&self) -> u64 {
+        self.create_timestamp
+    }
+
+    /// Returns when the token expires, in seconds since the UNIX epoch.
+    pub fn expire_timestamp(&self) -> u64 {
+        self.expire_timestamp
+    }
+
+    /// Returns the server addresses the client tries to connect to, in order.
+    pub fn server_addresses(&self) -> Vec<SocketAddr> {
+        self.server_addresses.iter().flatten().copied().collect()
+    }
+
+    /// Returns the connection timeout in seconds, negative values disable the timeout.
+    pub fn timeout_seconds(&self) -> i32 {
+        self.timeout_seconds
+    }
+
+    /// Returns the cipher suite used to encrypt the packets of the connection.
+    pub fn cipher_suite(&self) -> CipherSuite {
+        self.cipher_suite
+    }
+
+    /// Encodes the token as an URL-safe base64 string without padding, useful to send tokens in text formats.
+    /// The string contains the same bytes written by [ConnectToken::write].
+    ///
+    /// # Example
+    /// ```
+    /// # use renetcode::ConnectToken;
+    /// # let private_key = [0u8; renetcode::NETCODE_KEY_BYTES];
+    /// # let server_addresses = vec!["127.0.0.1:5000".parse().unwrap()];
+    /// # let current_time = std::time::Duration::ZERO;
+    /// let token = ConnectToken::generate(current_time, 0, 300, 1, 15, server_addresses, None, &private_key).unwrap();
+    /// let encoded: String = token.to_base64();
+    /// let decoded = ConnectToken::from_base64(&encoded).unwrap();
+    /// assert_eq!(token, decoded);
+    /// ```
+    pub fn to_base64(&self) -> String {
+        URL_SAFE_NO_PAD.encode(self.to_bytes())
+    }
+
+    /// Decodes a token from the string generated by [ConnectToken::to_base64].
+    pub fn from_base64(encoded: &str) -> Result<Self, NetcodeError> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(encoded)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Self::from_bytes(&bytes)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        self.write(&mut bytes).expect("writing to a Vec should not fail");
+        bytes
+    }
+
+    /// Reads a token written by [ConnectToken::write], rejecting any bytes left after the token
+    fn from_bytes(mut bytes: &[u8]) -> Result<Self, NetcodeError> {
+        let token = Self::read(&mut bytes)?;
+        if !bytes.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "trailing bytes after the connect token").into());
+        }
+
+        Ok(token)
+    }
+
     pub fn write(&self, writer: &mut impl io::Write) -> Result<(), io::Error> {
         writer.write_all(&self.client_id.to_le_bytes())?;
+        self.write_public(writer)
+    }
+
+    /// Writes the token in the netcode standard format, the same written by the reference implementation,
+    /// padded to [NETCODE_CONNECT_TOKEN_BYTES]. The client id is not included in this format.
+    pub fn write_standard(&self, writer: &mut impl io::Write) -> Result<(), io::Error> {
+        let mut buffer = [0u8; NETCODE_CONNECT_TOKEN_BYTES];
+        self.write_public(&mut Cursor::new(&mut buffer[..]))?;
+        writer.write_all(&buffer)
+    }
+
+    fn write_public(&self, writer: &mut impl io::Write) -> Result<(), io::Error> {
         writer.write_all(&self.version_info)?;
         writer.write_all(&self.protocol_id.to_le_bytes())?;
         writer.write_all(&self.create_timestamp.to_le_bytes())?;
@@ -130,12 +296,24 @@ impl ConnectToken {
         write_server_adresses(writer, &self.server_addresses)?;
         writer.write_all(&self.client_to_server_key)?;
         writer.write_all(&self.server_to_client_key)?;
+        writer.write_all(&[self.cipher_suite.id()])?;
 
         Ok(())
     }
 
     pub fn read(src: &mut impl io::Read) -> Result<Self, NetcodeError> {
         let client_id = read_u64(src)?;
+        Self::read_public(src, client_id)
+    }
+
+    /// Reads a token in the netcode standard format, like the tokens generated by the reference implementation.
+    /// The client id is only available in the private part of the token, so it's read as 0.
+    pub fn read_standard(src: &mut impl io::Read) -> Result<Self, NetcodeError> {
+        let buffer: [u8; NETCODE_CONNECT_TOKEN_BYTES] = read_bytes(src)?;
+        Self::read_public(&mut &buffer[..], 0)
+    }
+
+    fn read_public(src: &mut impl io::Read, client_id: u64) -> Result<Self, NetcodeError> {
         let version_info: [u8; 13] = read_bytes(src)?;
         if &version_info != NETCODE_VERSION_INFO {
             return Err(NetcodeError::InvalidVersion);
@@ -151,6 +329,12 @@ impl ConnectToken {
         let server_addresses = read_server_addresses(src)?;
         let client_to_server_key: [u8; NETCODE_KEY_BYTES] = read_bytes(src)?;
         let server_to_client_key: [u8; NETCODE_KEY_BYTES] = read_bytes(src)?;
+        // Tokens written before the cipher suite was added end after the keys, they use the standard cipher
+        let cipher_suite = match read_u8(src) {
+            Ok(id) => CipherSuite::from_id(id)?,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => CipherSuite::ChaCha20Poly1305,
+            Err(e) => return Err(e.into()),
+        };
 
         Ok(Self {
             client_id,
@@ -164,6 +348,7 @@ impl ConnectToken {
             client_to_server_key,
             server_to_client_key,
             timeout_seconds,
+            cipher_suite,
         })
     }
 }
@@ -174,6 +359,7 @@ impl PrivateConnectToken {
         timeout_seconds: i32,
         server_addresses: Vec<SocketAddr>,
         user_data: Option<&[u8; NETCODE_USER_DATA_BYTES]>,
+        cipher_suite: CipherSuite,
     ) -> Result<Self, TokenGenerationError> {
         if server_addresses.len() > 32 {
             return Err(TokenGenerationError::MaxHostCount);
@@ -202,6 +388,7 @@ impl PrivateConnectToken {
             client_to_server_key,
             server_to_client_key,
             user_data,
+            cipher_suite,
         })
     }
 
@@ -212,6 +399,7 @@ impl PrivateConnectToken {
         writer.write_all(&self.client_to_server_key)?;
         writer.write_all(&self.server_to_client_key)?;
         writer.write_all(&self.user_data)?;
+        writer.write_all(&[self.cipher_suite.id()])?;
 
         Ok(())
     }
@@ -229,6 +417,8 @@ impl PrivateConnectToken {
         let mut user_data = [0u8; 256];
         src.read_exact(&mut user_data)?;
 
+        let cipher_suite = CipherSuite::from_id(read_u8(src)?)?;
+
         Ok(Self {
             client_id,
             timeout_seconds,
@@ -236,6 +426,7 @@ impl PrivateConnectToken {
             client_to_server_key,
             server_to_client_key,
             user_data,
+            cipher_suite,
         })
     }
 
@@ -274,6 +465,53 @@ impl PrivateConnectToken {
     }
 }
 
+/// Human readable formats use the string from [ConnectToken::to_base64], other formats the bytes from [ConnectToken::write].
+#[cfg(feature = "serde")]
+impl serde::Serialize for ConnectToken {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&self.to_base64())
+        } else {
+            serializer.serialize_bytes(&self.to_bytes())
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ConnectToken {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct ConnectTokenVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for ConnectTokenVisitor {
+            type Value = ConnectToken;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a base64 encoded connect token or its bytes")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                ConnectToken::from_base64(value).map_err(E::custom)
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, value: &[u8]) -> Result<Self::Value, E> {
+                ConnectToken::from_bytes(value).map_err(E::custom)
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(ConnectTokenVisitor)
+        } else {
+            deserializer.deserialize_bytes(ConnectTokenVisitor)
+        }
+    }
+}
+
 fn write_server_adresses(writer: &mut impl io::Write, server_addresses: &[Option<SocketAddr>; 32]) -> Result<(), io::Error> {
     let num_server_addresses: u32 = server_addresses.iter().filter(|a| a.is_some()).count() as u32;
     writer.write_all(&num_server_addresses.to_le_bytes())?;
@@ -288,7 +526,8 @@ fn write_server_adresses(writer: &mut impl io::Write, server_addresses: &[Option
             }
             SocketAddr::V6(addr) => {
                 writer.write_all(&NETCODE_ADDRESS_IPV6.to_le_bytes())?;
-                for i in addr.ip().octets() {
+                // The standard writes each segment as a little endian u16
+                for i in addr.ip().segments() {
                     writer.write_all(&i.to_le_bytes())?;
                 }
             }
@@ -302,6 +541,19 @@ fn write_server_adresses(writer: &mut impl io::Write, server_addresses: &[Option
 fn read_server_addresses(src: &mut impl io::Read) -> Result<[Option<SocketAddr>; 32], io::Error> {
     let mut server_addresses = [None; 32];
     let num_server_addresses = read_u32(src)? as usize;
+    if num_server_addresses == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "ConnectToken does not have a server address",
+        ));
+    }
+    if num_server_addresses > server_addresses.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "ConnectToken has more than 32 server addresses",
+        ));
+    }
+
     for server_address in server_addresses.iter_mut().take(num_server_addresses) {
         let host_type = read_u8(src)?;
         match host_type {
@@ -313,8 +565,10 @@ fn read_server_addresses(src: &mut impl io::Read) -> Result<[Option<SocketAddr>;
                 *server_address = Some(addr);
             }
             NETCODE_ADDRESS_IPV6 => {
-                let mut ip = [0u8; 16];
-                src.read_exact(&mut ip)?;
+                let mut ip = [0u16; 8];
+                for segment in ip.iter_mut() {
+                    *segment = read_u16(src)?;
+                }
                 let port = read_u16(src)?;
                 let addr = SocketAddr::new(IpAddr::V6(Ipv6Addr::from(ip)), port);
                 *server_address = Some(addr);
@@ -324,13 +578,6 @@ fn read_server_addresses(src: &mut impl io::Read) -> Result<[Option<SocketAddr>;
         }
     }
 
-    if server_addresses.is_empty() {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "ConnectToken does not have a server address",
-        ));
-    }
-
     Ok(server_addresses)
 }
 
@@ -350,7 +597,7 @@ mod tests {
     #[test]
     fn private_connect_token_serialization() {
         let hosts: Vec<SocketAddr> = vec!["127.0.0.1:8080".parse().unwrap(), "127.0.0.2:3000".parse().unwrap()];
-        let token = PrivateConnectToken::generate(1, 5, hosts, Some(&generate_random_bytes())).unwrap();
+        let token = PrivateConnectToken::generate(1, 5, hosts, Some(&generate_random_bytes()), CipherSuite::Aes256Gcm).unwrap();
         let mut buffer: Vec<u8> = vec![];
 
         token.write(&mut buffer).unwrap();
@@ -362,7 +609,7 @@ mod tests {
     #[test]
     fn private_connect_token_encode_decode() {
         let hosts: Vec<SocketAddr> = vec!["127.0.0.1:8080".parse().unwrap(), "127.0.0.2:3000".parse().unwrap()];
-        let token = PrivateConnectToken::generate(1, 5, hosts, Some(&generate_random_bytes())).unwrap();
+        let token = PrivateConnectToken::generate(1, 5, hosts, Some(&generate_random_bytes()), CipherSuite::default()).unwrap();
         let key = b"an example very very secret key."; // 32-bytes
         let protocol_id = 12;
         let expire_timestamp = 0;
@@ -409,6 +656,7 @@ mod tests {
             private_key,
         )
         .unwrap();
+        assert_eq!(timeout_seconds, token.timeout_seconds);
         assert_eq!(timeout_seconds, private.timeout_seconds);
         assert_eq!(client_id, private.client_id);
         assert_eq!(user_data, private.user_data);
@@ -416,4 +664,96 @@ mod tests {
         assert_eq!(token.client_to_server_key, private.client_to_server_key);
         assert_eq!(token.server_to_client_key, private.server_to_client_key);
     }
+
+    fn test_token() -> ConnectToken {
+        let server_addresses: Vec<SocketAddr> = vec!["127.0.0.1:8080".parse().unwrap(), "[::1]:3000".parse().unwrap()];
+        let private_key = b"an example very very secret key."; // 32-bytes
+        ConnectToken::generate(Duration::from_secs(10), 2, 30, 4, 5, server_addresses, None, private_key).unwrap()
+    }
+
+    #[test]
+    fn connect_token_accessors() {
+        let token = test_token();
+        assert_eq!(token.client_id(), 4);
+        assert_eq!(token.protocol_id(), 2);
+        assert_eq!(token.create_timestamp(), 10);
+        assert_eq!(token.expire_timestamp(), 40);
+        assert_eq!(token.timeout_seconds(), 5);
+        assert_eq!(
+            token.server_addresses(),
+            vec!["127.0.0.1:8080".parse::<SocketAddr>().unwrap(), "[::1]:3000".parse().unwrap()]
+        );
+    }
+
+    #[test]
+    fn connect_token_cipher_suite() {
+        let server_addresses: Vec<SocketAddr> = vec!["127.0.0.1:8080".parse().unwrap()];
+        let private_key = b"an example very very secret key."; // 32-bytes
+        let token = ConnectToken::generate_with_cipher_suite(
+            Duration::ZERO,
+            2,
+            30,
+            4,
+            5,
+            server_addresses,
+            None,
+            0,
+            CipherSuite::Aes256Gcm,
+            private_key,
+        )
+        .unwrap();
+        assert_eq!(token.cipher_suite(), CipherSuite::Aes256Gcm);
+        assert_eq!(test_token().cipher_suite(), CipherSuite::ChaCha20Poly1305);
+
+        let private = PrivateConnectToken::decode(&token.private_data, 2, token.expire_timestamp, &token.xnonce, private_key).unwrap();
+        assert_eq!(private.cipher_suite, CipherSuite::Aes256Gcm);
+
+        let mut buffer: Vec<u8> = vec![];
+        token.write_standard(&mut buffer).unwrap();
+        let mut result = ConnectToken::read_standard(&mut buffer.as_slice()).unwrap();
+        result.client_id = token.client_id;
+        assert_eq!(result, token);
+
+        // Unknown cipher suites are rejected
+        let mut buffer: Vec<u8> = vec![];
+        token.write(&mut buffer).unwrap();
+        *buffer.last_mut().unwrap() = 2;
+        assert!(ConnectToken::read(&mut buffer.as_slice()).is_err());
+
+        // Tokens without the cipher suite byte use the standard cipher
+        let token = test_token();
+        let mut buffer: Vec<u8> = vec![];
+        token.write(&mut buffer).unwrap();
+        buffer.pop();
+        assert_eq!(ConnectToken::read(&mut buffer.as_slice()).unwrap(), token);
+    }
+
+    #[test]
+    fn connect_token_base64() {
+        let token = test_token();
+        let encoded = token.to_base64();
+        assert!(encoded.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+        assert_eq!(ConnectToken::from_base64(&encoded).unwrap(), token);
+
+        assert!(ConnectToken::from_base64("not a token!").is_err());
+        assert!(ConnectToken::from_base64(&encoded[..encoded.len() / 2]).is_err());
+
+        let mut bytes = token.to_bytes();
+        bytes.push(0);
+        assert!(ConnectToken::from_base64(&URL_SAFE_NO_PAD.encode(bytes)).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn connect_token_serde() {
+        let token = test_token();
+        let json = serde_json::to_string(&token).unwrap();
+        assert_eq!(json, format!("\"{}\"", token.to_base64()));
+        assert_eq!(serde_json::from_str::<ConnectToken>(&json).unwrap(), token);
+
+        let bytes = token.to_bytes();
+        let deserializer = serde::de::value::BytesDeserializer::<serde::de::value::Error>::new(&bytes);
+        let result: ConnectToken = serde::Deserialize::deserialize(deserializer).unwrap();
+        assert_eq!(result, token);
+    }
 }
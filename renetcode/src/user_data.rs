@@ -0,0 +1,98 @@
+use std::{error, fmt};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::NETCODE_USER_DATA_BYTES;
+
+/// Errors when encoding or decoding a value in the connect token user data.
+#[derive(Debug)]
+pub enum UserDataError {
+    /// The serialized value does not fit in the user data.
+    TooLarge { size: u64 },
+    /// The value could not be serialized or deserialized.
+    Serialization(bincode::Error),
+}
+
+impl fmt::Display for UserDataError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use UserDataError::*;
+
+        match *self {
+            TooLarge { size } => write!(
+                fmt,
+                "serialized user data has {} bytes, the maximum is {} bytes",
+                size, NETCODE_USER_DATA_BYTES
+            ),
+            Serialization(ref err) => write!(fmt, "{}", err),
+        }
+    }
+}
+
+impl error::Error for UserDataError {}
+
+impl From<bincode::Error> for UserDataError {
+    fn from(inner: bincode::Error) -> Self {
+        UserDataError::Serialization(inner)
+    }
+}
+
+/// Serializes a value with bincode into the user data of a connect token, the remaining bytes are zeroed.
+/// Use [decode_user_data] with the same type to read it on the server.
+///
+/// # Example
+/// ```
+/// # use renetcode::{decode_user_data, encode_user_data};
+/// #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+/// struct Account {
+///     id: u64,
+///     username: String,
+/// }
+///
+/// let account = Account { id: 42, username: "renet".to_string() };
+/// let user_data = encode_user_data(&account).unwrap();
+/// let decoded: Account = decode_user_data(&user_data).unwrap();
+/// assert_eq!(account, decoded);
+/// ```
+pub fn encode_user_data<T: Serialize + ?Sized>(value: &T) -> Result<[u8; NETCODE_USER_DATA_BYTES], UserDataError> {
+    let size = bincode::serialized_size(value)?;
+    if size > NETCODE_USER_DATA_BYTES as u64 {
+        return Err(UserDataError::TooLarge { size });
+    }
+
+    let mut user_data = [0u8; NETCODE_USER_DATA_BYTES];
+    bincode::serialize_into(&mut user_data[..], value)?;
+    Ok(user_data)
+}
+
+/// Deserializes a value written with [encode_user_data], the trailing zeroed bytes are ignored.
+pub fn decode_user_data<T: DeserializeOwned>(user_data: &[u8; NETCODE_USER_DATA_BYTES]) -> Result<T, UserDataError> {
+    Ok(bincode::deserialize(user_data)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_data_size_limit() {
+        let username = "a".repeat(NETCODE_USER_DATA_BYTES - 8);
+        let user_data = encode_user_data(&username).unwrap();
+        assert_eq!(decode_user_data::<String>(&user_data).unwrap(), username);
+
+        let username = "a".repeat(NETCODE_USER_DATA_BYTES - 7);
+        assert!(matches!(
+            encode_user_data(&username),
+            Err(UserDataError::TooLarge { size }) if size == NETCODE_USER_DATA_BYTES as u64 + 1
+        ));
+    }
+
+    #[test]
+    fn user_data_invalid() {
+        let mut user_data = [0u8; NETCODE_USER_DATA_BYTES];
+        user_data[..8].copy_from_slice(&1000u64.to_le_bytes());
+        assert!(matches!(
+            decode_user_data::<String>(&user_data),
+            Err(UserDataError::Serialization(_))
+        ));
+    }
+}
@@ -0,0 +1,174 @@
+#!/usr/bin/env python3
+"""Generates the netcode 1.02 conformance vectors used by the renetcode tests.
+
+The vectors are built from the byte layouts described in the netcode standard
+(https://github.com/networkprotocol/netcode/blob/master/STANDARD.md), the same layouts
+written by the reference C implementation, without using renetcode itself.
+Encryption uses the ChaCha20-Poly1305 implementation from the `cryptography` package,
+XChaCha20-Poly1305 is built on top of it with HChaCha20.
+
+Usage: python3 generate.py (writes the .bin files next to this script)
+"""
+
+import ipaddress
+import os
+import struct
+
+from cryptography.hazmat.primitives.ciphers.aead import ChaCha20Poly1305
+
+VERSION_INFO = b"NETCODE 1.02\x00"
+CONNECT_TOKEN_BYTES = 2048
+CONNECT_TOKEN_PRIVATE_BYTES = 1024
+CHALLENGE_TOKEN_BYTES = 300
+MAC_BYTES = 16
+
+# Inputs, must match renetcode/src/conformance.rs
+PROTOCOL_ID = 0x1122334455667788
+CREATE_TIMESTAMP = 1_000_000
+EXPIRE_TIMESTAMP = 1_000_300
+TIMEOUT_SECONDS = 15
+CLIENT_ID = 0x0102030405060708
+SERVER_ADDRESSES = [("127.0.0.1", 40000), ("2001:db8::1", 50000)]
+PRIVATE_KEY = bytes(range(32))
+CLIENT_TO_SERVER_KEY = bytes(range(0xA0, 0xC0))
+SERVER_TO_CLIENT_KEY = bytes(range(0xC0, 0xE0))
+XNONCE = bytes(range(0x40, 0x58))
+USER_DATA = bytes(i % 256 for i in range(256))
+CHALLENGE_KEY = bytes(range(0x60, 0x80))
+CHALLENGE_SEQUENCE = 0x1234
+PAYLOAD = bytes(range(100))
+
+
+def u8(v):
+    return struct.pack("<B", v)
+
+
+def u16(v):
+    return struct.pack("<H", v)
+
+
+def u32(v):
+    return struct.pack("<I", v)
+
+
+def i32(v):
+    return struct.pack("<i", v)
+
+
+def u64(v):
+    return struct.pack("<Q", v)
+
+
+def rotl32(v, c):
+    return ((v << c) & 0xFFFFFFFF) | (v >> (32 - c))
+
+
+def quarter_round(s, a, b, c, d):
+    s[a] = (s[a] + s[b]) & 0xFFFFFFFF
+    s[d] = rotl32(s[d] ^ s[a], 16)
+    s[c] = (s[c] + s[d]) & 0xFFFFFFFF
+    s[b] = rotl32(s[b] ^ s[c], 12)
+    s[a] = (s[a] + s[b]) & 0xFFFFFFFF
+    s[d] = rotl32(s[d] ^ s[a], 8)
+    s[c] = (s[c] + s[d]) & 0xFFFFFFFF
+    s[b] = rotl32(s[b] ^ s[c], 7)
+
+
+def hchacha20(key, nonce16):
+    state = [0x61707865, 0x3320646E, 0x79622D32, 0x6B206574]
+    state += list(struct.unpack("<8I", key))
+    state += list(struct.unpack("<4I", nonce16))
+    for _ in range(10):
+        quarter_round(state, 0, 4, 8, 12)
+        quarter_round(state, 1, 5, 9, 13)
+        quarter_round(state, 2, 6, 10, 14)
+        quarter_round(state, 3, 7, 11, 15)
+        quarter_round(state, 0, 5, 10, 15)
+        quarter_round(state, 1, 6, 11, 12)
+        quarter_round(state, 2, 7, 8, 13)
+        quarter_round(state, 3, 4, 9, 14)
+    return struct.pack("<8I", *(state[0:4] + state[12:16]))
+
+
+def encrypt(key, sequence, data, aad):
+    nonce = bytes(4) + u64(sequence)
+    return ChaCha20Poly1305(key).encrypt(nonce, data, aad)
+
+
+def encrypt_xnonce(key, xnonce, data, aad):
+    subkey = hchacha20(key, xnonce[:16])
+    nonce = bytes(4) + xnonce[16:]
+    return ChaCha20Poly1305(subkey).encrypt(nonce, data, aad)
+
+
+def write_addresses(addresses):
+    out = u32(len(addresses))
+    for host, port in addresses:
+        ip = ipaddress.ip_address(host)
+        if ip.version == 4:
+            out += u8(1) + ip.packed
+        else:
+            # Written as 8 little endian u16 values, like netcode_write_address
+            out += u8(2) + b"".join(u16(v) for v in struct.unpack(">8H", ip.packed))
+        out += u16(port)
+    return out
+
+
+def pad(data, size):
+    assert len(data) <= size
+    return data + bytes(size - len(data))
+
+
+def private_connect_token():
+    data = u64(CLIENT_ID) + i32(TIMEOUT_SECONDS) + write_addresses(SERVER_ADDRESSES)
+    data += CLIENT_TO_SERVER_KEY + SERVER_TO_CLIENT_KEY + USER_DATA
+    data = pad(data, CONNECT_TOKEN_PRIVATE_BYTES - MAC_BYTES)
+    aad = VERSION_INFO + u64(PROTOCOL_ID) + u64(EXPIRE_TIMESTAMP)
+    return encrypt_xnonce(PRIVATE_KEY, XNONCE, data, aad)
+
+
+def connect_token(private_data):
+    data = VERSION_INFO + u64(PROTOCOL_ID) + u64(CREATE_TIMESTAMP) + u64(EXPIRE_TIMESTAMP) + XNONCE + private_data
+    data += i32(TIMEOUT_SECONDS) + write_addresses(SERVER_ADDRESSES) + CLIENT_TO_SERVER_KEY + SERVER_TO_CLIENT_KEY
+    return pad(data, CONNECT_TOKEN_BYTES)
+
+
+def challenge_token():
+    data = pad(u64(CLIENT_ID) + USER_DATA, CHALLENGE_TOKEN_BYTES - MAC_BYTES)
+    return encrypt(CHALLENGE_KEY, CHALLENGE_SEQUENCE, data, b"")
+
+
+def sequence_bytes(sequence):
+    return max(1, (sequence.bit_length() + 7) // 8)
+
+
+def packet(packet_type, sequence, payload):
+    prefix = packet_type | (sequence_bytes(sequence) << 4)
+    aad = VERSION_INFO + u64(PROTOCOL_ID) + u8(prefix)
+    header = u8(prefix) + u64(sequence)[: sequence_bytes(sequence)]
+    return header + encrypt(CLIENT_TO_SERVER_KEY, sequence, payload, aad)
+
+
+def main():
+    directory = os.path.dirname(os.path.abspath(__file__))
+    private_data = private_connect_token()
+    challenge = challenge_token()
+    vectors = {
+        "private_connect_token.bin": private_data,
+        "connect_token.bin": connect_token(private_data),
+        "challenge_token.bin": challenge,
+        "connection_request.bin": u8(0) + VERSION_INFO + u64(PROTOCOL_ID) + u64(EXPIRE_TIMESTAMP) + XNONCE + private_data,
+        "connection_denied.bin": packet(1, 0, b""),
+        "challenge.bin": packet(2, 0x0102, u64(CHALLENGE_SEQUENCE) + challenge),
+        "response.bin": packet(3, 0x030405, u64(CHALLENGE_SEQUENCE) + challenge),
+        "keep_alive.bin": packet(4, 0x0102030405060708, u32(3) + u32(64)),
+        "payload.bin": packet(5, 1000, PAYLOAD),
+        "disconnect.bin": packet(6, 255, b""),
+    }
+    for name, data in vectors.items():
+        with open(os.path.join(directory, name), "wb") as f:
+            f.write(data)
+
+
+if __name__ == "__main__":
+    main()